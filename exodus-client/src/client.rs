@@ -1,62 +1,469 @@
-use exodus_common::{net::{connection::Connection, network_message::NetworkMessage}, consts::EXODUS_DIRECTORY};
+use std::{collections::{HashMap, VecDeque}, io::Error as IoError, os::fd::{AsFd, AsRawFd, BorrowedFd}, time::{Duration, Instant}};
+use exodus_common::{enums::{PixelFormat, PowerState, SurfaceTransform}, graphics::{buffer::DmaBuf, gamma::GammaRamp}, net::{connection::Connection, network_message::NetworkMessage, protocol_error::ProtocolErrorReply}, consts::{EXODUS_DIRECTORY, EXODUS_DISPLAY}};
 use exodus_errors::ErrorKind;
-use exodus_protocols::protocol_code::ProtocolCode::*;
+use exodus_protocols::{protocol_code::{ProtocolCode, PROTOCOL_VERSION_MIN, PROTOCOL_VERSION_MAX}, messages::*, wire::{Fd, Request, Utf16String}};
+use crate::utils::{Cursor, Display, GPU, Mode, Modes, Monitor, Region, Screen, SharedMemory};
 
+/// Longest wait for a reply before the server is considered stuck.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub struct Metadata {
     pub class: String,
     pub title: String,
+    /// Newest protocol version the entity can speak.
     pub version: u32,
+    /// Oldest protocol version the entity can speak.
+    pub min_version: u32,
     pub author: String,
     pub description: String,
+    /// Optional protocol extensions the entity wants to use.
+    pub extensions: Vec<String>,
+}
+
+impl Metadata {
+    /// Metadata speaking every protocol version known to this build, without extensions.
+    pub fn new(class: &str, title: &str, author: &str, description: &str) -> Self {
+        Self {
+            class: class.to_string(),
+            title: title.to_string(),
+            version: PROTOCOL_VERSION_MAX,
+            min_version: PROTOCOL_VERSION_MIN,
+            author: author.to_string(),
+            description: description.to_string(),
+            extensions: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Entity {
-    conn: Connection
+    display: i32,
+    conn: Connection,
+    serial: u32,
+    replies: HashMap<u32, NetworkMessage>,
+    events: VecDeque<NetworkMessage>,
+    last_error: Option<ProtocolErrorReply>,
+    version: u32,
+    capabilities: Vec<String>,
+    extensions: Vec<String>,
 }
 
 impl Entity {
     #[inline]
-    fn new(conn: Connection) -> Self {
+    fn new(display: i32, conn: Connection) -> Self {
         Self {
+            display,
             conn,
+            serial: 0,
+            replies: HashMap::new(),
+            events: VecDeque::new(),
+            last_error: None,
+            version: 0,
+            capabilities: Vec::new(),
+            extensions: Vec::new(),
         }
     }
 
+    /// Connects to the display `dpy`, e.g. "exodus-0".
+    /// 
+    /// Without `dpy` the `EXODUS_DISPLAY` environment variable is used, then "exodus-0".
     pub fn connect(dpy: Option<String>, metadata: Metadata) -> Result<Self, ErrorKind> {
-        if let Some(dpy) = dpy {
-            let conn = Connection::connect(&format!("{}/{}", EXODUS_DIRECTORY, dpy))?;
-            let mut entity = Self::new(conn);
-            entity.set_metadata(metadata);
-            return Ok(entity); 
-        }
+        let dpy = dpy
+            .or_else(|| std::env::var(EXODUS_DISPLAY).ok())
+            .unwrap_or_else(|| "exodus-0".to_string());
 
-        let mut entity = Self::new(Connection::connect(&format!("{}/exodus-0", EXODUS_DIRECTORY))?);
-        entity.set_metadata(metadata);
+        let display = dpy.trim_start_matches("exodus-").parse::<i32>().unwrap_or_default();
+        let mut conn = Connection::connect(&format!("{}/{}", EXODUS_DIRECTORY, dpy))?;
+        conn.set_nonblocking(true);
+
+        let mut entity = Self::new(display, conn);
+        entity.set_metadata(metadata)?;
 
         Ok(entity)
     }
 
+    /// Lists the id of every GPU of the display.
+    pub fn enumerate_gpus(&mut self) -> Result<Vec<i32>, ErrorKind> {
+        Ok(self.call(&EnumerateGPUsRequest {})?.gpus)
+    }
+
+    /// Describes the GPU `gpu`, fails with `GPU_NOT_FOUND` for unknown ids.
+    pub fn gpu_info(&mut self, gpu: i32) -> Result<GPU, ErrorKind> {
+        let reply = self.call(&GPUInfoRequest { gpu })?;
+        Ok(GPU::from(reply))
+    }
+
+    /// Lists the id of every screen connected to the GPU `gpu`.
+    pub fn enumerate_screens(&mut self, gpu: i32) -> Result<Vec<u32>, ErrorKind> {
+        Ok(self.call(&EnumerateScreensRequest { gpu })?.screens)
+    }
+
+    /// Describes the screen `screen` of the GPU `gpu`, fails with `GPU_NOT_FOUND` or `SCREEN_NOT_FOUND`.
+    pub fn screen_info(&mut self, gpu: i32, screen: u32) -> Result<Screen, ErrorKind> {
+        let reply = self.call(&ScreenInfoRequest { gpu, screen })?;
+        Ok(Screen::from_reply(gpu, reply))
+    }
+
+    /// Lists the modes of the screen `screen` of the GPU `gpu`, requires protocol 1.1.0.
+    pub fn modes(&mut self, gpu: i32, screen: u32) -> Result<Modes, ErrorKind> {
+        let reply = self.call(&EnumerateModesRequest { gpu, screen })?;
+        Ok(Modes { current: reply.current, modes: reply.modes.into_iter().map(Mode::from).collect() })
+    }
+
+    /// Switches the screen `screen` to the mode at `mode`, fails with `MODE_NOT_FOUND` for unknown indexes.
+    /// 
+    /// The other entities receive a `ProtocolScreenModeChanged` event.
+    pub fn set_mode(&mut self, gpu: i32, screen: u32, mode: u32) -> Result<Mode, ErrorKind> {
+        Ok(Mode::from(self.call(&SetModeRequest { gpu, screen, mode })?.mode))
+    }
+
+    /// Describes the monitor of the screen `screen`, `None` when it has no EDID, requires protocol 1.2.0.
+    pub fn monitor(&mut self, gpu: i32, screen: u32) -> Result<Option<Monitor>, ErrorKind> {
+        let reply = self.call(&ScreenDetailsRequest { gpu, screen })?;
+
+        match reply.has_edid {
+            true => Ok(Some(Monitor::from(reply))),
+            false => Ok(None),
+        }
+    }
+
+    /// Reads the gamma ramp of the screen `screen`, requires protocol 1.2.0.
+    pub fn gamma(&mut self, gpu: i32, screen: u32) -> Result<GammaRamp, ErrorKind> {
+        let reply = self.call(&GetGammaRequest { gpu, screen })?;
+        GammaRamp::new(reply.red, reply.green, reply.blue)
+    }
+
+    /// Programs the gamma ramp of the screen `screen`, it must have as many entries as `gamma` returns.
+    pub fn set_gamma(&mut self, gpu: i32, screen: u32, ramp: &GammaRamp) -> Result<(), ErrorKind> {
+        self.call(&SetGammaRequest { gpu, screen, red: ramp.red.clone(), green: ramp.green.clone(), blue: ramp.blue.clone() })?;
+        Ok(())
+    }
+
+    /// Shifts the white point of the screen `screen` to `temperature` kelvin, 6500 is neutral.
+    pub fn set_color_temperature(&mut self, gpu: i32, screen: u32, temperature: u32, gamma: f32, brightness: f32) -> Result<(), ErrorKind> {
+        self.call(&SetColorTemperatureRequest { gpu, screen, temperature, gamma, brightness })?;
+        Ok(())
+    }
+
+    /// Reads the power state of the screen `screen`, requires protocol 1.2.0.
+    pub fn power_state(&mut self, gpu: i32, screen: u32) -> Result<PowerState, ErrorKind> {
+        PowerState::try_from(self.call(&GetPowerStateRequest { gpu, screen })?.state)
+    }
+
+    /// Powers the screen `screen` on or down.
+    /// 
+    /// The other entities receive a `ProtocolPowerStateChanged` event.
+    pub fn set_power_state(&mut self, gpu: i32, screen: u32, state: PowerState) -> Result<(), ErrorKind> {
+        self.call(&SetPowerStateRequest { gpu, screen, state: state as u32 })?;
+        Ok(())
+    }
+
+    /// Powers every screen off after `timeout` without cursor activity, `None` keeps them on.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) -> Result<(), ErrorKind> {
+        let seconds = timeout.map(|timeout| timeout.as_secs().clamp(1, u32::MAX as u64) as u32).unwrap_or_default();
+        self.call(&SetIdleTimeoutRequest { seconds })?;
+        Ok(())
+    }
+
+    /// The part of the desktop each screen shows, requires protocol 1.2.0.
+    pub fn layout(&mut self) -> Result<Vec<Region>, ErrorKind> {
+        self.call(&GetLayoutRequest {})?.screens.into_iter().map(Region::try_from).collect()
+    }
+
+    /// Moves the screen `screen` to `x`, `y` of the desktop at `scale` pixels per unit.
+    /// 
+    /// The other entities receive a `ProtocolLayoutChanged` event.
+    pub fn set_screen_position(&mut self, gpu: i32, screen: u32, x: i32, y: i32, scale: f32) -> Result<(), ErrorKind> {
+        self.call(&SetScreenPositionRequest { gpu, screen, x, y, scale })?;
+        Ok(())
+    }
+
+    /// Makes the screen `screen` mirror `source`, given as `(gpu, screen)`, or stop when `None`.
+    pub fn set_mirror(&mut self, gpu: i32, screen: u32, source: Option<(i32, u32)>) -> Result<(), ErrorKind> {
+        let (source_gpu, source_screen) = source.unwrap_or_default();
+        self.call(&SetMirrorRequest { gpu, screen, mirroring: source.is_some(), source_gpu, source_screen })?;
+        Ok(())
+    }
+
+    /// Rotates or flips the picture of the screen `screen`.
+    /// 
+    /// Returns the size of the screen in the new orientation. The other
+    /// entities receive a `ProtocolLayoutChanged` event.
+    pub fn set_transform(&mut self, gpu: i32, screen: u32, transform: SurfaceTransform) -> Result<(u32, u32), ErrorKind> {
+        let reply = self.call(&SetTransformRequest { gpu, screen, transform: transform as u32 })?;
+        Ok((reply.width, reply.height))
+    }
+
+    /// Shares `memory` with the display as a pool, returns the id of the pool.
+    pub fn create_pool(&mut self, memory: &SharedMemory) -> Result<u32, ErrorKind> {
+        self.create_pool_from_fd(memory.as_fd(), memory.size())
+    }
+
+    /// Shares the first `size` bytes of the file `fd` with the display, the file must stay that long.
+    pub fn create_pool_from_fd(&mut self, fd: BorrowedFd, size: u32) -> Result<u32, ErrorKind> {
+        Ok(self.call(&CreatePoolRequest { size, fd: Fd::dup(fd)? })?.pool)
+    }
+
+    /// Destroys a pool, its buffers stay usable.
+    pub fn destroy_pool(&mut self, pool: u32) -> Result<(), ErrorKind> {
+        self.call(&DestroyPoolRequest { pool })?;
+        Ok(())
+    }
+
+    /// Creates a `width`x`height` buffer at `offset` of `pool`, rows are `stride` bytes apart.
+    pub fn create_buffer(&mut self, pool: u32, offset: u32, width: u32, height: u32, stride: u32, format: PixelFormat) -> Result<u32, ErrorKind> {
+        Ok(self.call(&CreateBufferRequest { pool, offset, width, height, stride, format: format as u32 })?.buffer)
+    }
+
+    pub fn destroy_buffer(&mut self, buffer: u32) -> Result<(), ErrorKind> {
+        self.call(&DestroyBufferRequest { buffer })?;
+        Ok(())
+    }
+
+    /// Imports a buffer drawn on the GPU `gpu`, see `Buffer::export`, the file descriptors stay owned by `dmabuf`.
+    /// 
+    /// Requires the `exodus_dmabuf` extension, requested in [`Metadata::extensions`].
+    pub fn create_dmabuf_buffer(&mut self, gpu: i32, dmabuf: &DmaBuf) -> Result<u32, ErrorKind> {
+        let planes = dmabuf.planes.iter()
+            .map(|plane| Ok(DmabufPlane { fd: Fd::dup(plane.fd.as_fd())?, offset: plane.offset, stride: plane.stride }))
+            .collect::<Result<Vec<_>, ErrorKind>>()?;
+
+        let request = CreateDmabufBufferRequest {
+            gpu,
+            width: dmabuf.width,
+            height: dmabuf.height,
+            format: dmabuf.fourcc,
+            modifier: dmabuf.modifier,
+            planes,
+        };
+
+        Ok(self.call(&request)?.buffer)
+    }
+
+    /// Shows `buffer` at `x`, `y` of the screen `screen`, the pool can be drawn into again once it returns.
+    pub fn attach_buffer(&mut self, gpu: i32, screen: u32, buffer: u32, x: i32, y: i32) -> Result<(), ErrorKind> {
+        self.call(&AttachBufferRequest { gpu, screen, buffer, x, y })?;
+        Ok(())
+    }
+
+    /// Sets the cursor image of the screen `screen`, requires protocol 1.2.0.
+    /// 
+    /// Returns whether the cursor plane shows the cursor, the server composites it otherwise.
+    pub fn set_cursor(&mut self, gpu: i32, screen: u32, cursor: &Cursor) -> Result<bool, ErrorKind> {
+        let request = SetCursorRequest {
+            gpu,
+            screen,
+            width: cursor.width,
+            height: cursor.height,
+            hot_x: cursor.hot_x,
+            hot_y: cursor.hot_y,
+            pixels: cursor.pixels.clone(),
+        };
+
+        Ok(self.call(&request)?.hardware)
+    }
+
+    /// Hides the cursor of the screen `screen`.
+    pub fn hide_cursor(&mut self, gpu: i32, screen: u32) -> Result<(), ErrorKind> {
+        self.call(&SetCursorRequest { gpu, screen, width: 0, height: 0, hot_x: 0, hot_y: 0, pixels: Vec::new() })?;
+        Ok(())
+    }
+
+    /// Moves the hotspot of the cursor of the screen `screen` to `x`, `y`.
+    pub fn move_cursor(&mut self, gpu: i32, screen: u32, x: i32, y: i32) -> Result<(), ErrorKind> {
+        self.call(&MoveCursorRequest { gpu, screen, x, y })?;
+        Ok(())
+    }
+
+    /// Describes the whole display, every GPU with its screens.
+    /// 
+    /// The info requests are pipelined, so this costs three round trips whatever the number of GPUs and screens.
+    pub fn display(&mut self) -> Result<Display, ErrorKind> {
+        let ids = self.enumerate_gpus()?;
+
+        let serials = ids.iter()
+            .map(|gpu| Ok((self.send(&GPUInfoRequest { gpu: *gpu })?, self.send(&EnumerateScreensRequest { gpu: *gpu })?)))
+            .collect::<Result<Vec<_>, ErrorKind>>()?;
+
+        let mut gpus = Vec::with_capacity(ids.len());
+        let mut pending = Vec::new();
+        for (info, screens) in serials {
+            let gpu = GPU::from(self.wait::<GPUInfoRequest>(info)?);
+            for screen in self.wait::<EnumerateScreensRequest>(screens)?.screens {
+                pending.push((gpu.id, self.send(&ScreenInfoRequest { gpu: gpu.id, screen })?));
+            }
+            gpus.push(gpu);
+        }
+
+        let mut screens = Vec::with_capacity(pending.len());
+        for (gpu, serial) in pending {
+            screens.push(Screen::from_reply(gpu, self.wait::<ScreenInfoRequest>(serial)?));
+        }
+
+        Ok(Display { id: self.display, gpus, screens })
+    }
+
+    /// The protocol version negotiated with the server.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The extensions supported by the server.
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    /// The extensions requested by the entity and supported by the server.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
     pub fn disconnect(&mut self) {
         self.conn.disconnect();
     }
 
-    fn request(&mut self, msg: NetworkMessage) -> Result<Option<NetworkMessage>, ErrorKind> {
-        self.conn.send(msg);
-        self.conn.buffer()
+    /// Sends a typed request without waiting, returns the serial to pass to `wait`.
+    ///
+    /// Several requests can be in flight at once, their replies are matched
+    /// by serial in any order.
+    pub fn send<R: Request>(&mut self, request: &R) -> Result<u32, ErrorKind> {
+        self.send_request(NetworkMessage::encode(request))
+    }
+
+    /// Waits for the typed reply of a request sent with `send`.
+    pub fn wait<R: Request>(&mut self, serial: u32) -> Result<R::Reply, ErrorKind> {
+        let mut reply = self.response(serial)?;
+        reply.decode()
+    }
+
+    /// Sends a request without waiting, returns the serial its reply will carry.
+    fn send_request(&mut self, mut msg: NetworkMessage) -> Result<u32, ErrorKind> {
+        let serial = self.next_serial();
+        msg.set_serial(serial);
+        self.conn.send(msg)?;
+        Ok(serial)
+    }
+
+    /// Blocks until the reply, or `ProtocolError`, for `serial` arrives.
+    ///
+    /// Replies to other requests and events received meanwhile are kept. Fails
+    /// with `CONNECTION_TIMEOUT` when nothing arrives within `REPLY_TIMEOUT`.
+    fn wait_reply(&mut self, serial: u32) -> Result<NetworkMessage, ErrorKind> {
+        if let Some(reply) = self.replies.remove(&serial) {
+            return Ok(reply);
+        }
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            let msg = match self.conn.buffer()? {
+                Some(msg) => msg,
+                None => {
+                    self.poll(deadline)?;
+                    continue;
+                },
+            };
+
+            if msg.is_event() {
+                self.events.push_back(msg);
+            }
+            else if msg.serial() == serial {
+                return Ok(msg);
+            }
+            else {
+                self.replies.insert(msg.serial(), msg);
+            }
+        }
+    }
+
+    /// Sleeps until the socket is readable, or writable while requests are queued.
+    fn poll(&mut self, deadline: Instant) -> Result<(), ErrorKind> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            return Err(ErrorKind::CONNECTION_TIMEOUT);
+        }
+
+        let mut events = libc::POLLIN;
+        if self.conn.has_pending_output() {
+            events |= libc::POLLOUT;
+        }
+
+        let mut fd = libc::pollfd { fd: self.conn.as_raw_fd(), events, revents: 0 };
+        let ready = unsafe { libc::poll(&mut fd, 1, timeout.as_millis().min(i32::MAX as u128) as i32) };
+        if ready < 0 {
+            return match IoError::last_os_error().kind() {
+                std::io::ErrorKind::Interrupted => Ok(()),
+                _ => Err(ErrorKind::CONNECTION_CLOSED),
+            };
+        }
+
+        if ready == 0 {
+            return Err(ErrorKind::CONNECTION_TIMEOUT);
+        }
+
+        if fd.revents & libc::POLLOUT != 0 {
+            self.conn.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the oldest event received so far, if any.
+    pub fn poll_event(&mut self) -> Option<NetworkMessage> {
+        self.events.pop_front()
+    }
+
+    /// Returns the last `ProtocolError` received, with the server's detail message.
+    pub fn last_error(&self) -> Option<&ProtocolErrorReply> {
+        self.last_error.as_ref()
+    }
+
+    /// Waits for the reply of `serial`, a `ProtocolError` reply is returned as its `ErrorKind`.
+    fn response(&mut self, serial: u32) -> Result<NetworkMessage, ErrorKind> {
+        let mut reply = self.wait_reply(serial)?;
+
+        if reply.code()? == ProtocolCode::ProtocolError as i32 {
+            let error = ProtocolErrorReply::decode(&mut reply)?;
+            let kind = error.kind();
+            self.last_error = Some(error);
+            return Err(kind);
+        }
+
+        Ok(reply)
+    }
+
+    /// Sends a typed request and waits for its typed reply.
+    fn call<R: Request>(&mut self, request: &R) -> Result<R::Reply, ErrorKind> {
+        let serial = self.send(request)?;
+        self.wait::<R>(serial)
+    }
+
+    /// Serial `0` is reserved for unsolicited events.
+    fn next_serial(&mut self) -> u32 {
+        self.serial = self.serial.wrapping_add(1);
+        if self.serial == 0 {
+            self.serial = 1;
+        }
+        self.serial
     }
 
-    fn set_metadata(&mut self, metadata: Metadata) {
-        let mut msg = NetworkMessage::new(ProtocolEntityRegister);
-        msg.write_string_utf8(&metadata.class);
-        msg.write_string_utf8(&metadata.title);
-        msg.write_u32(metadata.version);
-        msg.write_string_utf8(&metadata.author);
-        msg.write_string_utf8(&metadata.description);
+    /// Registers the entity and negotiates the protocol version with the server.
+    fn set_metadata(&mut self, metadata: Metadata) -> Result<(), ErrorKind> {
+        let request = EntityRegisterRequest {
+            class: metadata.class,
+            title: Utf16String(metadata.title),
+            min_version: metadata.min_version,
+            max_version: metadata.version,
+            author: metadata.author,
+            description: metadata.description,
+            extensions: metadata.extensions,
+        };
 
-        self.conn.send(msg);
+        let reply = self.call(&request)?;
+        self.version = reply.version;
+        self.extensions = request.extensions.into_iter().filter(|x| reply.capabilities.contains(x)).collect();
+        self.capabilities = reply.capabilities;
+        Ok(())
     }
 
 }
\ No newline at end of file
@@ -1,44 +1,286 @@
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
 use exodus_common::enums::*;
+use exodus_errors::ErrorKind;
+use exodus_protocols::messages::{GPUInfoReply, ScreenInfoReply, ScreenDetailsReply, ScreenRegion, ModeInfo, TimingInfo, MODE_FLAG_PREFERRED, MODE_FLAG_INTERLACED};
 
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct Display {
     pub id: i32,
-    pub gpu: i32,
-    pub gpu_count: u32,
-    pub gpus: Vec<i32>,
+    pub gpus: Vec<GPU>,
+    pub screens: Vec<Screen>,
 }
 
 impl Display {
-    pub(crate) fn new(id: i32, gpu: i32, gpu_count: u32, gpus: Vec<i32>) -> Self { Self { id, gpu, gpu_count, gpus } }
+    /// Screens connected to the GPU `gpu`.
+    pub fn screens_of(&self, gpu: i32) -> impl Iterator<Item = &Screen> {
+        self.screens.iter().filter(move |screen| screen.gpu == gpu)
+    }
 }
 
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct GPU {
     pub id: i32,
-    pub vendor: i32,
-    pub screen_count: u32,
-    pub screens: Vec<i32>,
+    pub vendor: Vendor,
+    pub vendor_name: String,
+    pub model: u32,
 }
 
-impl GPU {
-    pub(crate) fn new(id: i32, vendor: i32, screen_count: u32, screens: Vec<i32>) -> Self { Self { id, vendor, screen_count, screens } }
+impl From<GPUInfoReply> for GPU {
+    fn from(reply: GPUInfoReply) -> Self {
+        Self {
+            id: reply.gpu,
+            vendor: Vendor::from(reply.vendor),
+            vendor_name: reply.vendor_name,
+            model: reply.model,
+        }
+    }
 }
 
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct Screen {
-    pub id: i32,
+    pub id: u32,
+    pub gpu: i32,
+    pub width: u32,
+    pub height: u32,
+    pub refresh: u32,
     pub connector_type: ConnectorType,
     pub mm_width: u32,
     pub mm_height: u32,
     pub subpixel: SubPixel,
-    pub mode: u32,
-    pub modes_count: u32,
-    pub modes: Vec<u32>,
+    pub buffer_count: u32,
 }
 
 impl Screen {
-    pub(crate) fn new(id: i32, connector_type: ConnectorType, mm_width: u32, mm_height: u32, subpixel: SubPixel, mode: u32, modes_count: u32, modes: Vec<u32>) -> Self { Self { id, connector_type, mm_width, mm_height, subpixel, mode, modes_count, modes } }
-}
\ No newline at end of file
+    pub(crate) fn from_reply(gpu: i32, reply: ScreenInfoReply) -> Self {
+        Self {
+            id: reply.screen,
+            gpu,
+            width: reply.width,
+            height: reply.height,
+            refresh: reply.refresh,
+            connector_type: ConnectorType::from(reply.connector_type),
+            mm_width: reply.mm_width,
+            mm_height: reply.mm_height,
+            subpixel: SubPixel::from(reply.subpixel),
+            buffer_count: reply.buffer_count,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    /// Index to pass to `set_mode`.
+    pub index: u32,
+    pub width: u32,
+    pub height: u32,
+    pub refresh: u32,
+    pub preferred: bool,
+    pub interlaced: bool,
+}
+
+impl From<ModeInfo> for Mode {
+    fn from(info: ModeInfo) -> Self {
+        Self {
+            index: info.index,
+            width: info.width,
+            height: info.height,
+            refresh: info.refresh,
+            preferred: info.flags & MODE_FLAG_PREFERRED != 0,
+            interlaced: info.flags & MODE_FLAG_INTERLACED != 0,
+        }
+    }
+}
+
+/// The modes of a screen and the index of the one in use.
+#[derive(Debug, Clone)]
+pub struct Modes {
+    pub current: u32,
+    pub modes: Vec<Mode>,
+}
+
+impl Modes {
+    pub fn current(&self) -> Option<&Mode> {
+        self.modes.iter().find(|mode| mode.index == self.current)
+    }
+}
+
+/// A cursor image, ARGB pixels row by row, and the position of its hotspot in it.
+#[derive(Debug, Clone, Default)]
+pub struct Cursor {
+    pub width: u32,
+    pub height: u32,
+    pub hot_x: i32,
+    pub hot_y: i32,
+    pub pixels: Vec<u32>,
+}
+
+/// The part of the desktop a screen shows, in desktop units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub gpu: i32,
+    pub screen: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Pixels per desktop unit.
+    pub scale: f32,
+    /// `width` and `height` are in its orientation.
+    pub transform: SurfaceTransform,
+    /// `(gpu, screen)` of the screen this one mirrors.
+    pub mirror: Option<(i32, u32)>,
+}
+
+impl TryFrom<ScreenRegion> for Region {
+    type Error = ErrorKind;
+
+    /// Fails with `TRANSFORM_INVALID` for a transform this version does not know.
+    fn try_from(region: ScreenRegion) -> Result<Self, Self::Error> {
+        Ok(Self {
+            gpu: region.gpu,
+            screen: region.screen,
+            x: region.x,
+            y: region.y,
+            width: region.width,
+            height: region.height,
+            scale: region.scale,
+            transform: SurfaceTransform::try_from(region.transform)?,
+            mirror: region.mirroring.then_some((region.mirror_gpu, region.mirror_screen)),
+        })
+    }
+}
+
+/// Identity and capabilities of the monitor of a screen, from its EDID.
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    /// Stays the same across connectors and reboots.
+    pub identity: String,
+    pub manufacturer: String,
+    pub name: String,
+    pub serial: String,
+    pub product: u32,
+    pub week: u32,
+    pub year: u32,
+    pub bit_depth: u32,
+    pub gamma: f32,
+    /// x and y of the red, green and blue primaries, then of the white point.
+    pub chromaticity: Vec<f32>,
+    /// The native resolution first.
+    pub timings: Vec<TimingInfo>,
+    pub colorimetry: u32,
+    pub hdr: Option<Hdr>,
+}
+
+/// HDR capabilities of a monitor, luminances in cd/m², `0` when unknown.
+#[derive(Debug, Clone, Copy)]
+pub struct Hdr {
+    pub eotfs: u32,
+    pub max_luminance: f32,
+    pub max_frame_average: f32,
+    pub min_luminance: f32,
+}
+
+impl From<ScreenDetailsReply> for Monitor {
+    fn from(reply: ScreenDetailsReply) -> Self {
+        let hdr = match reply.hdr_eotfs {
+            0 => None,
+            eotfs => Some(Hdr {
+                eotfs,
+                max_luminance: reply.max_luminance,
+                max_frame_average: reply.max_frame_average,
+                min_luminance: reply.min_luminance,
+            }),
+        };
+
+        Self {
+            identity: reply.identity,
+            manufacturer: reply.manufacturer,
+            name: reply.name,
+            serial: reply.serial,
+            product: reply.product,
+            week: reply.week,
+            year: reply.year,
+            bit_depth: reply.bit_depth,
+            gamma: reply.gamma,
+            chromaticity: reply.chromaticity,
+            timings: reply.timings,
+            colorimetry: reply.colorimetry,
+            hdr,
+        }
+    }
+}
+
+/// Memory shared with the display through a pool, see `Client::create_pool`.
+///
+/// Backed by a memfd mapped read-write, the display reads the pixels drawn
+/// in it when a buffer of the pool is attached.
+#[derive(Debug)]
+pub struct SharedMemory {
+    fd: OwnedFd,
+    map: *mut u8,
+    size: usize,
+}
+
+impl SharedMemory {
+    pub fn new(size: u32) -> Result<Self, ErrorKind> {
+        let fd = unsafe { libc::memfd_create(c"exodus-pool".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(ErrorKind::SHM_INVALID);
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        if size == 0 || unsafe { libc::ftruncate(fd.as_raw_fd(), size as libc::off_t) } < 0 {
+            return Err(ErrorKind::SHM_INVALID);
+        }
+
+        let map = unsafe {
+            libc::mmap(std::ptr::null_mut(), size as usize, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd.as_raw_fd(), 0)
+        };
+
+        if map == libc::MAP_FAILED {
+            return Err(ErrorKind::SHM_INVALID);
+        }
+
+        Ok(Self { fd, map: map as *mut u8, size: size as usize })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size as u32
+    }
+
+    /// Writes `pixels` as `width` pixels wide rows at `offset`, rows are `stride` bytes apart.
+    pub fn write(&mut self, offset: u32, width: u32, stride: u32, pixels: &[u32]) -> Result<(), ErrorKind> {
+        if width == 0 || !pixels.len().is_multiple_of(width as usize) || stride < width * 4 {
+            return Err(ErrorKind::BUFFER_INVALID_PIXELS);
+        }
+
+        let rows = pixels.len() / width as usize;
+        let end = offset as u64 + stride as u64 * rows.saturating_sub(1) as u64 + width as u64 * 4;
+        if rows > 0 && end > self.size as u64 {
+            return Err(ErrorKind::BUFFER_OUT_OF_BOUNDS);
+        }
+
+        for (index, row) in pixels.chunks_exact(width as usize).enumerate() {
+            let target = offset as usize + index * stride as usize;
+            unsafe { std::ptr::copy_nonoverlapping(row.as_ptr() as *const u8, self.map.add(target), row.len() * 4) };
+        }
+
+        Ok(())
+    }
+}
+
+impl AsFd for SharedMemory {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map as *mut libc::c_void, self.size) };
+    }
+}
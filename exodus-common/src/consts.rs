@@ -3,4 +3,8 @@ pub const EXODUS_DIRECTORY: &'static str      = "/tmp/exodus/";
 pub const EXODUS_LOG_DIRECTORY: &'static str  = "/tmp/exodus/log/";
 pub const EXODUS_DISPLAY: &'static str        = "EXODUS_DISPLAY";
 pub const EXODUS_LOG: &'static str            = "EXODUS_LOG";
+pub const EXODUS_BACKEND: &'static str        = "EXODUS_BACKEND";
+pub const EXODUS_HEADLESS_SCREENS: &'static str = "EXODUS_HEADLESS_SCREENS";
+pub const EXODUS_IDLE_TIMEOUT: &'static str   = "EXODUS_IDLE_TIMEOUT";
+pub const EXODUS_LAYOUT: &'static str         = "EXODUS_LAYOUT";
 pub const EXODUS_FRAMEBUFFER_MAX: usize       = 3;
@@ -1,5 +1,5 @@
 use drm::*;
-use gbm::gbm_bo_format::*;
+use exodus_errors::ErrorKind;
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -95,47 +95,126 @@ impl From<i32> for ConnectorType {
 }
 
 
-#[derive(Debug, Copy, Clone)]
+/// Layout of the pixels of a buffer, the discriminant is the value sent over the protocol.
+/// 
+/// Names follow DRM, components are listed from the most significant bits of
+/// a little-endian pixel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum PixelFormat {
     XRGB8888 = 0,
     ARGB8888,
+    RGB565,
+    XBGR8888,
+    ABGR8888,
+    XRGB2101010,
+    /// Half float components.
+    ARGB16161616F,
+    /// Y plane followed by an interleaved CbCr plane, subsampled 2x2.
+    NV12,
+    /// Y, Cb and Cr planes, the chroma planes subsampled 2x2.
+    YUV420,
 }
 
+/// Every pixel format, in protocol order.
+pub const PIXEL_FORMATS: [PixelFormat; 9] = [
+    PixelFormat::XRGB8888,
+    PixelFormat::ARGB8888,
+    PixelFormat::RGB565,
+    PixelFormat::XBGR8888,
+    PixelFormat::ABGR8888,
+    PixelFormat::XRGB2101010,
+    PixelFormat::ARGB16161616F,
+    PixelFormat::NV12,
+    PixelFormat::YUV420,
+];
+
 impl PixelFormat {
+    /// Bits per pixel of the first plane.
     pub fn bpp(&self) -> u32 {
-        match self {
-            PixelFormat::XRGB8888 => 32,
-            PixelFormat::ARGB8888 => 32,
-            
+        self.plane_bpp(0)
+    }
+
+    /// Bits per pixel of `plane`, counting the pixels of the plane itself when it is subsampled.
+    pub fn plane_bpp(&self, plane: usize) -> u32 {
+        match (self, plane) {
+            (PixelFormat::XRGB8888 | PixelFormat::ARGB8888 | PixelFormat::XBGR8888
+                | PixelFormat::ABGR8888 | PixelFormat::XRGB2101010, 0) => 32,
+            (PixelFormat::RGB565, 0) => 16,
+            (PixelFormat::ARGB16161616F, 0) => 64,
+            (PixelFormat::NV12, 0) => 8,
+            (PixelFormat::NV12, 1) => 16,
+            (PixelFormat::YUV420, 0..=2) => 8,
+            _ => 0,
         }
     }
 
+    /// Bytes per pixel of the first plane.
     pub fn size(&self) -> usize {
+        self.bpp() as usize / 8
+    }
+
+    /// Number of planes of a buffer of this format.
+    pub fn planes(&self) -> usize {
         match self {
-            PixelFormat::XRGB8888 => 4,
-            PixelFormat::ARGB8888 => 4,
+            PixelFormat::NV12 => 2,
+            PixelFormat::YUV420 => 3,
+            _ => 1,
         }
     }
-}
 
-impl From<u32> for PixelFormat {
-    fn from(format: u32) -> Self {
-        match format {
-            GBM_BO_FORMAT_XRGB8888 => PixelFormat::XRGB8888,
-            GBM_BO_FORMAT_ARGB8888 => PixelFormat::ARGB8888,
-            _ => PixelFormat::XRGB8888,
+    /// Horizontal and vertical subsampling of the planes after the first one, `(1, 1)` when not subsampled.
+    pub fn subsampling(&self) -> (u32, u32) {
+        match self {
+            PixelFormat::NV12 | PixelFormat::YUV420 => (2, 2),
+            _ => (1, 1),
         }
     }
-}
 
-impl From<i32> for PixelFormat {
-    fn from(format: i32) -> Self {
-        match format as u32 {
-            GBM_BO_FORMAT_XRGB8888 => PixelFormat::XRGB8888,
-            GBM_BO_FORMAT_ARGB8888 => PixelFormat::ARGB8888,
-            _ => PixelFormat::XRGB8888,
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, PixelFormat::ARGB8888 | PixelFormat::ABGR8888 | PixelFormat::ARGB16161616F)
+    }
+
+    /// The same layout with the alpha channel ignored, when the format has such a variant.
+    pub fn opaque(&self) -> Self {
+        match self {
+            PixelFormat::ARGB8888 => PixelFormat::XRGB8888,
+            PixelFormat::ABGR8888 => PixelFormat::XBGR8888,
+            format => *format,
         }
     }
+
+    /// DRM fourcc code of the format, as used by GBM, KMS planes and DMA-BUFs.
+    pub fn fourcc(&self) -> u32 {
+        let code = match self {
+            PixelFormat::XRGB8888       => b"XR24",
+            PixelFormat::ARGB8888       => b"AR24",
+            PixelFormat::RGB565         => b"RG16",
+            PixelFormat::XBGR8888       => b"XB24",
+            PixelFormat::ABGR8888       => b"AB24",
+            PixelFormat::XRGB2101010    => b"XR30",
+            PixelFormat::ARGB16161616F  => b"AR4H",
+            PixelFormat::NV12           => b"NV12",
+            PixelFormat::YUV420         => b"YU12",
+        };
+
+        u32::from_le_bytes(*code)
+    }
+
+    /// Format of a DRM fourcc code, fails with `PIXEL_FORMAT_UNSUPPORTED` for the other codes.
+    pub fn from_fourcc(fourcc: u32) -> Result<Self, ErrorKind> {
+        PIXEL_FORMATS.into_iter()
+            .find(|format| format.fourcc() == fourcc)
+            .ok_or(ErrorKind::PIXEL_FORMAT_UNSUPPORTED)
+    }
+}
+
+impl TryFrom<u32> for PixelFormat {
+    type Error = ErrorKind;
+
+    /// Format of a protocol value, see `PixelFormat`.
+    fn try_from(format: u32) -> Result<Self, Self::Error> {
+        PIXEL_FORMATS.get(format as usize).copied().ok_or(ErrorKind::PIXEL_FORMAT_UNSUPPORTED)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -162,6 +241,30 @@ impl From<u32> for SubPixel {
     }
 }
 
+/// Power state of a screen, with the values of the DPMS connector property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerState {
+    On      = 0,
+    Standby = 1,
+    Suspend = 2,
+    Off     = 3,
+}
+
+impl TryFrom<u32> for PowerState {
+    type Error = ErrorKind;
+
+    /// State of a DPMS or protocol value, see `PowerState`.
+    fn try_from(state: u32) -> Result<Self, Self::Error> {
+        match state {
+            0 => Ok(PowerState::On),
+            1 => Ok(PowerState::Standby),
+            2 => Ok(PowerState::Suspend),
+            3 => Ok(PowerState::Off),
+            _ => Err(ErrorKind::POWER_STATE_INVALID),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Vendor {
     Unknown     = 0,
@@ -256,7 +359,7 @@ pub enum BufferFlag {
     Scanout
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Planes {
     None = 0,
     
@@ -293,7 +396,10 @@ impl From<i32> for Planes {
 }
 
 
-#[derive(Debug, Copy, Clone)]
+/// How the picture is turned on the panel, rotations are counter-clockwise.
+///
+/// Flips are applied before the rotation, as by the `rotation` plane property.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SurfaceTransform {
     Normal = 0,
     Rotate90,
@@ -303,4 +409,84 @@ pub enum SurfaceTransform {
     FlipVertical,
     Rotate90FlipHorizontal,
     Rotate90FlipVertical,
+}
+
+impl SurfaceTransform {
+    /// Returns `true` when the transform swaps width and height.
+    pub fn is_transposed(&self) -> bool {
+        matches!(self, Self::Rotate90 | Self::Rotate270 | Self::Rotate90FlipHorizontal | Self::Rotate90FlipVertical)
+    }
+
+    /// Size of the transformed picture of a `width`x`height` output.
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.is_transposed() {
+            true => (height, width),
+            false => (width, height),
+        }
+    }
+
+    /// Maps `x`, `y` of a `width`x`height` transformed picture to the pixel of the output showing it.
+    pub fn apply(&self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        let (right, bottom) = (width - 1 - x, height - 1 - y);
+
+        match self {
+            Self::Normal                    => (x, y),
+            Self::Rotate90                  => (y, right),
+            Self::Rotate180                 => (right, bottom),
+            Self::Rotate270                 => (bottom, x),
+            Self::FlipHorizontal            => (right, y),
+            Self::FlipVertical              => (x, bottom),
+            Self::Rotate90FlipHorizontal    => (y, x),
+            Self::Rotate90FlipVertical      => (bottom, right),
+        }
+    }
+
+    /// Maps the pixel `x`, `y` of the output back to its `width`x`height` transformed picture.
+    pub fn invert(&self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::Normal                    => (x, y),
+            Self::Rotate90                  => (width - 1 - y, x),
+            Self::Rotate180                 => (width - 1 - x, height - 1 - y),
+            Self::Rotate270                 => (y, height - 1 - x),
+            Self::FlipHorizontal            => (width - 1 - x, y),
+            Self::FlipVertical              => (x, height - 1 - y),
+            Self::Rotate90FlipHorizontal    => (y, x),
+            Self::Rotate90FlipVertical      => (width - 1 - y, height - 1 - x),
+        }
+    }
+
+    /// Value of the `rotation` plane property applying the transform.
+    pub fn rotation(&self) -> u64 {
+        let rotation = match self {
+            Self::Normal                    => DRM_MODE_ROTATE_0,
+            Self::Rotate90                  => DRM_MODE_ROTATE_90,
+            Self::Rotate180                 => DRM_MODE_ROTATE_180,
+            Self::Rotate270                 => DRM_MODE_ROTATE_270,
+            Self::FlipHorizontal            => DRM_MODE_ROTATE_0 | DRM_MODE_REFLECT_X,
+            Self::FlipVertical              => DRM_MODE_ROTATE_0 | DRM_MODE_REFLECT_Y,
+            Self::Rotate90FlipHorizontal    => DRM_MODE_ROTATE_90 | DRM_MODE_REFLECT_X,
+            Self::Rotate90FlipVertical      => DRM_MODE_ROTATE_90 | DRM_MODE_REFLECT_Y,
+        };
+
+        rotation as u64
+    }
+}
+
+impl TryFrom<u32> for SurfaceTransform {
+    type Error = ErrorKind;
+
+    /// Transform of a protocol value, see `SurfaceTransform`.
+    fn try_from(transform: u32) -> Result<Self, Self::Error> {
+        match transform {
+            0 => Ok(SurfaceTransform::Normal),
+            1 => Ok(SurfaceTransform::Rotate90),
+            2 => Ok(SurfaceTransform::Rotate180),
+            3 => Ok(SurfaceTransform::Rotate270),
+            4 => Ok(SurfaceTransform::FlipHorizontal),
+            5 => Ok(SurfaceTransform::FlipVertical),
+            6 => Ok(SurfaceTransform::Rotate90FlipHorizontal),
+            7 => Ok(SurfaceTransform::Rotate90FlipVertical),
+            _ => Err(ErrorKind::TRANSFORM_INVALID),
+        }
+    }
 }
\ No newline at end of file
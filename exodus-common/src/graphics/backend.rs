@@ -0,0 +1,277 @@
+use std::{fmt::Debug, os::fd::RawFd, sync::Arc};
+use exodus_errors::ErrorKind;
+use crate::enums::{BufferFlag, ConnectorType, PixelFormat, Planes, PowerState, ScreenFlags, SurfaceTransform, Vendor};
+use super::{buffer::{Buffer, DmaBuf}, device::GPUID, edid::Edid, gamma::GammaRamp};
+
+/// A GPU, or anything standing in for one, that the display drives.
+pub trait GraphicsDevice: Debug {
+    fn id(&self) -> GPUID;
+
+    fn vendor(&self) -> Vendor;
+
+    fn model(&self) -> u32;
+
+    /// Allocator for the buffers presented on the outputs of this device.
+    fn allocator(&self) -> Arc<dyn BufferAllocator>;
+
+    /// Enumerates the connected outputs.
+    fn outputs(&mut self, flags: &[ScreenFlags]) -> Result<Vec<Box<dyn Output>>, ErrorKind>;
+
+    /// File descriptor that becomes readable when presentations complete, `None`
+    /// when presenting is synchronous.
+    fn event_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    /// Processes the pending completion events of `event_fd`.
+    fn dispatch_events(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Starts collecting the modesets of the outputs, so `commit` applies them together.
+    fn begin(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Validates and applies the modesets collected since `begin`, all or none of them.
+    /// 
+    /// Devices without transactions apply each modeset when it is made.
+    fn commit(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Discards the modesets collected since `begin`.
+    fn abort(&mut self) {}
+}
+
+/// Allocates buffers that can be presented by a [`Presenter`].
+pub trait BufferAllocator: Debug {
+    fn allocate(&self, width: u32, height: u32, format: PixelFormat, flags: &[BufferFlag]) -> Result<Buffer, ErrorKind>;
+
+    /// Imports a DMA-BUF as a buffer of this allocator, fails when it has no GPU to import it.
+    fn import(&self, _dmabuf: &DmaBuf, _flags: &[BufferFlag]) -> Result<Buffer, ErrorKind> {
+        Err(ErrorKind::DMABUF_IMPORT_FAILED)
+    }
+
+    /// Returns `false` when `import` always fails.
+    fn can_import(&self) -> bool {
+        false
+    }
+}
+
+/// Shows buffers on an output.
+pub trait Presenter: Debug {
+    /// Prepares the buffers of a screen, which are later referred to by index.
+    fn attach(&mut self, buffers: &[Buffer]) -> Result<(), ErrorKind>;
+
+    /// Shows the attached buffer at `index`.
+    /// 
+    /// The buffer may only be shown at the next vblank, it is `pending` until then.
+    fn present(&mut self, index: usize) -> Result<(), ErrorKind>;
+
+    /// Index of the buffer being shown, `None` before the first presentation.
+    fn displayed(&self) -> Option<usize>;
+
+    /// Index of the buffer presented but not shown yet.
+    fn pending(&self) -> Option<usize> {
+        None
+    }
+
+    /// Blocks until the pending buffer is shown.
+    fn wait(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Shows `buffer` at `x`, `y` on a free overlay plane, above the presented buffers.
+    /// 
+    /// Returns the id of the plane, or `None` when no plane can scan out the
+    /// buffer and it has to be composited instead.
+    fn set_overlay(&mut self, _buffer: &Buffer, _x: i32, _y: i32) -> Result<Option<u32>, ErrorKind> {
+        Ok(None)
+    }
+
+    /// Turns off the overlay plane `plane` set by `set_overlay`.
+    fn clear_overlay(&mut self, _plane: u32) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Shows `buffer` on the cursor plane with its hotspot at `hot_x`, `hot_y`, or
+    /// hides the cursor when `buffer` is `None`.
+    /// 
+    /// Returns `false` when there is no cursor plane and the cursor has to be composited.
+    fn set_cursor(&mut self, _buffer: Option<&Buffer>, _hot_x: i32, _hot_y: i32) -> Result<bool, ErrorKind> {
+        Ok(false)
+    }
+
+    /// Moves the top left corner of the cursor set by `set_cursor` to `x`, `y`.
+    fn move_cursor(&mut self, _x: i32, _y: i32) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Restores what the output showed before the display took it over.
+    fn restore(&mut self);
+}
+
+/// A monitor, or virtual sink, and the mode it is driven at.
+pub trait Output: Presenter {
+    fn id(&self) -> u32;
+
+    fn connector_type(&self) -> ConnectorType;
+
+    /// Physical width in millimeters, `0` when unknown.
+    fn mm_width(&self) -> u32;
+
+    /// Physical height in millimeters, `0` when unknown.
+    fn mm_height(&self) -> u32;
+
+    fn subpixel(&self) -> u32;
+
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    fn refresh(&self) -> u32;
+
+    /// Index of the current mode.
+    fn mode(&self) -> u32;
+
+    /// The modes the output can be driven at, indexed as in [`Output::set_mode`].
+    fn modes(&self) -> Vec<Mode> {
+        vec![Mode { width: self.width(), height: self.height(), refresh: self.refresh(), preferred: true, interlaced: false }]
+    }
+
+    /// Identity and capabilities of the monitor, `None` when it has no valid EDID.
+    fn edid(&self) -> Option<&Edid> {
+        None
+    }
+
+    /// Number of entries of the gamma ramp, `0` when the output has none.
+    fn gamma_size(&self) -> usize {
+        0
+    }
+
+    fn gamma(&self) -> Result<GammaRamp, ErrorKind> {
+        Err(ErrorKind::GAMMA_FAILED)
+    }
+
+    /// Programs a ramp of `gamma_size` entries, kept until the output is restored.
+    fn set_gamma(&mut self, _ramp: &GammaRamp) -> Result<(), ErrorKind> {
+        Err(ErrorKind::GAMMA_FAILED)
+    }
+
+    fn power_state(&self) -> PowerState {
+        PowerState::On
+    }
+
+    /// Bit of the encoder of this output, as used by `possible_clones`, `0` when unknown.
+    fn encoder_mask(&self) -> u32 {
+        0
+    }
+
+    /// Encoders, as `encoder_mask` bits, that can show the same picture as this output.
+    fn possible_clones(&self) -> u32 {
+        0
+    }
+
+    /// Also drives the outputs `clones`, given by id, from the CRTC of this one, applied
+    /// by the next presentation. The clones show the same picture at the same mode.
+    /// 
+    /// Fails with `LAYOUT_INVALID` when the output cannot drive clones, or with
+    /// the error of the modeset when the hardware refuses them.
+    fn set_clones(&mut self, clones: &[u32]) -> Result<(), ErrorKind> {
+        if clones.is_empty() {
+            return Ok(());
+        }
+
+        Err(ErrorKind::LAYOUT_INVALID)
+    }
+
+    /// Gives up the CRTC while another output drives this one as a clone, or takes it back.
+    /// 
+    /// Presentations made while cloned are shown once the output has its CRTC again.
+    fn set_cloned(&mut self, cloned: bool) -> Result<(), ErrorKind> {
+        if !cloned {
+            return Ok(());
+        }
+
+        Err(ErrorKind::LAYOUT_INVALID)
+    }
+
+    /// Blanks or powers down the monitor, presentations made meanwhile are shown once it is on again.
+    fn set_power_state(&mut self, state: PowerState) -> Result<(), ErrorKind> {
+        if state == self.power_state() {
+            return Ok(());
+        }
+
+        Err(ErrorKind::POWER_FAILED)
+    }
+
+    /// Scans out the attached buffers turned by `transform`, applied by the next presentation.
+    /// 
+    /// Returns `false` when the hardware cannot, the output then scans out
+    /// upright and the picture has to be turned before it is presented. The
+    /// buffers of a transform swapping width and height must be attached
+    /// again with the swapped size.
+    fn set_transform(&mut self, transform: SurfaceTransform) -> Result<bool, ErrorKind> {
+        Ok(transform == SurfaceTransform::Normal)
+    }
+
+    /// The hardware planes that can scan out on this output.
+    fn planes(&self) -> Vec<PlaneInfo> {
+        Vec::new()
+    }
+
+    /// Switches to the mode at `index`, applied by the next presentation.
+    /// 
+    /// The buffers must be attached again, as their size may have changed. A
+    /// `checkpoint` taken before keeps the previous ones until the new mode is shown.
+    fn set_mode(&mut self, index: u32) -> Result<(), ErrorKind> {
+        if index == self.mode() {
+            return Ok(());
+        }
+
+        Err(ErrorKind::MODE_NOT_FOUND)
+    }
+
+    /// Keeps the current mode and attached buffers until `release` or `rollback`,
+    /// so the output can be switched to another mode and back.
+    fn checkpoint(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    /// Drops the state kept by `checkpoint`, once the new mode is shown.
+    fn release(&mut self) {}
+
+    /// Returns to the mode and buffers kept by `checkpoint`, showing the displayed buffer again.
+    fn rollback(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+}
+
+/// A resolution and refresh rate an output can be driven at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh: u32,
+    pub preferred: bool,
+    pub interlaced: bool,
+}
+
+/// A hardware plane of an output and the pixel formats it scans out, as fourcc codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaneInfo {
+    pub id: u32,
+    pub kind: Planes,
+    pub formats: Vec<u32>,
+}
+
+/// Allocates buffers in process memory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryAllocator;
+
+impl BufferAllocator for MemoryAllocator {
+    fn allocate(&self, width: u32, height: u32, format: PixelFormat, _flags: &[BufferFlag]) -> Result<Buffer, ErrorKind> {
+        Buffer::memory(width, height, format)
+    }
+}
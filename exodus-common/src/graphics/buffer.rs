@@ -1,3 +1,5 @@
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use drm::{drmIoctl, drm_mode_create_dumb, drm_mode_destroy_dumb, drm_mode_map_dumb};
 use exodus_errors::ErrorKind;
 use gbm::gbm_bo_transfer_flags::{GBM_BO_TRANSFER_WRITE, GBM_BO_TRANSFER_READ};
 use gbm::gbm_bo_flags::*;
@@ -8,19 +10,82 @@ use libc::c_void;
 use crate::enums::{PixelFormat, BufferFlag};
 use crate::{error, debug, verbose};
 
-use super::device::Device;
+use super::device::{Device, GPUID};
 
+/// `DRM_IOWR(nr, T)`, bindgen does not expand the ioctl macros of `drm.h`.
+const fn drm_iowr<T>(nr: u64) -> libc::c_ulong {
+    (3 << 30) | ((std::mem::size_of::<T>() as u64) << 16) | ((b'd' as u64) << 8) | nr
+}
+
+const DRM_IOCTL_MODE_CREATE_DUMB: libc::c_ulong = drm_iowr::<drm_mode_create_dumb>(0xb2);
+const DRM_IOCTL_MODE_MAP_DUMB: libc::c_ulong = drm_iowr::<drm_mode_map_dumb>(0xb3);
+const DRM_IOCTL_MODE_DESTROY_DUMB: libc::c_ulong = drm_iowr::<drm_mode_destroy_dumb>(0xb4);
+
+/// The layout of the buffer is given by the driver, no explicit modifier.
+pub const DRM_FORMAT_MOD_INVALID: u64 = 0x00ffffffffffffff;
+pub const DRM_FORMAT_MOD_LINEAR: u64 = 0;
+/// Most planes of a DMA-BUF, as in `GBM_MAX_PLANES`.
+pub const MAX_DMABUF_PLANES: usize = 4;
+
+/// A plane of a DMA-BUF, its pixels start at `offset` of `fd`.
+#[derive(Debug)]
+pub struct DmaBufPlane {
+    pub fd: OwnedFd,
+    pub offset: u32,
+    pub stride: u32,
+}
+
+/// Where a plane of a buffer starts in the memory of `handle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+    pub handle: u32,
+    pub offset: u32,
+    pub stride: u32,
+}
+
+/// A buffer shared between processes and devices as DMA-BUF file descriptors.
+#[derive(Debug)]
+pub struct DmaBuf {
+    pub width: u32,
+    pub height: u32,
+    /// DRM fourcc code of the pixels.
+    pub fourcc: u32,
+    /// Tiling and compression of the planes, `DRM_FORMAT_MOD_INVALID` when implicit.
+    pub modifier: u64,
+    pub planes: Vec<DmaBufPlane>,
+}
+
+/// Pixels a screen can copy from, read as 32 bits XRGB or ARGB.
+pub trait PixelSource {
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    /// Reads the `width`x`height` rectangle at `x`, `y`.
+    fn read(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u32>, ErrorKind>;
+}
 
 #[derive(Debug)]
 pub enum Buffer {
+    /// DRM dumb buffer, or shared memory when there is no device, mapped in process memory.
+    /// 
+    /// Dumb buffers can be scanned out by any KMS driver, shared memory can
+    /// only be composited but can be handed to other processes.
     Legacy {
         width: u32,
         height: u32,
+        /// Handle of the dumb buffer, `0` for shared memory.
         handle: u32,
         stride: u32,
         bpp: u32,
         format: PixelFormat,
         buffer: *mut c_void,
+        /// Length of the mapping in bytes.
+        size: usize,
+        /// GPU owning the dumb buffer, `None` for shared memory.
+        gpu: Option<GPUID>,
+        /// The memfd of the shared memory, `None` for dumb buffers.
+        fd: Option<OwnedFd>,
     },
     Native {
         width: u32,
@@ -29,28 +94,32 @@ pub enum Buffer {
         stride: u32,
         bpp: u32,
         format: PixelFormat,
+        /// Tiling and compression chosen by the driver, `DRM_FORMAT_MOD_INVALID` when it does not tell.
+        modifier: u64,
         buffer: *mut gbm_bo,
     },
+    /// Buffer kept in process memory, used by the headless backend.
+    Memory {
+        width: u32,
+        height: u32,
+        stride: u32,
+        bpp: u32,
+        format: PixelFormat,
+        pixels: Vec<u32>,
+    },
 }
 
 impl Buffer {
     pub fn new(device: &Device, width: u32, height: u32, format: PixelFormat, buffer_flags: &[BufferFlag]) -> Result<Self, ErrorKind> {
         debug!("Creating buffer. - Width: {}, Height: {}, Format: {:?}, Flags: {:?}", width, height, format, buffer_flags);
 
-        let mut flags = 0;
-
-        for flag in buffer_flags {
-            match flag {
-                BufferFlag::Cursor      => flags |= GBM_BO_USE_CURSOR,
-                BufferFlag::Linear      => flags |= GBM_BO_USE_LINEAR,
-                BufferFlag::Protected   => flags |= GBM_BO_USE_PROTECTED,
-                BufferFlag::Rendering   => flags |= GBM_BO_USE_RENDERING,
-                BufferFlag::Scanout     => flags |= GBM_BO_USE_SCANOUT,
-            }
+        if !device.has_gbm() {
+            error!("Failed to create buffer, GBM is not available. - ErrorKind: {:?}", ErrorKind::BUFFER_CREATE_FAILED);
+            return Err(ErrorKind::BUFFER_CREATE_FAILED);
         }
 
         let buffer = unsafe {
-            gbm_bo_create(device.as_ptr(), width, height, format as u32, flags)
+            gbm_bo_create(device.as_ptr(), width, height, format.fourcc(), Self::usage(buffer_flags))
         };
 
         if buffer.is_null() {
@@ -61,6 +130,7 @@ impl Buffer {
         let handle = unsafe { gbm_bo_get_handle(buffer).u32_ };
         let stride = unsafe { gbm_bo_get_stride(buffer) };
         let bpp = unsafe { gbm_bo_get_bpp(buffer) };
+        let modifier = unsafe { gbm_bo_get_modifier(buffer) };
         
         Ok(Self::Native {
             width,
@@ -69,14 +139,276 @@ impl Buffer {
             stride,
             bpp,
             format,
+            modifier,
+            buffer,
+        })
+    }
+
+    /// Imports a DMA-BUF, from another process or device, as a buffer of `device`.
+    pub fn import(device: &Device, dmabuf: &DmaBuf, buffer_flags: &[BufferFlag]) -> Result<Self, ErrorKind> {
+        debug!("Importing buffer. - Width: {}, Height: {}, Fourcc: {:#x}, Modifier: {:#x}, Planes: {}",
+            dmabuf.width, dmabuf.height, dmabuf.fourcc, dmabuf.modifier, dmabuf.planes.len());
+
+        let format = match PixelFormat::from_fourcc(dmabuf.fourcc) {
+            Ok(format) => format,
+            Err(err) => {
+                error!("Unsupported DMA-BUF format. - Fourcc: {:#x} - ErrorKind: {:?}", dmabuf.fourcc, err);
+                return Err(err);
+            },
+        };
+
+        // Compressed modifiers may add planes to those of the format.
+        if !device.has_gbm() || dmabuf.planes.len() < format.planes() || dmabuf.planes.len() > MAX_DMABUF_PLANES {
+            let err = ErrorKind::DMABUF_IMPORT_FAILED;
+            error!("Failed to import buffer. - Planes: {} - ErrorKind: {:?}", dmabuf.planes.len(), err);
+            return Err(err);
+        }
+
+        let buffer = match dmabuf.modifier {
+            // Without a modifier only single plane buffers can be described, starting at the beginning of the file.
+            DRM_FORMAT_MOD_INVALID if dmabuf.planes.len() == 1 => {
+                if dmabuf.planes[0].offset != 0 {
+                    let err = ErrorKind::DMABUF_IMPORT_FAILED;
+                    error!("Buffers without modifier must start at offset 0. - Offset: {} - ErrorKind: {:?}", dmabuf.planes[0].offset, err);
+                    return Err(err);
+                }
+
+                let mut data = gbm_import_fd_data {
+                    fd: dmabuf.planes[0].fd.as_raw_fd(),
+                    width: dmabuf.width,
+                    height: dmabuf.height,
+                    stride: dmabuf.planes[0].stride,
+                    format: dmabuf.fourcc,
+                };
+
+                unsafe { gbm_bo_import(device.as_ptr(), GBM_BO_IMPORT_FD, &mut data as *mut _ as *mut c_void, Self::usage(buffer_flags)) }
+            },
+            modifier => {
+                let mut data = gbm_import_fd_modifier_data {
+                    width: dmabuf.width,
+                    height: dmabuf.height,
+                    format: dmabuf.fourcc,
+                    num_fds: dmabuf.planes.len() as u32,
+                    fds: [-1; MAX_DMABUF_PLANES],
+                    strides: [0; MAX_DMABUF_PLANES],
+                    offsets: [0; MAX_DMABUF_PLANES],
+                    modifier,
+                };
+
+                for (index, plane) in dmabuf.planes.iter().enumerate() {
+                    data.fds[index] = plane.fd.as_raw_fd();
+                    data.strides[index] = plane.stride as i32;
+                    data.offsets[index] = plane.offset as i32;
+                }
+
+                unsafe { gbm_bo_import(device.as_ptr(), GBM_BO_IMPORT_FD_MODIFIER, &mut data as *mut _ as *mut c_void, Self::usage(buffer_flags)) }
+            },
+        };
+
+        if buffer.is_null() {
+            let err = ErrorKind::DMABUF_IMPORT_FAILED;
+            error!("Failed to import buffer. - Fourcc: {:#x} - Modifier: {:#x} - ErrorKind: {:?}", dmabuf.fourcc, dmabuf.modifier, err);
+            return Err(err);
+        }
+
+        Ok(Self::Native {
+            width: dmabuf.width,
+            height: dmabuf.height,
+            handle: unsafe { gbm_bo_get_handle(buffer).u32_ },
+            stride: unsafe { gbm_bo_get_stride(buffer) },
+            bpp: unsafe { gbm_bo_get_bpp(buffer) },
+            format,
+            modifier: dmabuf.modifier,
+            buffer,
+        })
+    }
+
+    /// Exports the buffer as DMA-BUF file descriptors, one per plane, to share it with another process or device.
+    pub fn export(&self) -> Result<DmaBuf, ErrorKind> {
+        let bo = match self {
+            Self::Native { buffer, .. } => *buffer,
+            _ => {
+                let err = ErrorKind::DMABUF_EXPORT_FAILED;
+                error!("Only GBM buffers can be exported. - ErrorKind: {:?}", err);
+                return Err(err);
+            },
+        };
+
+        let count = unsafe { gbm_bo_get_plane_count(bo) }.clamp(0, MAX_DMABUF_PLANES as i32);
+        let mut planes = Vec::with_capacity(count as usize);
+
+        for plane in 0..count {
+            let fd = unsafe { gbm_bo_get_fd_for_plane(bo, plane) };
+            if fd < 0 {
+                let err = ErrorKind::DMABUF_EXPORT_FAILED;
+                error!("Failed to export buffer plane. - Plane: {} - ErrorKind: {:?}", plane, err);
+                return Err(err);
+            }
+
+            planes.push(DmaBufPlane {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                offset: unsafe { gbm_bo_get_offset(bo, plane) },
+                stride: unsafe { gbm_bo_get_stride_for_plane(bo, plane) },
+            });
+        }
+
+        if planes.is_empty() {
+            let err = ErrorKind::DMABUF_EXPORT_FAILED;
+            error!("Buffer has no plane to export. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        Ok(DmaBuf {
+            width: self.width(),
+            height: self.height(),
+            fourcc: unsafe { gbm_bo_get_format(bo) },
+            modifier: unsafe { gbm_bo_get_modifier(bo) },
+            planes,
+        })
+    }
+
+    /// GBM usage flags of `buffer_flags`.
+    fn usage(buffer_flags: &[BufferFlag]) -> u32 {
+        let mut flags = 0;
+
+        for flag in buffer_flags {
+            match flag {
+                BufferFlag::Cursor      => flags |= GBM_BO_USE_CURSOR,
+                BufferFlag::Linear      => flags |= GBM_BO_USE_LINEAR,
+                BufferFlag::Protected   => flags |= GBM_BO_USE_PROTECTED,
+                BufferFlag::Rendering   => flags |= GBM_BO_USE_RENDERING,
+                BufferFlag::Scanout     => flags |= GBM_BO_USE_SCANOUT,
+            }
+        }
+
+        flags
+    }
+
+    /// Whether the pixels of `format` can be read and written as `u32`, one per pixel.
+    fn packed(format: PixelFormat) -> Result<(), ErrorKind> {
+        if format.planes() != 1 || format.size() != 4 {
+            let err = ErrorKind::PIXEL_FORMAT_UNSUPPORTED;
+            error!("Pixels of the format are not 32 bits. - Format: {:?} - ErrorKind: {:?}", format, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Creates a buffer in process memory, without any GPU involved, for 32 bits formats only.
+    pub fn memory(width: u32, height: u32, format: PixelFormat) -> Result<Self, ErrorKind> {
+        debug!("Creating memory buffer. - Width: {}, Height: {}, Format: {:?}", width, height, format);
+
+        if width == 0 || height == 0 {
+            error!("Failed to create buffer. - ErrorKind: {:?}", ErrorKind::BUFFER_CREATE_FAILED);
+            return Err(ErrorKind::BUFFER_CREATE_FAILED);
+        }
+
+        Self::packed(format)?;
+
+        Ok(Self::Memory {
+            width,
+            height,
+            stride: width * 4,
+            bpp: 32,
+            format,
+            pixels: vec![0; (width * height) as usize],
+        })
+    }
+
+    /// Creates a dumb buffer on `gpu`, or a shared memory buffer when `None`, for 32 bits formats only.
+    pub fn legacy(gpu: Option<GPUID>, width: u32, height: u32, format: PixelFormat) -> Result<Self, ErrorKind> {
+        debug!("Creating legacy buffer. - GPUID: {:?}, Width: {}, Height: {}, Format: {:?}", gpu, width, height, format);
+
+        if width == 0 || height == 0 {
+            error!("Failed to create buffer. - ErrorKind: {:?}", ErrorKind::BUFFER_CREATE_FAILED);
+            return Err(ErrorKind::BUFFER_CREATE_FAILED);
+        }
+
+        Self::packed(format)?;
+
+        match gpu {
+            Some(gpu) => Self::create_dumb_buffer(gpu, width, height, format),
+            None => Self::create_shared_buffer(width, height, format),
+        }
+    }
+
+    /// Allocates a dumb buffer with `DRM_IOCTL_MODE_CREATE_DUMB` and maps it.
+    fn create_dumb_buffer(gpu: GPUID, width: u32, height: u32, format: PixelFormat) -> Result<Self, ErrorKind> {
+        let bpp = format.bpp();
+        let mut create = drm_mode_create_dumb { height, width, bpp, flags: 0, handle: 0, pitch: 0, size: 0 };
+
+        if unsafe { drmIoctl(gpu, DRM_IOCTL_MODE_CREATE_DUMB, &mut create as *mut _ as *mut c_void) } != 0 {
+            error!("Failed to create dumb buffer. - GPUID: {} - ErrorKind: {:?}", gpu, ErrorKind::BUFFER_CREATE_FAILED);
+            return Err(ErrorKind::BUFFER_CREATE_FAILED);
+        }
+
+        let mut map = drm_mode_map_dumb { handle: create.handle, pad: 0, offset: 0 };
+        let buffer = match unsafe { drmIoctl(gpu, DRM_IOCTL_MODE_MAP_DUMB, &mut map as *mut _ as *mut c_void) } {
+            0 => unsafe { libc::mmap(std::ptr::null_mut(), create.size as usize, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, gpu, map.offset as libc::off_t) },
+            _ => libc::MAP_FAILED,
+        };
+
+        if buffer == libc::MAP_FAILED {
+            Self::destroy_dumb_buffer(gpu, create.handle);
+            error!("Failed to map dumb buffer. - GPUID: {} - ErrorKind: {:?}", gpu, ErrorKind::BUFFER_MAPPING_FAILED);
+            return Err(ErrorKind::BUFFER_MAPPING_FAILED);
+        }
+
+        Ok(Self::Legacy {
+            width,
+            height,
+            handle: create.handle,
+            stride: create.pitch,
+            bpp,
+            format,
             buffer,
+            size: create.size as usize,
+            gpu: Some(gpu),
+            fd: None,
         })
     }
 
-    #[allow(dead_code)]
-    #[allow(unused_variables)]
-    fn create_legacy_buffer(width: u32, height: u32, format: PixelFormat) -> Result<Self, ErrorKind> {
-        todo!()
+    fn destroy_dumb_buffer(gpu: GPUID, handle: u32) {
+        let mut destroy = drm_mode_destroy_dumb { handle };
+        unsafe { drmIoctl(gpu, DRM_IOCTL_MODE_DESTROY_DUMB, &mut destroy as *mut _ as *mut c_void) };
+    }
+
+    /// Allocates a memfd of the size of the buffer and maps it.
+    fn create_shared_buffer(width: u32, height: u32, format: PixelFormat) -> Result<Self, ErrorKind> {
+        let stride = width * 4;
+        let size = (stride * height) as usize;
+
+        let fd = unsafe { libc::memfd_create(c"exodus-buffer".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            error!("Failed to create shared memory. - ErrorKind: {:?}", ErrorKind::BUFFER_CREATE_FAILED);
+            return Err(ErrorKind::BUFFER_CREATE_FAILED);
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), size as libc::off_t) } != 0 {
+            error!("Failed to size shared memory. - Size: {} - ErrorKind: {:?}", size, ErrorKind::BUFFER_CREATE_FAILED);
+            return Err(ErrorKind::BUFFER_CREATE_FAILED);
+        }
+
+        let buffer = unsafe { libc::mmap(std::ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd.as_raw_fd(), 0) };
+        if buffer == libc::MAP_FAILED {
+            error!("Failed to map shared memory. - ErrorKind: {:?}", ErrorKind::BUFFER_MAPPING_FAILED);
+            return Err(ErrorKind::BUFFER_MAPPING_FAILED);
+        }
+
+        Ok(Self::Legacy {
+            width,
+            height,
+            handle: 0,
+            stride,
+            bpp: 32,
+            format,
+            buffer,
+            size,
+            gpu: None,
+            fd: Some(fd),
+        })
     }
 
     /// Get the width of the buffer.
@@ -84,6 +416,7 @@ impl Buffer {
         match self {
             Self::Legacy { width, .. } => *width,
             Self::Native { width, .. } => *width,
+            Self::Memory { width, .. } => *width,
         }
     }
 
@@ -92,6 +425,7 @@ impl Buffer {
         match self {
             Self::Legacy { height, .. } => *height,
             Self::Native { height, .. } => *height,
+            Self::Memory { height, .. } => *height,
         }
     }
 
@@ -100,6 +434,7 @@ impl Buffer {
         match self {
             Self::Legacy { handle, .. } => *handle,
             Self::Native { handle, .. } => *handle,
+            Self::Memory { .. } => 0,
         }
     }
 
@@ -108,6 +443,7 @@ impl Buffer {
         match self {
             Self::Legacy { stride, .. } => *stride,
             Self::Native { stride, .. } => *stride,
+            Self::Memory { stride, .. } => *stride,
         }
     }
 
@@ -116,6 +452,15 @@ impl Buffer {
         match self {
             Self::Legacy { bpp, .. } => *bpp,
             Self::Native { bpp, .. } => *bpp,
+            Self::Memory { bpp, .. } => *bpp,
+        }
+    }
+
+    /// Get the file descriptor of the shared memory backing the buffer, to hand it to another process.
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        match self {
+            Self::Legacy { fd, .. } => fd.as_ref().map(|fd| fd.as_fd()),
+            _ => None,
         }
     }
 
@@ -124,6 +469,34 @@ impl Buffer {
         match self {
             Self::Legacy { format, .. } => *format,
             Self::Native { format, .. } => *format,
+            Self::Memory { format, .. } => *format,
+        }
+    }
+
+    /// Get the format modifier of the buffer, `DRM_FORMAT_MOD_INVALID` when the layout is implicit.
+    /// 
+    /// Dumb buffers and memory are linear, but drivers without modifiers
+    /// reject framebuffers that state it, so they report no modifier.
+    pub fn modifier(&self) -> u64 {
+        match self {
+            Self::Native { modifier, .. } => *modifier,
+            _ => DRM_FORMAT_MOD_INVALID,
+        }
+    }
+
+    /// Get where each plane of the buffer lies, to create a framebuffer from it.
+    pub fn layout(&self) -> Vec<PlaneLayout> {
+        match self {
+            Self::Native { buffer, .. } => {
+                let count = unsafe { gbm_bo_get_plane_count(*buffer) }.clamp(1, MAX_DMABUF_PLANES as i32);
+
+                (0..count).map(|plane| PlaneLayout {
+                    handle: unsafe { gbm_bo_get_handle_for_plane(*buffer, plane).u32_ },
+                    offset: unsafe { gbm_bo_get_offset(*buffer, plane) },
+                    stride: unsafe { gbm_bo_get_stride_for_plane(*buffer, plane) },
+                }).collect()
+            },
+            _ => vec![PlaneLayout { handle: self.handle(), offset: 0, stride: self.stride() }],
         }
     }
 
@@ -141,9 +514,24 @@ impl Buffer {
             return Err(ErrorKind::BUFFER_OUT_OF_BOUNDS);
         }
 
+        Self::packed(self.format())?;
+
         match self {
-            Self::Legacy { .. } => todo!(),
+            Self::Legacy { buffer, stride, .. } => {
+                let dst = *buffer as *mut u32;
+                for row in 0..height {
+                    let src = &pixels[(row * width) as usize..((row + 1) * width) as usize];
+                    unsafe { std::ptr::copy_nonoverlapping(src.as_ptr(), dst.add(((y + row) * (*stride / 4) + x) as usize), width as usize) };
+                }
+            },
             Self::Native { .. } => self.write_buffer(x, y, width, height, pixels)?,
+            Self::Memory { width: stride, pixels: dst, .. } => {
+                for row in 0..height {
+                    let src = (row * width) as usize;
+                    let dst_start = ((y + row) * *stride + x) as usize;
+                    dst[dst_start..dst_start + width as usize].copy_from_slice(&pixels[src..src + width as usize]);
+                }
+            },
         }
         Ok(())
     }
@@ -182,9 +570,27 @@ impl Buffer {
             return Err(ErrorKind::BUFFER_OUT_OF_BOUNDS);
         }
 
+        Self::packed(self.format())?;
+
         match self {
-            Self::Legacy { .. } => todo!(),
+            Self::Legacy { buffer, stride, .. } => {
+                let src = *buffer as *const u32;
+                let mut dst = Vec::with_capacity((width * height) as usize);
+                for row in 0..height {
+                    let start = unsafe { src.add(((y + row) * (stride / 4) + x) as usize) };
+                    dst.extend_from_slice(unsafe { std::slice::from_raw_parts(start, width as usize) });
+                }
+                Ok(dst)
+            },
             Self::Native { .. } => self.read_buffer(x, y, width, height),
+            Self::Memory { width: stride, pixels, .. } => {
+                let mut dst = Vec::with_capacity((width * height) as usize);
+                for row in 0..height {
+                    let start = ((y + row) * stride + x) as usize;
+                    dst.extend_from_slice(&pixels[start..start + width as usize]);
+                }
+                Ok(dst)
+            },
         }
     }
 
@@ -232,7 +638,38 @@ impl Buffer {
         match self {
             Self::Legacy { buffer, .. } => *buffer,
             Self::Native { buffer, .. } => *buffer as *mut c_void,
+            Self::Memory { .. } => std::ptr::null_mut(),
         }
     }
     
 }
+
+impl PixelSource for Buffer {
+    fn width(&self) -> u32 {
+        Buffer::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        Buffer::height(self)
+    }
+
+    fn read(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u32>, ErrorKind> {
+        Buffer::read(self, x, y, width, height)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        match self {
+            Self::Legacy { buffer, size, gpu, handle, .. } => {
+                unsafe { libc::munmap(*buffer, *size) };
+
+                if let Some(gpu) = gpu {
+                    Self::destroy_dumb_buffer(*gpu, *handle);
+                }
+            },
+            Self::Native { buffer, .. } => unsafe { gbm_bo_destroy(*buffer) },
+            Self::Memory { .. } => (),
+        }
+    }
+}
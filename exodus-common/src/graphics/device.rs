@@ -2,7 +2,9 @@ use exodus_errors::ErrorKind;
 use gbm::gbm_create_device;
 use std::sync::Arc;
 
-use crate::{debug, error};
+use crate::{debug, warn};
+use crate::enums::{BufferFlag, PixelFormat};
+use super::{backend::BufferAllocator, buffer::{Buffer, DmaBuf}};
 
 pub type GPUID = i32;
 
@@ -11,9 +13,11 @@ pub type NativeBufferRaw = *mut gbm::gbm_bo;
 pub type NativeSurfaceRaw = *mut gbm::gbm_surface;
 
 
+/// Allocates the buffers of a GPU with GBM, or as dumb buffers when GBM cannot.
 #[derive(Debug)]
 pub struct Device {
     id : GPUID,
+    /// Null on KMS-only drivers without a usable GBM.
     device: *mut gbm::gbm_device,
 }
 
@@ -26,8 +30,7 @@ impl Device {
         };
 
         if device.is_null() {
-            error!("Failed to create gbm_device. - GPUID: {}", id);
-            return Err(ErrorKind::DEVICE_MANAGER_CREATE_FAILED);
+            warn!("Failed to create gbm_device, using dumb buffers. - GPUID: {}", id);
         }
 
         Ok(DeviceRef::new(Device {
@@ -43,6 +46,28 @@ impl Device {
     pub fn id(&self) -> i32 {
         self.id
     }
+
+    /// Returns `false` when only dumb buffers can be allocated.
+    pub fn has_gbm(&self) -> bool {
+        !self.device.is_null()
+    }
+}
+
+impl BufferAllocator for Device {
+    fn allocate(&self, width: u32, height: u32, format: PixelFormat, flags: &[BufferFlag]) -> Result<Buffer, ErrorKind> {
+        Buffer::new(self, width, height, format, flags).or_else(|err| {
+            debug!("GBM allocation failed, using a dumb buffer. - GPUID: {} - ErrorKind: {:?}", self.id, err);
+            Buffer::legacy(Some(self.id), width, height, format)
+        })
+    }
+
+    fn import(&self, dmabuf: &DmaBuf, flags: &[BufferFlag]) -> Result<Buffer, ErrorKind> {
+        Buffer::import(self, dmabuf, flags)
+    }
+
+    fn can_import(&self) -> bool {
+        self.has_gbm()
+    }
 }
 
 unsafe impl Send for Device {}
@@ -0,0 +1,263 @@
+use exodus_errors::ErrorKind;
+use crate::error;
+
+pub const EDID_BLOCK_SIZE: usize = 128;
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+const DESCRIPTORS_OFFSET: usize = 54;
+const DESCRIPTOR_SIZE: usize = 18;
+const DESCRIPTOR_SERIAL: u8 = 0xff;
+const DESCRIPTOR_NAME: u8 = 0xfc;
+
+const CTA_EXTENSION_TAG: u8 = 0x02;
+const CTA_EXTENDED_BLOCK: u8 = 0x07;
+const CTA_COLORIMETRY_BLOCK: u8 = 0x05;
+const CTA_HDR_STATIC_METADATA_BLOCK: u8 = 0x06;
+
+/// `Edid::colorimetry` bits, from the CTA-861 colorimetry data block.
+pub const COLORIMETRY_XVYCC_601: u8 = 0x01;
+pub const COLORIMETRY_XVYCC_709: u8 = 0x02;
+pub const COLORIMETRY_SYCC_601: u8 = 0x04;
+pub const COLORIMETRY_OPYCC_601: u8 = 0x08;
+pub const COLORIMETRY_OPRGB: u8 = 0x10;
+pub const COLORIMETRY_BT2020_CYCC: u8 = 0x20;
+pub const COLORIMETRY_BT2020_YCC: u8 = 0x40;
+pub const COLORIMETRY_BT2020_RGB: u8 = 0x80;
+
+/// `HdrMetadata::eotfs` bits, the transfer functions the monitor accepts.
+pub const EOTF_TRADITIONAL_SDR: u8 = 0x01;
+pub const EOTF_TRADITIONAL_HDR: u8 = 0x02;
+pub const EOTF_SMPTE_ST2084: u8 = 0x04;
+pub const EOTF_HLG: u8 = 0x08;
+
+/// A detailed timing, the first one of the base block is the native resolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    pub width: u32,
+    pub height: u32,
+    /// Refresh rate in hertz.
+    pub refresh: f32,
+    pub pixel_clock: u32,
+}
+
+/// CIE 1931 `(x, y)` coordinates of the primaries and white point.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Chromaticity {
+    pub red: (f32, f32),
+    pub green: (f32, f32),
+    pub blue: (f32, f32),
+    pub white: (f32, f32),
+}
+
+/// HDR static metadata, luminances are in cd/m², `None` when not given.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HdrMetadata {
+    /// `EOTF_*` bits.
+    pub eotfs: u8,
+    pub max_luminance: Option<f32>,
+    pub max_frame_average: Option<f32>,
+    pub min_luminance: Option<f32>,
+}
+
+/// Identity and capabilities of a monitor, parsed from its EDID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edid {
+    /// Three letter PNP id, `DEL` for example.
+    pub manufacturer: String,
+    pub product: u16,
+    pub serial: u32,
+    /// Serial number descriptor, usually more reliable than `serial`.
+    pub serial_string: Option<String>,
+    pub name: Option<String>,
+    /// Week of manufacture, `None` when unknown or when `year` is the model year.
+    pub week: Option<u8>,
+    pub year: u16,
+    pub version: u8,
+    pub revision: u8,
+    pub digital: bool,
+    /// Bits per color, `None` when undefined or for analog inputs.
+    pub bit_depth: Option<u8>,
+    /// Physical size in centimeters, `0` when unknown.
+    pub width: u32,
+    pub height: u32,
+    pub gamma: Option<f32>,
+    pub chromaticity: Chromaticity,
+    /// The preferred timing first, then the other detailed timings, extensions included.
+    pub timings: Vec<Timing>,
+    /// `COLORIMETRY_*` bits.
+    pub colorimetry: u8,
+    pub hdr: Option<HdrMetadata>,
+}
+
+impl Edid {
+    /// Parses the base block and the CTA-861 extensions of an EDID blob.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ErrorKind> {
+        if bytes.len() < EDID_BLOCK_SIZE || bytes[..8] != EDID_HEADER {
+            let err = ErrorKind::EDID_INVALID;
+            error!("Invalid EDID header. - Length: {} - ErrorKind: {:?}", bytes.len(), err);
+            return Err(err);
+        }
+
+        let blocks: Vec<&[u8]> = bytes.chunks_exact(EDID_BLOCK_SIZE).collect();
+        if !Self::checksum(blocks[0]) {
+            let err = ErrorKind::EDID_INVALID;
+            error!("Invalid EDID checksum. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        let base = blocks[0];
+        let id = u16::from_be_bytes([base[8], base[9]]);
+        let letter = |shift: u16| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char;
+
+        let digital = base[20] & 0x80 != 0;
+        let bit_depth = match (digital, (base[20] >> 4) & 0x07) {
+            (true, depth @ 1..=6) => Some(4 + depth * 2),
+            _ => None,
+        };
+
+        let mut edid = Self {
+            manufacturer: [letter(10), letter(5), letter(0)].iter().collect(),
+            product: u16::from_le_bytes([base[10], base[11]]),
+            serial: u32::from_le_bytes([base[12], base[13], base[14], base[15]]),
+            serial_string: None,
+            name: None,
+            week: match base[16] { 1..=54 => Some(base[16]), _ => None },
+            year: 1990 + base[17] as u16,
+            version: base[18],
+            revision: base[19],
+            digital,
+            bit_depth,
+            width: base[21] as u32,
+            height: base[22] as u32,
+            gamma: match base[23] { 0xff => None, gamma => Some((gamma as f32 + 100.0) / 100.0) },
+            chromaticity: Self::chromaticity(base),
+            timings: Vec::new(),
+            colorimetry: 0,
+            hdr: None,
+        };
+
+        for descriptor in base[DESCRIPTORS_OFFSET..DESCRIPTORS_OFFSET + 4 * DESCRIPTOR_SIZE].chunks_exact(DESCRIPTOR_SIZE) {
+            edid.descriptor(descriptor);
+        }
+
+        // Extensions with a wrong checksum are skipped, the base block is still usable.
+        for block in blocks.iter().skip(1).take(base[126] as usize) {
+            if block[0] == CTA_EXTENSION_TAG && Self::checksum(block) {
+                edid.cta_extension(block);
+            }
+        }
+
+        Ok(edid)
+    }
+
+    /// Identifies the monitor across connectors and reboots.
+    pub fn identity(&self) -> String {
+        match &self.serial_string {
+            Some(serial) => format!("{}-{:04x}-{}", self.manufacturer, self.product, serial),
+            None => format!("{}-{:04x}-{:08x}", self.manufacturer, self.product, self.serial),
+        }
+    }
+
+    /// The native resolution of the monitor.
+    pub fn preferred(&self) -> Option<&Timing> {
+        self.timings.first()
+    }
+
+    fn checksum(block: &[u8]) -> bool {
+        block.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+    }
+
+    /// Coordinates are 10 bit fractions, the 2 low bits of each are packed in bytes 25 and 26.
+    fn chromaticity(base: &[u8]) -> Chromaticity {
+        let coordinate = |high: usize, low: u8, shift: u8| {
+            ((base[high] as u16) << 2 | ((low >> shift) & 0x03) as u16) as f32 / 1024.0
+        };
+
+        Chromaticity {
+            red: (coordinate(27, base[25], 6), coordinate(28, base[25], 4)),
+            green: (coordinate(29, base[25], 2), coordinate(30, base[25], 0)),
+            blue: (coordinate(31, base[26], 6), coordinate(32, base[26], 4)),
+            white: (coordinate(33, base[26], 2), coordinate(34, base[26], 0)),
+        }
+    }
+
+    fn descriptor(&mut self, descriptor: &[u8]) {
+        if descriptor[0] != 0 || descriptor[1] != 0 {
+            self.timings.push(Self::timing(descriptor));
+            return;
+        }
+
+        let text = || {
+            let text = &descriptor[5..];
+            let end = text.iter().position(|byte| *byte == b'\n').unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).trim_end().to_string()
+        };
+
+        match descriptor[3] {
+            DESCRIPTOR_NAME => self.name = Some(text()),
+            DESCRIPTOR_SERIAL => self.serial_string = Some(text()),
+            _ => (),
+        }
+    }
+
+    fn timing(descriptor: &[u8]) -> Timing {
+        let pixel_clock = u16::from_le_bytes([descriptor[0], descriptor[1]]) as u32 * 10;
+        let width = descriptor[2] as u32 | ((descriptor[4] & 0xf0) as u32) << 4;
+        let hblank = descriptor[3] as u32 | ((descriptor[4] & 0x0f) as u32) << 8;
+        let height = descriptor[5] as u32 | ((descriptor[7] & 0xf0) as u32) << 4;
+        let vblank = descriptor[6] as u32 | ((descriptor[7] & 0x0f) as u32) << 8;
+
+        let total = (width + hblank) * (height + vblank);
+        let refresh = match total {
+            0 => 0.0,
+            _ => pixel_clock as f32 * 1000.0 / total as f32,
+        };
+
+        Timing { width, height, refresh, pixel_clock }
+    }
+
+    /// Reads the detailed timings and the colorimetry and HDR data blocks of a CTA-861 extension.
+    fn cta_extension(&mut self, block: &[u8]) {
+        let timings_offset = (block[2] as usize).min(EDID_BLOCK_SIZE - 1);
+        let mut offset = 4;
+
+        while offset < timings_offset {
+            let tag = block[offset] >> 5;
+            let length = (block[offset] & 0x1f) as usize;
+            let end = (offset + 1 + length).min(timings_offset);
+            let data = &block[offset + 1..end];
+
+            if tag == CTA_EXTENDED_BLOCK && !data.is_empty() {
+                match data[0] {
+                    CTA_COLORIMETRY_BLOCK if data.len() >= 2 => self.colorimetry = data[1],
+                    CTA_HDR_STATIC_METADATA_BLOCK if data.len() >= 3 => self.hdr = Some(Self::hdr_metadata(data)),
+                    _ => (),
+                }
+            }
+
+            offset = end;
+        }
+
+        if timings_offset >= 4 {
+            for descriptor in block[timings_offset..EDID_BLOCK_SIZE - 1].chunks_exact(DESCRIPTOR_SIZE) {
+                if descriptor[0] == 0 && descriptor[1] == 0 {
+                    break;
+                }
+                self.timings.push(Self::timing(descriptor));
+            }
+        }
+    }
+
+    /// Luminances are coded as in CTA-861.3, the minimum relative to the maximum.
+    fn hdr_metadata(data: &[u8]) -> HdrMetadata {
+        let luminance = |value: u8| 50.0 * 2f32.powf(value as f32 / 32.0);
+        let max_luminance = data.get(3).map(|value| luminance(*value));
+
+        HdrMetadata {
+            eotfs: data[1],
+            max_luminance,
+            max_frame_average: data.get(4).map(|value| luminance(*value)),
+            min_luminance: data.get(5).zip(max_luminance).map(|(value, max)| max * (*value as f32 / 255.0).powi(2) / 100.0),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use exodus_errors::ErrorKind;
+use crate::error;
+
+/// Color temperature of the white point of a monitor, at which ramps are left unchanged.
+pub const NEUTRAL_TEMPERATURE: u32 = 6500;
+pub const MIN_TEMPERATURE: u32 = 1000;
+pub const MAX_TEMPERATURE: u32 = 25000;
+
+/// Per-channel lookup tables applied by the CRTC to every pixel it scans out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GammaRamp {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamp {
+    pub fn new(red: Vec<u16>, green: Vec<u16>, blue: Vec<u16>) -> Result<Self, ErrorKind> {
+        if red.is_empty() || red.len() != green.len() || red.len() != blue.len() {
+            let err = ErrorKind::GAMMA_FAILED;
+            error!("Gamma ramp channels differ in size. - Red: {} - Green: {} - Blue: {} - ErrorKind: {:?}", red.len(), green.len(), blue.len(), err);
+            return Err(err);
+        }
+
+        Ok(Self { red, green, blue })
+    }
+
+    /// Ramp of `size` entries leaving colors unchanged.
+    pub fn linear(size: usize) -> Self {
+        Self::curve(size, 1.0, 1.0)
+    }
+
+    /// Ramp of `size` entries applying `gamma` and scaling by `brightness`, from `0.0` to `1.0`.
+    pub fn curve(size: usize, gamma: f32, brightness: f32) -> Self {
+        Self::temperature(size, NEUTRAL_TEMPERATURE, gamma, brightness)
+    }
+
+    /// Ramp of `size` entries shifting the white point to `kelvin`, for night light.
+    ///
+    /// `kelvin` is clamped to `MIN_TEMPERATURE..=MAX_TEMPERATURE`.
+    pub fn temperature(size: usize, kelvin: u32, gamma: f32, brightness: f32) -> Self {
+        let (red, green, blue) = white_point(kelvin);
+        let brightness = brightness.clamp(0.0, 1.0);
+        let gamma = if gamma > 0.0 { gamma } else { 1.0 };
+
+        let channel = |factor: f32| -> Vec<u16> {
+            (0..size).map(|index| {
+                let input = match size {
+                    1 => 1.0,
+                    _ => index as f32 / (size - 1) as f32,
+                };
+                let value = input.powf(1.0 / gamma) * brightness * factor;
+                (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+            }).collect()
+        };
+
+        Self { red: channel(red), green: channel(green), blue: channel(blue) }
+    }
+
+    /// Number of entries of each channel.
+    pub fn size(&self) -> usize {
+        self.red.len()
+    }
+}
+
+/// Color of a black body at `kelvin`, relative to `NEUTRAL_TEMPERATURE`.
+///
+/// Uses Tanner Helland's fit of the black body curve.
+pub fn white_point(kelvin: u32) -> (f32, f32, f32) {
+    let (red, green, blue) = black_body(kelvin.clamp(MIN_TEMPERATURE, MAX_TEMPERATURE));
+    let (neutral_red, neutral_green, neutral_blue) = black_body(NEUTRAL_TEMPERATURE);
+
+    ((red / neutral_red).min(1.0), (green / neutral_green).min(1.0), (blue / neutral_blue).min(1.0))
+}
+
+fn black_body(kelvin: u32) -> (f32, f32, f32) {
+    let temperature = kelvin as f32 / 100.0;
+
+    let red = match temperature <= 66.0 {
+        true => 255.0,
+        false => 329.698_73 * (temperature - 60.0).powf(-0.133_204_76),
+    };
+
+    let green = match temperature <= 66.0 {
+        true => 99.470_8 * temperature.ln() - 161.119_57,
+        false => 288.122_17 * (temperature - 60.0).powf(-0.075_514_85),
+    };
+
+    let blue = match temperature {
+        t if t >= 66.0 => 255.0,
+        t if t <= 19.0 => 0.0,
+        t => 138.517_73 * (t - 10.0).ln() - 305.044_8,
+    };
+
+    (red.clamp(0.0, 255.0) / 255.0, green.clamp(0.0, 255.0) / 255.0, blue.clamp(0.0, 255.0) / 255.0)
+}
@@ -1,3 +1,6 @@
 pub mod device;
 pub mod surface;
 pub mod buffer;
+pub mod backend;
+pub mod edid;
+pub mod gamma;
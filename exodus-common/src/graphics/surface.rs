@@ -36,7 +36,12 @@ impl Surface {
             }
         }
 
-        let surface = unsafe { gbm_surface_create(device.as_ptr(), width, height, format as u32, flags) };
+        if !device.has_gbm() {
+            error!("Failed to create surface, GBM is not available. - ErrorKind: {:?}", ErrorKind::SURFACE_CREATE_FAILED);
+            return Err(ErrorKind::SURFACE_CREATE_FAILED);
+        }
+
+        let surface = unsafe { gbm_surface_create(device.as_ptr(), width, height, format.fourcc(), flags) };
         if surface.is_null() {
             error!("Failed to create surface. - ErrorKind: {:?}", ErrorKind::SURFACE_CREATE_FAILED);
             return Err(ErrorKind::SURFACE_CREATE_FAILED);
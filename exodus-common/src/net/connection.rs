@@ -1,13 +1,34 @@
-use std::{os::unix::net::UnixStream, io::{Read, Write}};
+use std::{collections::VecDeque, os::{fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd}, unix::net::UnixStream}, io::{Error as IoError, ErrorKind as IoErrorKind}, sync::Arc};
 use exodus_errors::ErrorKind;
-use crate::if_;
-use super::network_message::NetworkMessage;
+use super::network_message::{NetworkMessage, DEFAULT_BUFFER_SIZE, MAX_MESSAGE_FDS};
 
+/// Room for the `SCM_RIGHTS` ancillary data of one message.
+const CONTROL_SIZE: usize = unsafe { libc::CMSG_SPACE((MAX_MESSAGE_FDS * std::mem::size_of::<RawFd>()) as u32) } as usize;
 
+
+/// A framed connection over a unix socket.
+///
+/// Bytes read from the socket are accumulated until a complete frame is
+/// available, so partial reads and several coalesced messages are handled
+/// transparently. Writes that would block are kept and flushed later.
+///
+/// File descriptors travel as `SCM_RIGHTS` ancillary data, attached to the
+/// first byte of the frame they are sent with and counted in its header.
+/// Received descriptors are queued in order and handed to the messages
+/// announcing them, at most `MAX_MESSAGE_FDS` each. Descriptors no frame
+/// announces fail the connection.
 #[derive(Debug)]
 pub struct Connection {
     id: u32,
     socket: UnixStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    /// Received descriptors, with the stream offset where the read bringing them ended.
+    incoming_fds: VecDeque<(u64, OwnedFd)>,
+    /// Bytes read from the socket since the connection was made.
+    received: u64,
+    /// Descriptors waiting to be sent, with the offset in `outgoing` of the frame they go with.
+    outgoing_fds: VecDeque<(usize, Vec<Arc<OwnedFd>>)>,
 }
 
 impl Connection {
@@ -18,6 +39,11 @@ impl Connection {
         Self { 
             id: unsafe { ID += 1; ID },
             socket,
+            incoming: Vec::with_capacity(DEFAULT_BUFFER_SIZE),
+            outgoing: Vec::new(),
+            incoming_fds: VecDeque::new(),
+            received: 0,
+            outgoing_fds: VecDeque::new(),
         } 
     }
 
@@ -40,19 +66,208 @@ impl Connection {
     #[inline]
     pub fn id(&self) -> u32 { self.id }
 
+    /// Returns the next complete message.
+    ///
+    /// On a blocking socket this waits until a whole frame has arrived. On a
+    /// non-blocking socket `Ok(None)` is returned when no complete frame is
+    /// buffered yet, the bytes received so far are kept for the next call.
     pub fn buffer(&mut self) -> Result<Option<NetworkMessage>, ErrorKind> {
-        let mut msg = NetworkMessage::default();
+        let mut chunk = [0u8; DEFAULT_BUFFER_SIZE];
+
+        loop {
+            if let Some(msg) = self.take_message()? {
+                return Ok(Some(msg));
+            }
 
-        match self.socket.read(msg.get_buffer()) {
-            Ok(size) => if_!(size > 0, return Ok(Some(msg))),
-            Err(_) => return Err(ErrorKind::CONNECTION_CLOSED),
+            match self.recv(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::CONNECTION_CLOSED),
+                Ok(size) => self.incoming.extend_from_slice(&chunk[..size]),
+                Err(e) if e.kind() == IoErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == IoErrorKind::Interrupted => continue,
+                Err(_) => return Err(ErrorKind::CONNECTION_CLOSED),
+            }
         }
+    }
 
-        Ok(None)
+    /// Returns the next complete message and takes its file descriptors out of it.
+    ///
+    /// The descriptors are closed when dropped, whether or not the message announced them.
+    pub fn recv_with_fds(&mut self) -> Result<Option<(NetworkMessage, Vec<OwnedFd>)>, ErrorKind> {
+        Ok(self.buffer()?.map(|mut msg| {
+            let fds = msg.take_fds();
+            (msg, fds)
+        }))
     }
 
-    pub fn send(&mut self, mut msg: NetworkMessage) {
-        self.socket.write(msg.get_buffer()).unwrap_or_default();
+    /// Splits the first complete frame off the incoming bytes, with the descriptors it announces.
+    fn take_message(&mut self) -> Result<Option<NetworkMessage>, ErrorKind> {
+        match NetworkMessage::frame_size(&self.incoming)? {
+            Some(size) if self.incoming.len() >= size => {
+                let frame = self.incoming.drain(..size).collect::<Vec<u8>>();
+                let mut msg = NetworkMessage::from_frame(frame)?;
+
+                // The descriptors arrive with the first byte of the frame, missing ones mean a broken peer.
+                let count = msg.fd_count();
+                if count > MAX_MESSAGE_FDS || count > self.incoming_fds.len() {
+                    return Err(ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+                }
+
+                msg.set_fds(self.incoming_fds.drain(..count).map(|(_, fd)| Arc::new(fd)).collect());
+
+                // Descriptors read no later than the end of this frame cannot belong to a following one.
+                let end = self.received - self.incoming.len() as u64;
+                if self.incoming_fds.front().is_some_and(|(limit, _)| *limit <= end) {
+                    return Err(ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+                }
+
+                Ok(Some(msg))
+            },
+            _ => Ok(None),
+        }
     }
+
+    /// Reads bytes from the socket, queueing the descriptors that come with them.
+    fn recv(&mut self, chunk: &mut [u8]) -> Result<usize, IoError> {
+        let mut control = [0u8; CONTROL_SIZE];
+        let mut iov = libc::iovec { iov_base: chunk.as_mut_ptr() as *mut libc::c_void, iov_len: chunk.len() };
+
+        let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        header.msg_controllen = control.len() as _;
+
+        let size = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut header, libc::MSG_CMSG_CLOEXEC) };
+        if size < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        let limit = self.received + size as u64;
+        self.received = limit;
+
+        let mut message = unsafe { libc::CMSG_FIRSTHDR(&header) };
+        while !message.is_null() {
+            let cmsg = unsafe { &*message };
+
+            if cmsg.cmsg_level == libc::SOL_SOCKET && cmsg.cmsg_type == libc::SCM_RIGHTS {
+                let length = cmsg.cmsg_len as usize - unsafe { libc::CMSG_LEN(0) } as usize;
+                let data = unsafe { libc::CMSG_DATA(message) } as *const RawFd;
+
+                for index in 0..length / std::mem::size_of::<RawFd>() {
+                    let fd = unsafe { data.add(index).read_unaligned() };
+                    self.incoming_fds.push_back((limit, unsafe { OwnedFd::from_raw_fd(fd) }));
+                }
+            }
+
+            message = unsafe { libc::CMSG_NXTHDR(&header, message) };
+        }
+
+        // Descriptors that did not fit are closed by the kernel, the stream cannot be trusted anymore.
+        // Only the descriptors of the incomplete frame are queued before a read, which brings those of one more at most.
+        if header.msg_flags & libc::MSG_CTRUNC != 0 || self.incoming_fds.len() > 2 * MAX_MESSAGE_FDS {
+            return Err(IoError::from(IoErrorKind::InvalidData));
+        }
+
+        Ok(size as usize)
+    }
+
+    /// Queues the message, with the file descriptors written in it, and writes as much as the socket accepts.
+    pub fn send(&mut self, mut msg: NetworkMessage) -> Result<(), ErrorKind> {
+        if msg.fds().len() > MAX_MESSAGE_FDS {
+            return Err(ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+        }
+
+        if !msg.fds().is_empty() {
+            self.outgoing_fds.push_back((self.outgoing.len(), msg.fds().to_vec()));
+        }
+
+        self.outgoing.extend_from_slice(msg.as_frame());
+        self.flush()
+    }
+
+    /// Queues the message with duplicates of `fds`, read back in order by the peer after those of the message.
+    pub fn send_with_fds(&mut self, mut msg: NetworkMessage, fds: &[BorrowedFd]) -> Result<(), ErrorKind> {
+        for fd in fds {
+            let fd = fd.try_clone_to_owned().map_err(|_| ErrorKind::NETWORKMESSAGE_FDS_INVALID)?;
+            msg.write_fd(Arc::new(fd));
+        }
+
+        self.send(msg)
+    }
+
+    /// Writes pending outgoing bytes, stopping without error when the socket would block.
+    pub fn flush(&mut self) -> Result<(), ErrorKind> {
+        while !self.outgoing.is_empty() {
+            match self.write() {
+                Ok(0) => return Err(ErrorKind::CONNECTION_CLOSED),
+                Ok(size) => {
+                    self.outgoing.drain(..size);
+                    for (offset, _) in self.outgoing_fds.iter_mut() {
+                        *offset -= size;
+                    }
+                },
+                Err(e) if e.kind() == IoErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == IoErrorKind::Interrupted => continue,
+                Err(_) => return Err(ErrorKind::CONNECTION_CLOSED),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes outgoing bytes up to the next frame with descriptors, sending those of the first byte with it.
+    fn write(&mut self) -> Result<usize, IoError> {
+        let attached = self.outgoing_fds.front().is_some_and(|(offset, _)| *offset == 0);
+        let end = self.outgoing_fds.iter()
+            .map(|(offset, _)| *offset)
+            .find(|offset| *offset > 0)
+            .unwrap_or(self.outgoing.len());
+
+        let mut iov = libc::iovec { iov_base: self.outgoing.as_mut_ptr() as *mut libc::c_void, iov_len: end };
+        let mut control = [0u8; CONTROL_SIZE];
+
+        let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+
+        if attached {
+            let fds = &self.outgoing_fds[0].1;
+            let length = (fds.len() * std::mem::size_of::<RawFd>()) as u32;
+            header.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            header.msg_controllen = unsafe { libc::CMSG_SPACE(length) } as _;
+
+            unsafe {
+                let cmsg = libc::CMSG_FIRSTHDR(&header);
+                (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(length) as _;
+
+                let data = libc::CMSG_DATA(cmsg) as *mut RawFd;
+                for (index, fd) in fds.iter().enumerate() {
+                    data.add(index).write_unaligned(fd.as_raw_fd());
+                }
+            }
+        }
+
+        let size = unsafe { libc::sendmsg(self.socket.as_raw_fd(), &header, libc::MSG_NOSIGNAL) };
+        if size < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        if attached && size > 0 {
+            self.outgoing_fds.pop_front();
+        }
+
+        Ok(size as usize)
+    }
+
+    /// Returns `true` while some outgoing bytes could not be written yet.
+    #[inline]
+    pub fn has_pending_output(&self) -> bool { !self.outgoing.is_empty() }
 }
 
+impl AsRawFd for Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
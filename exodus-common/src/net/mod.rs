@@ -1,2 +1,3 @@
 pub mod connection;
-pub mod network_message;
\ No newline at end of file
+pub mod network_message;
+pub mod protocol_error;
@@ -1,17 +1,58 @@
+use std::{os::fd::OwnedFd, sync::Arc};
 use exodus_errors::ErrorKind;
 use exodus_protocols::protocol_code::ProtocolCode;
+use exodus_protocols::wire::{Message, Event, WireReader, WireWriter};
 
 pub const DEFAULT_BUFFER_SIZE: usize          = 0x200;
 
-#[derive(Debug)]
+/// Size in bytes of the header that prefixes every message on the wire.
+///
+/// | Offset | Size | Field                           |
+/// |--------|------|---------------------------------|
+/// | 0      | 4    | `code` - i32, `ProtocolCode`    |
+/// | 4      | 4    | `flags` - u32, `MESSAGE_FLAG_*` |
+/// |        |      | and `MESSAGE_FDS_MASK`          |
+/// | 8      | 4    | `serial` - u32                  |
+/// | 12     | 4    | `length` - u32, payload bytes   |
+pub const HEADER_SIZE: usize                  = 16;
+
+/// Largest payload accepted from the wire, anything bigger is treated as a corrupted stream.
+pub const MAX_PAYLOAD_SIZE: usize             = 0x100000;
+
+/// Most file descriptors a single message can carry.
+pub const MAX_MESSAGE_FDS: usize              = 8;
+
+const CODE_OFFSET: usize                      = 0;
+const FLAGS_OFFSET: usize                     = 4;
+const SERIAL_OFFSET: usize                    = 8;
+const LENGTH_OFFSET: usize                    = 12;
+
+/// The message answers the request carrying the same serial.
+pub const MESSAGE_FLAG_REPLY: u32             = 0x1;
+/// The message is an event, its serial is the request that caused it or `0` when unsolicited.
+pub const MESSAGE_FLAG_EVENT: u32             = 0x2;
+/// Bits of the flags holding the number of file descriptors sent with the message.
+pub const MESSAGE_FDS_MASK: u32               = 0xff000000;
+const MESSAGE_FDS_SHIFT: u32                  = 24;
+
+/// A single protocol frame, header followed by the payload.
+///
+/// The read cursor only walks the payload, the header is accessed through
+/// `code`, `flags`, `serial` and `payload_len`. File descriptors travel next
+/// to the frame and are read in the order they were written.
+#[derive(Debug, Clone)]
 pub struct NetworkMessage {
     index: usize,
-    buffer: Vec<u8>
+    buffer: Vec<u8>,
+    fds: Vec<Arc<OwnedFd>>,
+    fd_index: usize,
 }
 
 impl Default for NetworkMessage {
     fn default() -> Self {
-        Self { index: 3, buffer: Vec::with_capacity(DEFAULT_BUFFER_SIZE) }
+        let mut buffer = Vec::with_capacity(DEFAULT_BUFFER_SIZE);
+        buffer.resize(HEADER_SIZE, 0);
+        Self { index: HEADER_SIZE, buffer, fds: Vec::new(), fd_index: 0 }
     }
 }
 
@@ -23,156 +64,227 @@ impl NetworkMessage {
         msg
     }
 
+    /// Creates the reply to `request`, echoing its serial.
+    pub fn reply(protocol: ProtocolCode, request: &NetworkMessage) -> Self {
+        let mut msg = Self::new(protocol);
+        msg.set_serial(request.serial());
+        msg.set_flags(MESSAGE_FLAG_REPLY);
+        msg
+    }
+
+    /// Creates an event, `serial` is the request that caused it or `0` when unsolicited.
+    pub fn event(protocol: ProtocolCode, serial: u32) -> Self {
+        let mut msg = Self::new(protocol);
+        msg.set_serial(serial);
+        msg.set_flags(MESSAGE_FLAG_EVENT);
+        msg
+    }
+
+    /// Encodes a typed message.
+    pub fn encode<M: Message>(message: &M) -> Self {
+        let mut msg = Self::new(M::CODE);
+        message.encode(&mut msg);
+        msg
+    }
+
+    /// Encodes the typed reply to `request`, echoing its serial.
+    pub fn encode_reply<M: Message>(request: &NetworkMessage, reply: &M) -> Self {
+        let mut msg = Self::reply(M::CODE, request);
+        reply.encode(&mut msg);
+        msg
+    }
+
+    /// Encodes a typed event, `serial` is the request that caused it or `0` when unsolicited.
+    pub fn encode_event<M: Event>(serial: u32, event: &M) -> Self {
+        let mut msg = Self::event(M::CODE, serial);
+        event.encode(&mut msg);
+        msg
+    }
+
+    /// Decodes the payload as `M`, failing when the message carries another code.
+    pub fn decode<M: Message>(&mut self) -> Result<M, ErrorKind> {
+        if self.code()? != M::CODE as i32 {
+            return Err(ErrorKind::NETWORKMESSAGE_FAILED);
+        }
+
+        M::decode(self)
+    }
+
+    /// Returns the total size of the frame at the start of `bytes`, header included.
+    ///
+    /// Returns `Ok(None)` while the header itself is still incomplete.
+    pub fn frame_size(bytes: &[u8]) -> Result<Option<usize>, ErrorKind> {
+        if bytes.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let length = u32::from_le_bytes([bytes[LENGTH_OFFSET], bytes[LENGTH_OFFSET + 1], bytes[LENGTH_OFFSET + 2], bytes[LENGTH_OFFSET + 3]]) as usize;
+        if length > MAX_PAYLOAD_SIZE {
+            return Err(ErrorKind::NETWORKMESSAGE_TOO_LARGE);
+        }
+
+        Ok(Some(HEADER_SIZE + length))
+    }
+
+    /// Builds a message from a complete frame as returned by `frame_size`.
+    pub fn from_frame(frame: Vec<u8>) -> Result<Self, ErrorKind> {
+        match Self::frame_size(&frame)? {
+            Some(size) if size == frame.len() => Ok(Self { index: HEADER_SIZE, buffer: frame, fds: Vec::new(), fd_index: 0 }),
+            _ => Err(ErrorKind::NETWORKMESSAGE_FAILED),
+        }
+    }
+
+    /// Returns the encoded frame, with the header length and descriptor count updated.
+    pub fn as_frame(&mut self) -> &[u8] {
+        let length = (self.payload_len() as u32).to_le_bytes();
+        self.buffer[LENGTH_OFFSET..LENGTH_OFFSET + 4].copy_from_slice(&length);
+
+        let flags = (self.flags() & !MESSAGE_FDS_MASK) | (((self.fds.len() as u32) << MESSAGE_FDS_SHIFT) & MESSAGE_FDS_MASK);
+        self.set_flags(flags);
+        &self.buffer
+    }
+
+    /// Number of file descriptors the header announces, received before the frame completes.
+    #[inline]
+    pub fn fd_count(&self) -> usize { ((self.flags() & MESSAGE_FDS_MASK) >> MESSAGE_FDS_SHIFT) as usize }
+
+    /// The file descriptors sent with the message, in order.
+    #[inline]
+    pub fn fds(&self) -> &[Arc<OwnedFd>] { &self.fds }
+
+    /// Attaches the descriptors received with the frame.
+    pub(crate) fn set_fds(&mut self, fds: Vec<Arc<OwnedFd>>) {
+        self.fds = fds;
+        self.fd_index = 0;
+    }
+
+    /// Takes the descriptors out of the message, duplicating those still shared with a clone.
+    pub fn take_fds(&mut self) -> Vec<OwnedFd> {
+        self.fd_index = 0;
+        self.fds.drain(..)
+            .filter_map(|fd| Arc::try_unwrap(fd).or_else(|fd| fd.try_clone()).ok())
+            .collect()
+    }
+
     #[inline]
     pub fn get_index(&self) -> usize { self.index }
 
+    /// Number of payload bytes, the header is not included.
+    #[inline]
+    pub fn payload_len(&self) -> usize { self.buffer.len() - HEADER_SIZE }
+
+    /// Number of payload bytes not read yet.
     #[inline]
-    pub fn get_buffer(&mut self) -> &mut Vec<u8> { &mut self.buffer }
+    pub fn remaining(&self) -> usize { self.buffer.len() - self.index }
 
     pub fn write_protocol(&mut self, protocol: i32) {
-        let bytes = protocol.to_le_bytes();
-        self.buffer.insert(0, bytes[0]);
-        self.buffer.insert(1, bytes[1]);
-        self.buffer.insert(2, bytes[2]);
-        self.buffer.insert(3, bytes[3]);
+        self.buffer[CODE_OFFSET..CODE_OFFSET + 4].copy_from_slice(&protocol.to_le_bytes());
     }
 
     pub fn code(&self) -> Result<i32, ErrorKind> {
-        if self.buffer.len() >= 4 {
-            let value = i32::from_le_bytes([self.buffer[0], self.buffer[1], self.buffer[2], self.buffer[3]]);
-            return Ok(value);
-        }
+        let bytes = &self.buffer[CODE_OFFSET..CODE_OFFSET + 4];
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
 
-        Err(ErrorKind::NETWORKMESSAGE_FAILED)
+    pub fn flags(&self) -> u32 {
+        let bytes = &self.buffer[FLAGS_OFFSET..FLAGS_OFFSET + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
     }
 
-    pub fn read_u8(&mut self) -> Result<u8, ErrorKind> {
-        if self.buffer.is_empty() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
+    pub fn set_flags(&mut self, flags: u32) {
+        self.buffer[FLAGS_OFFSET..FLAGS_OFFSET + 4].copy_from_slice(&flags.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn is_reply(&self) -> bool { self.flags() & MESSAGE_FLAG_REPLY != 0 }
+
+    #[inline]
+    pub fn is_event(&self) -> bool { self.flags() & MESSAGE_FLAG_EVENT != 0 }
 
-        let value = self.buffer[self.index];
-        self.index += 1;
-        Ok(value)
+    pub fn serial(&self) -> u32 {
+        let bytes = &self.buffer[SERIAL_OFFSET..SERIAL_OFFSET + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
     }
 
-    pub fn read_u16(&mut self) -> Result<u16, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 1 >= self.buffer.len() {
+    pub fn set_serial(&mut self, serial: u32) {
+        self.buffer[SERIAL_OFFSET..SERIAL_OFFSET + 4].copy_from_slice(&serial.to_le_bytes());
+    }
+
+    /// Reads the next `N` bytes of the payload, advancing the cursor.
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ErrorKind> {
+        if self.index + N > self.buffer.len() {
             return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
         }
 
-        let value = u16::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1]]);
-        self.index += 2;
-        Ok(value)
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(&self.buffer[self.index..self.index + N]);
+        self.index += N;
+        Ok(bytes)
     }
 
-    pub fn read_u32(&mut self) -> Result<u32, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 3 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
+    pub fn read_u8(&mut self) -> Result<u8, ErrorKind> {
+        Ok(u8::from_le_bytes(self.read_array()?))
+    }
 
-        let value = u32::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1], self.buffer[self.index + 2], self.buffer[self.index + 3]]);
-        self.index += 4;
-        Ok(value)
+    pub fn read_u16(&mut self) -> Result<u16, ErrorKind> {
+        Ok(u16::from_le_bytes(self.read_array()?))
     }
 
-    pub fn read_u64(&mut self) -> Result<u64, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 7 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
+    pub fn read_u32(&mut self) -> Result<u32, ErrorKind> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
 
-        let value = u64::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1], self.buffer[self.index + 2], self.buffer[self.index + 3], self.buffer[self.index + 4], self.buffer[self.index + 5], self.buffer[self.index + 6], self.buffer[self.index + 7]]);
-        self.index += 8;
-        Ok(value)
+    pub fn read_u64(&mut self) -> Result<u64, ErrorKind> {
+        Ok(u64::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_i8(&mut self) -> Result<i8, ErrorKind> {
-        if self.buffer.is_empty() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
-
-        let value = i8::from_le_bytes([self.buffer[self.index]]);
-        self.index += 1;
-        Ok(value)
+        Ok(i8::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_i16(&mut self) -> Result<i16, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 1 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
-
-        let value = i16::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1]]);
-        self.index += 2;
-        Ok(value)
+        Ok(i16::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_i32(&mut self) -> Result<i32, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 3 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
-
-        let value = i32::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1], self.buffer[self.index + 2], self.buffer[self.index + 3]]);
-        self.index += 4;
-        Ok(value)
+        Ok(i32::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_i64(&mut self) -> Result<i64, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 7 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
-
-        let value = i64::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1], self.buffer[self.index + 2], self.buffer[self.index + 3], self.buffer[self.index + 4], self.buffer[self.index + 5], self.buffer[self.index + 6], self.buffer[self.index + 7]]);
-        self.index += 8;
-        Ok(value)
+        Ok(i64::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_f32(&mut self) -> Result<f32, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 3 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
-
-        let value = f32::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1], self.buffer[self.index + 2], self.buffer[self.index + 3]]);
-        self.index += 4;
-        Ok(value)
+        Ok(f32::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_f64(&mut self) -> Result<f64, ErrorKind> {
-        if self.buffer.is_empty() || self.index + 7 >= self.buffer.len() {
-            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
-        }
-
-        let value = f64::from_le_bytes([self.buffer[self.index], self.buffer[self.index + 1], self.buffer[self.index + 2], self.buffer[self.index + 3], self.buffer[self.index + 4], self.buffer[self.index + 5], self.buffer[self.index + 6], self.buffer[self.index + 7]]);
-        self.index += 8;
-        Ok(value)
+        Ok(f64::from_le_bytes(self.read_array()?))
     }
     
     pub fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, ErrorKind> {
-        if self.buffer.is_empty() || self.index + length >= self.buffer.len() {
+        if self.index + length > self.buffer.len() {
             return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
         }
 
         let bytes = Vec::from(&self.buffer[self.index..self.index + length]);
+        self.index += length;
         Ok(bytes)
     }
 
     pub fn read_string_utf16(&mut self) -> Result<String, ErrorKind> {
         let length = self.read_u32()? as usize;
-        let mut bytes = Vec::with_capacity(length);
+        let mut bytes = Vec::with_capacity(length.min(self.remaining() / 2));
         for _ in 0..length {
             bytes.push(self.read_u16()?);
         }
-        let string = String::from_utf16(&bytes).unwrap();
-        Ok(string)
+        String::from_utf16(&bytes).map_err(|_| ErrorKind::NETWORKMESSAGE_FAILED)
     }
 
     pub fn read_string_utf8(&mut self) -> Result<String, ErrorKind> {
         let length = self.read_u32()? as usize;
-        let mut bytes = Vec::with_capacity(length);
-        for _ in 0..length {
-            bytes.push(self.read_u8()?);
-        }
-        let string = String::from_utf8(bytes).unwrap();
-        Ok(string)
+        let bytes = self.read_bytes(length)?;
+        String::from_utf8(bytes).map_err(|_| ErrorKind::NETWORKMESSAGE_FAILED)
     }
 
     pub fn write_u8(&mut self, value: u8) {
@@ -278,12 +390,62 @@ impl NetworkMessage {
         }
     }
 
+    /// Attaches a file descriptor, sent as `SCM_RIGHTS` ancillary data with the frame.
+    pub fn write_fd(&mut self, fd: Arc<OwnedFd>) {
+        self.fds.push(fd);
+    }
+
+    /// Reads the next file descriptor sent with the message.
+    pub fn read_fd(&mut self) -> Result<Arc<OwnedFd>, ErrorKind> {
+        let fd = self.fds.get(self.fd_index).cloned().ok_or(ErrorKind::NETWORKMESSAGE_FDS_INVALID)?;
+        self.fd_index += 1;
+        Ok(fd)
+    }
+
+    /// Drops the payload and the file descriptors, the header is kept.
     pub fn clear(&mut self) { 
-        self.index = 0;
-        self.buffer.clear();
+        self.index = HEADER_SIZE;
+        self.buffer.truncate(HEADER_SIZE);
+        self.fds.clear();
+        self.fd_index = 0;
     }
 
+    /// Rewinds the read cursors to the start of the payload and the first file descriptor.
     pub fn reset(&mut self) {
-        self.index = 0;
+        self.index = HEADER_SIZE;
+        self.fd_index = 0;
     }
 }
+
+macro_rules! forward_wire {
+    ($($write:ident, $read:ident => $ty:ty;)*) => {
+        impl WireWriter for NetworkMessage {
+            $(fn $write(&mut self, value: $ty) { NetworkMessage::$write(self, value) })*
+
+            fn write_string_utf8(&mut self, value: &str) { NetworkMessage::write_string_utf8(self, value) }
+            fn write_string_utf16(&mut self, value: &str) { NetworkMessage::write_string_utf16(self, value) }
+            fn write_fd(&mut self, fd: Arc<OwnedFd>) { NetworkMessage::write_fd(self, fd) }
+        }
+
+        impl WireReader for NetworkMessage {
+            $(fn $read(&mut self) -> Result<$ty, ErrorKind> { NetworkMessage::$read(self) })*
+
+            fn read_string_utf8(&mut self) -> Result<String, ErrorKind> { NetworkMessage::read_string_utf8(self) }
+            fn read_string_utf16(&mut self) -> Result<String, ErrorKind> { NetworkMessage::read_string_utf16(self) }
+            fn read_fd(&mut self) -> Result<Arc<OwnedFd>, ErrorKind> { NetworkMessage::read_fd(self) }
+        }
+    };
+}
+
+forward_wire! {
+    write_u8, read_u8 => u8;
+    write_u16, read_u16 => u16;
+    write_u32, read_u32 => u32;
+    write_u64, read_u64 => u64;
+    write_i8, read_i8 => i8;
+    write_i16, read_i16 => i16;
+    write_i32, read_i32 => i32;
+    write_i64, read_i64 => i64;
+    write_f32, read_f32 => f32;
+    write_f64, read_f64 => f64;
+}
@@ -0,0 +1,83 @@
+use exodus_errors::ErrorKind;
+use exodus_protocols::{protocol_code::ProtocolCode, messages::ErrorMessage};
+use super::network_message::{NetworkMessage, MESSAGE_FLAG_REPLY};
+
+/// A `ProtocolError` reply, see `ErrorMessage` for the layout.
+#[derive(Debug, Clone)]
+pub struct ProtocolErrorReply {
+    error: u32,
+    code: i32,
+    serial: u32,
+    message: Option<String>,
+}
+
+impl ProtocolErrorReply {
+    /// Creates the error answering `request`.
+    pub fn new(kind: ErrorKind, request: &NetworkMessage) -> Self {
+        Self {
+            error: kind.code(),
+            code: request.code().unwrap_or(ProtocolCode::ProtocolNone as i32),
+            serial: request.serial(),
+            message: None,
+        }
+    }
+
+    /// Creates the error for a request that is no longer at hand.
+    pub fn from_request(kind: ErrorKind, code: i32, serial: u32) -> Self {
+        Self { error: kind.code(), code, serial, message: None }
+    }
+
+    pub fn with_message(mut self, message: &str) -> Self {
+        self.message = Some(message.to_string());
+        self
+    }
+
+    /// The error as `ErrorKind`, codes unknown to this version map to `PROTOCOL_FAILED`.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::from_code(self.error).unwrap_or(ErrorKind::PROTOCOL_FAILED)
+    }
+
+    /// The raw error code as sent by the server.
+    pub fn error_code(&self) -> u32 {
+        self.error
+    }
+
+    /// The `ProtocolCode` of the failed request.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// The serial of the failed request.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn encode(&self) -> NetworkMessage {
+        let payload = ErrorMessage {
+            error: self.error,
+            code: self.code,
+            serial: self.serial,
+            message: self.message.clone().unwrap_or_default(),
+        };
+
+        let mut msg = NetworkMessage::encode(&payload);
+        msg.set_serial(self.serial);
+        msg.set_flags(MESSAGE_FLAG_REPLY);
+        msg
+    }
+
+    pub fn decode(msg: &mut NetworkMessage) -> Result<Self, ErrorKind> {
+        let payload: ErrorMessage = msg.decode()?;
+
+        Ok(Self {
+            error: payload.error,
+            code: payload.code,
+            serial: payload.serial,
+            message: if payload.message.is_empty() { None } else { Some(payload.message) },
+        })
+    }
+}
@@ -0,0 +1,62 @@
+use std::os::fd::AsRawFd;
+use exodus_errors::ErrorKind;
+
+use crate::enums::PixelFormat;
+use crate::graphics::backend::{BufferAllocator, MemoryAllocator};
+use crate::graphics::buffer::{Buffer, DmaBuf, DmaBufPlane, DRM_FORMAT_MOD_INVALID};
+
+#[test]
+fn legacy_shared_buffer() {
+
+    let mut buffer = Buffer::legacy(None, 4, 3, PixelFormat::ARGB8888).unwrap();
+    assert_eq!((buffer.width(), buffer.height(), buffer.stride(), buffer.bpp()), (4, 3, 16, 32));
+
+    // Shared memory has no handle to scan out from, only a memfd.
+    assert_eq!(buffer.handle(), 0);
+    assert!(buffer.fd().is_some());
+
+    buffer.clear().unwrap();
+    buffer.write(1, 1, 2, 2, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(buffer.read(0, 1, 4, 1).unwrap(), vec![0, 1, 2, 0]);
+    assert_eq!(buffer.read(1, 2, 2, 1).unwrap(), vec![3, 4]);
+    assert_eq!(buffer.write(3, 0, 2, 1, &[0, 0]), Err(ErrorKind::BUFFER_OUT_OF_BOUNDS));
+}
+
+#[test]
+fn legacy_shared_buffer_mapped() {
+
+    let mut buffer = Buffer::legacy(None, 2, 2, PixelFormat::XRGB8888).unwrap();
+    buffer.write(0, 0, 2, 2, &[0x11, 0x22, 0x33, 0x44]).unwrap();
+
+    // Another mapping of the memfd, as a process receiving it would make, sees the pixels.
+    let fd = buffer.fd().unwrap().as_raw_fd();
+    let pixels = unsafe {
+        let map = libc::mmap(std::ptr::null_mut(), 16, libc::PROT_READ, libc::MAP_SHARED, fd, 0);
+        assert_ne!(map, libc::MAP_FAILED);
+        let pixels = std::slice::from_raw_parts(map as *const u32, 4).to_vec();
+        libc::munmap(map, 16);
+        pixels
+    };
+
+    assert_eq!(pixels, vec![0x11, 0x22, 0x33, 0x44]);
+    assert_eq!(Buffer::legacy(None, 0, 2, PixelFormat::XRGB8888).err(), Some(ErrorKind::BUFFER_CREATE_FAILED));
+}
+
+#[test]
+fn dmabuf_without_gpu() {
+
+    // Only GBM buffers have DMA-BUFs, process memory and shared memory have none to export.
+    let buffer = Buffer::legacy(None, 2, 2, PixelFormat::XRGB8888).unwrap();
+    assert_eq!(buffer.export().err(), Some(ErrorKind::DMABUF_EXPORT_FAILED));
+    assert_eq!(Buffer::memory(2, 2, PixelFormat::XRGB8888).unwrap().export().err(), Some(ErrorKind::DMABUF_EXPORT_FAILED));
+
+    let dmabuf = DmaBuf {
+        width: 2,
+        height: 2,
+        fourcc: u32::from_le_bytes(*b"XR24"),
+        modifier: DRM_FORMAT_MOD_INVALID,
+        planes: vec![DmaBufPlane { fd: buffer.fd().unwrap().try_clone_to_owned().unwrap(), offset: 0, stride: 8 }],
+    };
+
+    assert_eq!(MemoryAllocator.import(&dmabuf, &[]).err(), Some(ErrorKind::DMABUF_IMPORT_FAILED));
+}
@@ -0,0 +1,165 @@
+use std::{fs::File, io::Write, os::{fd::{AsFd, AsRawFd}, unix::net::UnixStream}};
+use exodus_errors::ErrorKind;
+use exodus_protocols::{protocol_code::ProtocolCode, messages::CreatePoolRequest, wire::Fd};
+
+use crate::net::{connection::Connection, network_message::{NetworkMessage, MAX_MESSAGE_FDS}};
+
+fn message(code: ProtocolCode, value: u32) -> NetworkMessage {
+    let mut msg = NetworkMessage::new(code);
+    msg.write_u32(value);
+    msg
+}
+
+#[test]
+fn connection_coalesced_messages() {
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut sender = Connection::new(a);
+    let mut receiver = Connection::new(b);
+
+    sender.send(message(ProtocolCode::ProtocolGPUInfo, 1)).unwrap();
+    sender.send(message(ProtocolCode::ProtocolScreenInfo, 2)).unwrap();
+
+    let mut first = receiver.buffer().unwrap().unwrap();
+    let mut second = receiver.buffer().unwrap().unwrap();
+
+    assert_eq!(first.code().unwrap(), ProtocolCode::ProtocolGPUInfo as i32);
+    assert_eq!(first.read_u32().unwrap(), 1);
+    assert_eq!(second.code().unwrap(), ProtocolCode::ProtocolScreenInfo as i32);
+    assert_eq!(second.read_u32().unwrap(), 2);
+}
+
+#[test]
+fn connection_partial_message() {
+
+    let (mut a, b) = UnixStream::pair().unwrap();
+    let mut receiver = Connection::new(b);
+    receiver.set_nonblocking(true);
+
+    let mut msg = message(ProtocolCode::ProtocolGPUInfo, 7);
+    let frame = msg.as_frame().to_vec();
+
+    a.write_all(&frame[..5]).unwrap();
+    assert!(receiver.buffer().unwrap().is_none());
+
+    a.write_all(&frame[5..]).unwrap();
+    let mut msg = receiver.buffer().unwrap().unwrap();
+    assert_eq!(msg.read_u32().unwrap(), 7);
+}
+
+#[test]
+fn connection_closed() {
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut receiver = Connection::new(b);
+    drop(a);
+
+    assert!(receiver.buffer().is_err());
+}
+
+#[test]
+fn connection_fd_passing() {
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut sender = Connection::new(a);
+    let mut receiver = Connection::new(b);
+
+    let (file, _) = UnixStream::pair().unwrap();
+    file.set_nonblocking(true).unwrap();
+    let null = File::open("/dev/null").unwrap();
+
+    sender.send(message(ProtocolCode::ProtocolGPUInfo, 1)).unwrap();
+    sender.send_with_fds(message(ProtocolCode::ProtocolScreenInfo, 2), &[file.as_fd(), null.as_fd()]).unwrap();
+    sender.send(message(ProtocolCode::ProtocolGPUInfo, 3)).unwrap();
+
+    let (_, fds) = receiver.recv_with_fds().unwrap().unwrap();
+    assert!(fds.is_empty());
+
+    let (mut second, fds) = receiver.recv_with_fds().unwrap().unwrap();
+    assert_eq!(second.read_u32().unwrap(), 2);
+    assert_eq!(fds.len(), 2);
+
+    let flags = unsafe { libc::fcntl(fds[0].as_raw_fd(), libc::F_GETFL) };
+    assert!(flags & libc::O_NONBLOCK != 0);
+
+    let (_, fds) = receiver.recv_with_fds().unwrap().unwrap();
+    assert!(fds.is_empty());
+}
+
+#[test]
+fn connection_fds_decoded_in_order() {
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut sender = Connection::new(a);
+    let mut receiver = Connection::new(b);
+
+    let null = File::open("/dev/null").unwrap();
+    let request = CreatePoolRequest { size: 4096, fd: Fd::dup(null.as_fd()).unwrap() };
+    sender.send(NetworkMessage::encode(&request)).unwrap();
+    sender.send(NetworkMessage::encode(&CreatePoolRequest { size: 0, fd: Fd::default() })).unwrap();
+
+    let mut msg = receiver.buffer().unwrap().unwrap();
+    assert_eq!(msg.fd_count(), 1);
+    let decoded: CreatePoolRequest = msg.decode().unwrap();
+    assert_eq!(decoded.size, 4096);
+    assert!(decoded.fd.into_owned().is_some());
+
+    let mut msg = receiver.buffer().unwrap().unwrap();
+    let decoded: CreatePoolRequest = msg.decode().unwrap();
+    assert!(decoded.fd.as_fd().is_none());
+}
+
+#[test]
+fn connection_fds_limited() {
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut sender = Connection::new(a);
+
+    let null = File::open("/dev/null").unwrap();
+    let fds = vec![null.as_fd(); MAX_MESSAGE_FDS + 1];
+    assert_eq!(sender.send_with_fds(message(ProtocolCode::ProtocolGPUInfo, 1), &fds).unwrap_err(), ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+    assert!(!sender.has_pending_output());
+    drop(b);
+
+    // A header announcing a descriptor that was not sent.
+    let (mut a, b) = UnixStream::pair().unwrap();
+    let mut receiver = Connection::new(b);
+
+    let mut frame = message(ProtocolCode::ProtocolGPUInfo, 1).as_frame().to_vec();
+    frame[7] = 1;
+    a.write_all(&frame).unwrap();
+
+    assert_eq!(receiver.buffer().unwrap_err(), ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+}
+
+#[test]
+fn connection_unannounced_fds() {
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut receiver = Connection::new(b);
+
+    // A frame announcing no descriptor, sent with one.
+    let mut frame = message(ProtocolCode::ProtocolGPUInfo, 1).as_frame().to_vec();
+    let null = File::open("/dev/null").unwrap();
+    let fd = null.as_raw_fd();
+
+    let mut iov = libc::iovec { iov_base: frame.as_mut_ptr() as *mut libc::c_void, iov_len: frame.len() };
+    let mut control = [0u8; 64];
+
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    header.msg_controllen = unsafe { libc::CMSG_SPACE(std::mem::size_of_val(&fd) as u32) } as _;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&header);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of_val(&fd) as u32) as _;
+        (libc::CMSG_DATA(cmsg) as *mut i32).write_unaligned(fd);
+    }
+
+    assert_eq!(unsafe { libc::sendmsg(a.as_raw_fd(), &header, 0) }, frame.len() as isize);
+    assert_eq!(receiver.buffer().unwrap_err(), ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+}
@@ -0,0 +1,97 @@
+use exodus_errors::ErrorKind;
+
+use crate::graphics::edid::*;
+
+/// Text descriptor of `tag` holding `text`, newline terminated and space padded.
+fn text_descriptor(tag: u8, text: &str) -> [u8; 18] {
+    let mut descriptor = [0x20; 18];
+    descriptor[..5].copy_from_slice(&[0, 0, 0, tag, 0]);
+    descriptor[5..5 + text.len()].copy_from_slice(text.as_bytes());
+    descriptor[5 + text.len()] = b'\n';
+    descriptor
+}
+
+fn set_checksum(block: &mut [u8]) {
+    let sum = block[..127].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    block[127] = 0u8.wrapping_sub(sum);
+}
+
+/// A 27" 4K monitor with HDR10 support.
+fn edid() -> Vec<u8> {
+    let mut base = vec![0u8; 128];
+    base[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+    base[8..10].copy_from_slice(&[0x10, 0xac]);
+    base[10..12].copy_from_slice(&[0xb1, 0xa0]);
+    base[12..16].copy_from_slice(&0x12345678u32.to_le_bytes());
+    base[16..24].copy_from_slice(&[10, 30, 1, 4, 0xa5, 60, 34, 120]);
+    base[26] = 0b0000_0100;
+    base[33] = 0x50;
+    base[54..72].copy_from_slice(&[0x02, 0x3a, 0x80, 0x18, 0x71, 0x38, 0x2d, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    base[72..90].copy_from_slice(&text_descriptor(0xfc, "DELL U2720Q"));
+    base[90..108].copy_from_slice(&text_descriptor(0xff, "ABC123"));
+    base[108..126].copy_from_slice(&text_descriptor(0x10, ""));
+    base[126] = 1;
+    set_checksum(&mut base);
+
+    let mut cta = vec![0u8; 128];
+    cta[..4].copy_from_slice(&[0x02, 0x03, 15, 0x00]);
+    cta[4..8].copy_from_slice(&[0xe3, 0x05, 0xc0, 0x00]);
+    cta[8..15].copy_from_slice(&[0xe6, 0x06, 0x05, 0x01, 0x60, 0x50, 0x40]);
+    cta[15..33].copy_from_slice(&[0x04, 0x74, 0x00, 0x30, 0xf2, 0x70, 0x5a, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    set_checksum(&mut cta);
+
+    base.extend(cta);
+    base
+}
+
+#[test]
+fn edid_parse_identity() {
+
+    let edid = Edid::parse(&edid()).unwrap();
+    assert_eq!(edid.manufacturer, "DEL");
+    assert_eq!(edid.product, 0xa0b1);
+    assert_eq!(edid.serial, 0x12345678);
+    assert_eq!(edid.serial_string.as_deref(), Some("ABC123"));
+    assert_eq!(edid.name.as_deref(), Some("DELL U2720Q"));
+    assert_eq!((edid.week, edid.year), (Some(10), 2020));
+    assert_eq!((edid.version, edid.revision), (1, 4));
+    assert_eq!(edid.identity(), "DEL-a0b1-ABC123");
+}
+
+#[test]
+fn edid_parse_capabilities() {
+
+    let edid = Edid::parse(&edid()).unwrap();
+    assert!(edid.digital);
+    assert_eq!(edid.bit_depth, Some(8));
+    assert_eq!((edid.width, edid.height), (60, 34));
+    assert_eq!(edid.gamma, Some(2.2));
+    assert_eq!(edid.chromaticity.white, (321.0 / 1024.0, 0.0));
+
+    let preferred = edid.preferred().unwrap();
+    assert_eq!((preferred.width, preferred.height, preferred.refresh), (1920, 1080, 60.0));
+    assert_eq!(edid.timings.len(), 2);
+    assert_eq!((edid.timings[1].width, edid.timings[1].height, edid.timings[1].refresh), (3840, 2160, 30.0));
+
+    assert_eq!(edid.colorimetry, COLORIMETRY_BT2020_YCC | COLORIMETRY_BT2020_RGB);
+    let hdr = edid.hdr.unwrap();
+    assert_eq!(hdr.eotfs, EOTF_TRADITIONAL_SDR | EOTF_SMPTE_ST2084);
+    assert_eq!(hdr.max_luminance, Some(50.0 * 8.0));
+    assert!(hdr.min_luminance.unwrap() > 0.0);
+}
+
+#[test]
+fn edid_parse_invalid() {
+
+    let mut bytes = edid();
+    assert_eq!(Edid::parse(&bytes[..64]), Err(ErrorKind::EDID_INVALID));
+
+    bytes[20] ^= 0x01;
+    assert_eq!(Edid::parse(&bytes), Err(ErrorKind::EDID_INVALID));
+
+    // A broken extension only loses the extension data.
+    let mut bytes = edid();
+    bytes[200] ^= 0x01;
+    let edid = Edid::parse(&bytes).unwrap();
+    assert_eq!((edid.timings.len(), edid.hdr), (1, None));
+}
@@ -0,0 +1,47 @@
+use exodus_errors::ErrorKind;
+
+use crate::graphics::gamma::*;
+
+#[test]
+fn gamma_ramp_linear() {
+
+    let ramp = GammaRamp::linear(256);
+    assert_eq!(ramp.size(), 256);
+    assert_eq!((ramp.red[0], ramp.red[255]), (0, u16::MAX));
+    assert_eq!(ramp.red, ramp.green);
+    assert_eq!(ramp.red, ramp.blue);
+    assert_eq!(ramp.red[128], (128.0 / 255.0 * u16::MAX as f32).round() as u16);
+}
+
+#[test]
+fn gamma_ramp_curve() {
+
+    let ramp = GammaRamp::curve(256, 2.2, 0.5);
+    assert_eq!(ramp.red[255], (u16::MAX as f32 * 0.5).round() as u16);
+    assert!(ramp.red[64] > GammaRamp::curve(256, 1.0, 0.5).red[64]);
+    assert!(ramp.red.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[test]
+fn gamma_ramp_temperature() {
+
+    assert_eq!(white_point(NEUTRAL_TEMPERATURE), (1.0, 1.0, 1.0));
+
+    // Warmer white points dim blue first, then green.
+    let (red, green, blue) = white_point(3400);
+    assert_eq!(red, 1.0);
+    assert!(blue < green && green < 1.0);
+    assert_eq!(white_point(0), white_point(MIN_TEMPERATURE));
+
+    let ramp = GammaRamp::temperature(256, 3400, 1.0, 1.0);
+    assert_eq!(ramp.red[255], u16::MAX);
+    assert!(ramp.blue[255] < ramp.green[255]);
+}
+
+#[test]
+fn gamma_ramp_new() {
+
+    assert!(GammaRamp::new(vec![0; 4], vec![0; 4], vec![0; 4]).is_ok());
+    assert_eq!(GammaRamp::new(vec![0; 4], vec![0; 3], vec![0; 4]), Err(ErrorKind::GAMMA_FAILED));
+    assert_eq!(GammaRamp::new(vec![], vec![], vec![]), Err(ErrorKind::GAMMA_FAILED));
+}
@@ -1,2 +1,16 @@
 #[cfg(test)]
-pub mod network_message;
\ No newline at end of file
+pub mod network_message;
+#[cfg(test)]
+pub mod connection;
+#[cfg(test)]
+pub mod edid;
+#[cfg(test)]
+pub mod gamma;
+#[cfg(test)]
+pub mod transform;
+#[cfg(test)]
+pub mod buffer;
+#[cfg(test)]
+pub mod pixel_format;
+#[cfg(test)]
+pub mod power_state;
@@ -1,4 +1,8 @@
-use crate::net::network_message::NetworkMessage;
+use std::{fs::File, os::fd::{AsRawFd, OwnedFd}, sync::Arc};
+use exodus_errors::ErrorKind;
+use exodus_protocols::{protocol_code::ProtocolCode, messages::{GPUInfoRequest, GPUInfoReply}};
+
+use crate::net::{network_message::{NetworkMessage, HEADER_SIZE, MAX_PAYLOAD_SIZE, MESSAGE_FLAG_REPLY}, protocol_error::ProtocolErrorReply};
 
 #[test]
 fn network_message_write_u8() {
@@ -111,3 +115,154 @@ fn network_message_write_i64() {
     assert_eq!(msg.read_i64().unwrap(), 2);
     assert_eq!(msg.read_i64().unwrap(), 3);
 }
+
+#[test]
+fn network_message_read_out_of_bounds() {
+
+    let mut msg = NetworkMessage::default();
+    msg.write_u16(1);
+    msg.reset();
+
+    assert!(msg.read_u32().is_err());
+    assert_eq!(msg.read_u16().unwrap(), 1);
+    assert!(msg.read_u8().is_err());
+}
+
+#[test]
+fn network_message_frame_roundtrip() {
+
+    let mut msg = NetworkMessage::new(ProtocolCode::ProtocolGPUInfo);
+    msg.write_i32(5);
+    msg.write_string_utf8("exodus");
+
+    let frame = msg.as_frame().to_vec();
+    assert_eq!(NetworkMessage::frame_size(&frame).unwrap(), Some(frame.len()));
+    assert_eq!(frame.len(), HEADER_SIZE + 4 + 4 + 6);
+
+    let mut msg = NetworkMessage::from_frame(frame).unwrap();
+    assert_eq!(msg.code().unwrap(), ProtocolCode::ProtocolGPUInfo as i32);
+    assert_eq!(msg.read_i32().unwrap(), 5);
+    assert_eq!(msg.read_string_utf8().unwrap(), "exodus");
+}
+
+#[test]
+fn network_message_frame_incomplete_header() {
+
+    let mut msg = NetworkMessage::new(ProtocolCode::ProtocolEnumerateGPUS);
+    let frame = msg.as_frame().to_vec();
+
+    assert_eq!(NetworkMessage::frame_size(&frame[..HEADER_SIZE - 1]).unwrap(), None);
+}
+
+#[test]
+fn network_message_frame_too_large() {
+
+    let mut frame = vec![0u8; HEADER_SIZE];
+    frame[12..16].copy_from_slice(&((MAX_PAYLOAD_SIZE + 1) as u32).to_le_bytes());
+
+    assert!(NetworkMessage::frame_size(&frame).is_err());
+}
+
+#[test]
+fn network_message_reply_echoes_serial() {
+
+    let mut request = NetworkMessage::new(ProtocolCode::ProtocolGPUInfo);
+    request.set_serial(42);
+
+    let mut reply = NetworkMessage::reply(ProtocolCode::ProtocolGPUInfo, &request);
+    let reply = NetworkMessage::from_frame(reply.as_frame().to_vec()).unwrap();
+
+    assert_eq!(reply.serial(), 42);
+    assert_eq!(reply.flags(), MESSAGE_FLAG_REPLY);
+    assert!(reply.is_reply());
+    assert!(!reply.is_event());
+}
+
+#[test]
+fn network_message_unsolicited_event() {
+
+    let event = NetworkMessage::event(ProtocolCode::ProtocolScreenInfo, 0);
+
+    assert_eq!(event.serial(), 0);
+    assert!(event.is_event());
+    assert!(!event.is_reply());
+}
+
+#[test]
+fn network_message_protocol_error_roundtrip() {
+
+    let mut request = NetworkMessage::new(ProtocolCode::ProtocolScreenInfo);
+    request.set_serial(7);
+
+    let error = ProtocolErrorReply::new(ErrorKind::SCREEN_NOT_FOUND, &request).with_message("Screen not found.");
+    let mut reply = NetworkMessage::from_frame(error.encode().as_frame().to_vec()).unwrap();
+    assert_eq!(reply.serial(), 7);
+    assert!(reply.is_reply());
+
+    let error = ProtocolErrorReply::decode(&mut reply).unwrap();
+    assert_eq!(error.kind(), ErrorKind::SCREEN_NOT_FOUND);
+    assert_eq!(error.code(), ProtocolCode::ProtocolScreenInfo as i32);
+    assert_eq!(error.serial(), 7);
+    assert_eq!(error.message(), Some("Screen not found."));
+}
+
+#[test]
+fn network_message_protocol_error_unknown_code() {
+
+    let request = NetworkMessage::new(ProtocolCode::ProtocolGPUInfo);
+    let mut reply = ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).encode();
+    reply.clear();
+    reply.write_u32(u32::MAX);
+    reply.write_i32(ProtocolCode::ProtocolGPUInfo as i32);
+    reply.write_u32(0);
+    reply.write_string_utf8("");
+
+    let error = ProtocolErrorReply::decode(&mut reply).unwrap();
+    assert_eq!(error.kind(), ErrorKind::PROTOCOL_FAILED);
+    assert_eq!(error.error_code(), u32::MAX);
+    assert_eq!(error.message(), None);
+}
+
+#[test]
+fn network_message_typed_roundtrip() {
+
+    let mut request = NetworkMessage::encode(&GPUInfoRequest { gpu: 5 });
+    request.set_serial(3);
+    let mut request = NetworkMessage::from_frame(request.as_frame().to_vec()).unwrap();
+    assert_eq!(request.decode::<GPUInfoRequest>().unwrap(), GPUInfoRequest { gpu: 5 });
+
+    let reply = GPUInfoReply { gpu: 5, vendor: 0x10DE, vendor_name: "NVIDIA Corporation".to_string(), model: 0x1F02 };
+    let mut message = NetworkMessage::encode_reply(&request, &reply);
+    let mut message = NetworkMessage::from_frame(message.as_frame().to_vec()).unwrap();
+    assert_eq!(message.serial(), 3);
+    assert_eq!(message.decode::<GPUInfoReply>().unwrap(), reply);
+}
+
+#[test]
+fn network_message_typed_wrong_code() {
+
+    let mut message = NetworkMessage::encode(&GPUInfoRequest { gpu: 5 });
+    message.write_protocol(ProtocolCode::ProtocolScreenInfo as i32);
+    assert!(message.decode::<GPUInfoRequest>().is_err());
+}
+
+#[test]
+fn network_message_fds() {
+
+    let request = NetworkMessage::new(ProtocolCode::ProtocolGPUInfo);
+    let mut msg = NetworkMessage::reply(ProtocolCode::ProtocolGPUInfo, &request);
+    msg.write_fd(Arc::new(OwnedFd::from(File::open("/dev/null").unwrap())));
+    msg.write_fd(Arc::new(OwnedFd::from(File::open("/dev/null").unwrap())));
+    msg.as_frame();
+
+    assert_eq!(msg.fd_count(), 2);
+    assert!(msg.is_reply());
+
+    let first = msg.read_fd().unwrap().as_raw_fd();
+    msg.read_fd().unwrap();
+    assert_eq!(msg.read_fd().unwrap_err(), ErrorKind::NETWORKMESSAGE_FDS_INVALID);
+
+    msg.reset();
+    assert_eq!(msg.read_fd().unwrap().as_raw_fd(), first);
+    assert_eq!(msg.take_fds().len(), 2);
+}
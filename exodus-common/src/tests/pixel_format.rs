@@ -0,0 +1,50 @@
+use exodus_errors::ErrorKind;
+
+use crate::enums::{PixelFormat, PIXEL_FORMATS};
+use crate::graphics::buffer::{Buffer, DRM_FORMAT_MOD_INVALID};
+
+#[test]
+fn pixel_format_fourcc() {
+
+    assert_eq!(PixelFormat::XRGB8888.fourcc(), 0x34325258);
+    assert_eq!(PixelFormat::NV12.fourcc(), u32::from_le_bytes(*b"NV12"));
+
+    for (index, format) in PIXEL_FORMATS.into_iter().enumerate() {
+        assert_eq!(PixelFormat::from_fourcc(format.fourcc()), Ok(format));
+        assert_eq!(PixelFormat::try_from(index as u32), Ok(format));
+        assert_eq!(format as usize, index);
+    }
+
+    // Unknown values are rejected instead of read as XRGB8888.
+    assert_eq!(PixelFormat::from_fourcc(u32::from_le_bytes(*b"P010")), Err(ErrorKind::PIXEL_FORMAT_UNSUPPORTED));
+    assert_eq!(PixelFormat::try_from(PIXEL_FORMATS.len() as u32), Err(ErrorKind::PIXEL_FORMAT_UNSUPPORTED));
+}
+
+#[test]
+fn pixel_format_layout() {
+
+    assert_eq!((PixelFormat::RGB565.bpp(), PixelFormat::RGB565.size()), (16, 2));
+    assert_eq!((PixelFormat::ARGB16161616F.bpp(), PixelFormat::ARGB16161616F.size()), (64, 8));
+    assert_eq!(PixelFormat::XRGB2101010.bpp(), 32);
+
+    assert_eq!((PixelFormat::NV12.planes(), PixelFormat::NV12.subsampling()), (2, (2, 2)));
+    assert_eq!((PixelFormat::NV12.plane_bpp(0), PixelFormat::NV12.plane_bpp(1), PixelFormat::NV12.plane_bpp(2)), (8, 16, 0));
+    assert_eq!((PixelFormat::YUV420.planes(), PixelFormat::YUV420.plane_bpp(2)), (3, 8));
+    assert_eq!((PixelFormat::ABGR8888.planes(), PixelFormat::ABGR8888.subsampling()), (1, (1, 1)));
+
+    assert!(PixelFormat::ABGR8888.has_alpha() && !PixelFormat::XBGR8888.has_alpha());
+    assert_eq!(PixelFormat::ARGB8888.opaque(), PixelFormat::XRGB8888);
+    assert_eq!(PixelFormat::RGB565.opaque(), PixelFormat::RGB565);
+}
+
+#[test]
+fn pixel_format_buffers() {
+
+    // Process memory holds one `u32` per pixel, other layouts need a GPU.
+    let buffer = Buffer::memory(2, 2, PixelFormat::ABGR8888).unwrap();
+    assert_eq!((buffer.bpp(), buffer.modifier()), (32, DRM_FORMAT_MOD_INVALID));
+    assert_eq!(buffer.layout().len(), 1);
+
+    assert_eq!(Buffer::memory(2, 2, PixelFormat::NV12).err(), Some(ErrorKind::PIXEL_FORMAT_UNSUPPORTED));
+    assert_eq!(Buffer::legacy(None, 2, 2, PixelFormat::RGB565).err(), Some(ErrorKind::PIXEL_FORMAT_UNSUPPORTED));
+}
@@ -0,0 +1,15 @@
+use exodus_errors::ErrorKind;
+
+use crate::enums::PowerState;
+
+#[test]
+fn power_state_from_value() {
+
+    for state in [PowerState::On, PowerState::Standby, PowerState::Suspend, PowerState::Off] {
+        assert_eq!(PowerState::try_from(state as u32), Ok(state));
+    }
+
+    // Unknown values are rejected instead of read as on.
+    assert_eq!(PowerState::try_from(4), Err(ErrorKind::POWER_STATE_INVALID));
+    assert_eq!(PowerState::try_from(u32::MAX), Err(ErrorKind::POWER_STATE_INVALID));
+}
@@ -0,0 +1,51 @@
+use exodus_errors::ErrorKind;
+
+use crate::enums::SurfaceTransform;
+
+const TRANSFORMS: [SurfaceTransform; 8] = [
+    SurfaceTransform::Normal,
+    SurfaceTransform::Rotate90,
+    SurfaceTransform::Rotate180,
+    SurfaceTransform::Rotate270,
+    SurfaceTransform::FlipHorizontal,
+    SurfaceTransform::FlipVertical,
+    SurfaceTransform::Rotate90FlipHorizontal,
+    SurfaceTransform::Rotate90FlipVertical,
+];
+
+#[test]
+fn transform_round_trip() {
+
+    // The picture shown by a 3x4 output, in every orientation.
+    for transform in TRANSFORMS {
+        let (width, height) = transform.size(3, 4);
+        assert_eq!((width, height), if transform.is_transposed() { (4, 3) } else { (3, 4) });
+
+        let mut seen = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let (px, py) = transform.apply(x, y, width, height);
+                assert!(px < 3 && py < 4, "{:?} maps {}x{} outside the output", transform, x, y);
+                assert_eq!(transform.invert(px, py, width, height), (x, y));
+                seen.push((px, py));
+            }
+        }
+
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 12);
+        assert_eq!(SurfaceTransform::try_from(transform as u32), Ok(transform));
+    }
+}
+
+#[test]
+fn transform_rotate_counter_clockwise() {
+
+    // The top right corner of the picture ends up top left on the panel.
+    assert_eq!(SurfaceTransform::Rotate90.apply(3, 0, 4, 2), (0, 0));
+    assert_eq!(SurfaceTransform::Rotate90.apply(0, 0, 4, 2), (0, 3));
+    assert_eq!(SurfaceTransform::Rotate270.apply(0, 0, 4, 2), (1, 0));
+    assert_eq!(SurfaceTransform::Rotate90FlipHorizontal.apply(3, 1, 4, 2), (1, 3));
+    assert_eq!(SurfaceTransform::try_from(8), Err(ErrorKind::TRANSFORM_INVALID));
+    assert_eq!(SurfaceTransform::try_from(u32::MAX), Err(ErrorKind::TRANSFORM_INVALID));
+}
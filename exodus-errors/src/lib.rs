@@ -1,7 +1,11 @@
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
 
-#[derive(Debug, Clone, Copy)]
+/// Errors shared by every exodus crate.
+///
+/// The discriminant is the numeric code sent on the wire in `ProtocolError`
+/// replies, so new variants are only ever appended, here and in `ERROR_KINDS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorKind {
     // Connection
     CONNECTION_FAILED = 1,
@@ -10,6 +14,8 @@ pub enum ErrorKind {
 
     NETWORKMESSAGE_FAILED,
     NETWORKMESSAGE_EMPTY,
+    /// This error is thrown when a frame header announces a payload bigger than `MAX_PAYLOAD_SIZE`.
+    NETWORKMESSAGE_TOO_LARGE,
 
     // GPU
     GPU_LOAD_FAILED,
@@ -61,6 +67,122 @@ pub enum ErrorKind {
 
     // Protocol
     PROTOCOL_FAILED,
+    /// This error is thrown when the client and server version ranges do not overlap.
+    PROTOCOL_VERSION_UNSUPPORTED,
+    /// This error is thrown when a request is not available in the negotiated version or extensions.
+    PROTOCOL_UNSUPPORTED,
 
+    // Server
+    EVENT_LOOP_FAILED,
+    SIGNAL_HANDLER_FAILED,
+    HEADLESS_MODE_INVALID,
+    MODE_NOT_FOUND,
+    PAGE_FLIP_FAILED,
+    ATOMIC_COMMIT_FAILED,
+    PLANE_FAILED,
+    HOTPLUG_FAILED,
+    EDID_INVALID,
+    GAMMA_FAILED,
+    POWER_FAILED,
+    LAYOUT_INVALID,
+    SHM_INVALID,
+    SHM_TRUNCATED,
+    NETWORKMESSAGE_FDS_INVALID,
+    DMABUF_EXPORT_FAILED,
+    DMABUF_IMPORT_FAILED,
+    PIXEL_FORMAT_UNSUPPORTED,
+    CURSOR_INVALID,
+    POWER_STATE_INVALID,
+    TRANSFORM_INVALID,
+}
 
-}
\ No newline at end of file
+/// Every `ErrorKind`, ordered by code.
+const ERROR_KINDS: &[ErrorKind] = &[
+    ErrorKind::CONNECTION_FAILED,
+    ErrorKind::CONNECTION_CLOSED,
+    ErrorKind::CONNECTION_TIMEOUT,
+    ErrorKind::NETWORKMESSAGE_FAILED,
+    ErrorKind::NETWORKMESSAGE_EMPTY,
+    ErrorKind::NETWORKMESSAGE_TOO_LARGE,
+    ErrorKind::GPU_LOAD_FAILED,
+    ErrorKind::GPU_RESOURCES_FAILED,
+    ErrorKind::GPU_NOT_FOUND,
+    ErrorKind::GPUS_LIST_FAILED,
+    ErrorKind::DISPLAY_NOT_FOUND,
+    ErrorKind::DISPLAY_LISTENER_FAILED,
+    ErrorKind::NATIVE_DEVICE_NOT_FOUND,
+    ErrorKind::DEVICE_MANAGER_CREATE_FAILED,
+    ErrorKind::BUFFER_EMPTY,
+    ErrorKind::BUFFER_CREATE_FAILED,
+    ErrorKind::BUFFER_MAPPING_FAILED,
+    ErrorKind::BUFFER_OUT_OF_BOUNDS,
+    ErrorKind::BUFFER_INVALID_PIXELS,
+    ErrorKind::SURFACE_CREATE_FAILED,
+    ErrorKind::SURFACE_GET_BUFFER_FAILED,
+    ErrorKind::SURFACE_LOCK_FAILED,
+    ErrorKind::SURFACE_LOCK_MAPPING_FAILED,
+    ErrorKind::FRAMEBUFFER_CREATE_FAILED,
+    ErrorKind::CRTC_NOT_FOUND,
+    ErrorKind::CRTC_FAILED,
+    ErrorKind::CRTC_SET_FAILED,
+    ErrorKind::ENCODER_FAILED,
+    ErrorKind::CONNECTOR_FAILED,
+    ErrorKind::CONNECTOR_MODE_FAILED,
+    ErrorKind::SCREEN_DISCONNECTED,
+    ErrorKind::SCREEN_NOT_FOUND,
+    ErrorKind::PROTOCOL_FAILED,
+    ErrorKind::PROTOCOL_VERSION_UNSUPPORTED,
+    ErrorKind::PROTOCOL_UNSUPPORTED,
+    ErrorKind::EVENT_LOOP_FAILED,
+    ErrorKind::SIGNAL_HANDLER_FAILED,
+    ErrorKind::HEADLESS_MODE_INVALID,
+    ErrorKind::MODE_NOT_FOUND,
+    ErrorKind::PAGE_FLIP_FAILED,
+    ErrorKind::ATOMIC_COMMIT_FAILED,
+    ErrorKind::PLANE_FAILED,
+    ErrorKind::HOTPLUG_FAILED,
+    ErrorKind::EDID_INVALID,
+    ErrorKind::GAMMA_FAILED,
+    ErrorKind::POWER_FAILED,
+    ErrorKind::LAYOUT_INVALID,
+    ErrorKind::SHM_INVALID,
+    ErrorKind::SHM_TRUNCATED,
+    ErrorKind::NETWORKMESSAGE_FDS_INVALID,
+    ErrorKind::DMABUF_EXPORT_FAILED,
+    ErrorKind::DMABUF_IMPORT_FAILED,
+    ErrorKind::PIXEL_FORMAT_UNSUPPORTED,
+    ErrorKind::CURSOR_INVALID,
+    ErrorKind::POWER_STATE_INVALID,
+    ErrorKind::TRANSFORM_INVALID,
+];
+
+impl ErrorKind {
+    /// Numeric code of the error, stable on the wire.
+    pub fn code(&self) -> u32 {
+        *self as u32
+    }
+
+    /// Returns the error matching a wire code, `None` when the code is unknown to this version.
+    pub fn from_code(code: u32) -> Option<Self> {
+        ERROR_KINDS.iter().find(|kind| kind.code() == code).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, ERROR_KINDS};
+
+    #[test]
+    fn error_kind_codes_are_sequential() {
+        for (i, kind) in ERROR_KINDS.iter().enumerate() {
+            assert_eq!(kind.code(), i as u32 + 1, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn error_kind_from_code() {
+        assert_eq!(ErrorKind::from_code(ErrorKind::GPU_NOT_FOUND.code()), Some(ErrorKind::GPU_NOT_FOUND));
+        assert_eq!(ErrorKind::from_code(0), None);
+        assert_eq!(ErrorKind::from_code(u32::MAX), None);
+    }
+}
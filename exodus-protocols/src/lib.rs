@@ -1 +1,5 @@
-pub mod protocol_code;
\ No newline at end of file
+pub mod protocol_code;
+pub mod wire;
+pub mod macros;
+pub mod messages;
+mod tests;
@@ -0,0 +1,114 @@
+/// Declares a struct and its `Wire` encoding, to be nested inside messages.
+///
+/// Fields are encoded in declaration order, so the struct is the layout.
+#[macro_export]
+macro_rules! record {
+    (
+        $(#[$meta:meta])*
+        $name:ident { $($(#[$fmeta:meta])* $field:ident : $ty:ty),* $(,)? }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct $name {
+            $($(#[$fmeta])* pub $field: $ty,)*
+        }
+
+        impl $crate::wire::Wire for $name {
+            fn encode<W: $crate::wire::WireWriter + ?Sized>(&self, _writer: &mut W) {
+                $($crate::wire::Wire::encode(&self.$field, _writer);)*
+            }
+
+            fn decode<R: $crate::wire::WireReader + ?Sized>(_reader: &mut R) -> Result<Self, exodus_errors::ErrorKind> {
+                Ok(Self {
+                    $($field: $crate::wire::Wire::decode(_reader)?,)*
+                })
+            }
+        }
+    };
+}
+
+/// Declares a message payload and its `Wire` encoding.
+///
+/// Fields are encoded in declaration order, so the struct is the layout.
+#[macro_export]
+macro_rules! message {
+    (
+        $(#[$meta:meta])*
+        $code:ident => $name:ident { $($body:tt)* }
+    ) => {
+        $crate::record!($(#[$meta])* $name { $($body)* });
+
+        impl $crate::wire::Message for $name {
+            const CODE: $crate::protocol_code::ProtocolCode = $crate::protocol_code::ProtocolCode::$code;
+        }
+    };
+}
+
+/// Declares the protocol: requests with their replies, events, standalone messages
+/// and records nested inside them.
+///
+/// ```ignore
+/// protocol! {
+///     request ProtocolGPUInfo => GPUInfoRequest { gpu: i32 }
+///     reply GPUInfoReply { gpu: i32, vendor: u32 }
+///
+///     event ProtocolScreenAdded => ScreenAddedEvent { gpu: i32, screen: u32 }
+///
+///     message ProtocolError => ErrorMessage { error: u32 }
+///
+///     record ModeInfo { width: u32, height: u32 }
+/// }
+/// ```
+#[macro_export]
+macro_rules! protocol {
+    () => {};
+
+    (
+        $(#[$rmeta:meta])*
+        request $code:ident => $request:ident { $($rbody:tt)* }
+        $(#[$pmeta:meta])*
+        reply $reply:ident { $($pbody:tt)* }
+        $($rest:tt)*
+    ) => {
+        $crate::message!($(#[$rmeta])* $code => $request { $($rbody)* });
+        $crate::message!($(#[$pmeta])* $code => $reply { $($pbody)* });
+
+        impl $crate::wire::Request for $request {
+            type Reply = $reply;
+        }
+
+        $crate::protocol!($($rest)*);
+    };
+
+    (
+        $(#[$emeta:meta])*
+        event $code:ident => $event:ident { $($ebody:tt)* }
+        $($rest:tt)*
+    ) => {
+        $crate::message!($(#[$emeta])* $code => $event { $($ebody)* });
+
+        impl $crate::wire::Event for $event {}
+
+        $crate::protocol!($($rest)*);
+    };
+
+    (
+        $(#[$mmeta:meta])*
+        message $code:ident => $name:ident { $($mbody:tt)* }
+        $($rest:tt)*
+    ) => {
+        $crate::message!($(#[$mmeta])* $code => $name { $($mbody)* });
+
+        $crate::protocol!($($rest)*);
+    };
+
+    (
+        $(#[$cmeta:meta])*
+        record $name:ident { $($cbody:tt)* }
+        $($rest:tt)*
+    ) => {
+        $crate::record!($(#[$cmeta])* $name { $($cbody)* });
+
+        $crate::protocol!($($rest)*);
+    };
+}
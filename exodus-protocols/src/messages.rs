@@ -0,0 +1,375 @@
+//! Typed payloads of every `ProtocolCode`, the single source of the wire layout.
+//!
+//! See the documentation of each `ProtocolCode` for the meaning of the fields.
+
+use crate::protocol;
+use crate::wire::{Fd, Utf16String};
+
+/// The mode is the one advertised as preferred by the monitor.
+pub const MODE_FLAG_PREFERRED: u32 = 0x1;
+/// The mode is interlaced.
+pub const MODE_FLAG_INTERLACED: u32 = 0x2;
+
+protocol! {
+    /// Payload of a `ProtocolError` reply.
+    message ProtocolError => ErrorMessage {
+        /// The `ErrorKind` code.
+        error: u32,
+        /// The `ProtocolCode` of the failed request.
+        code: i32,
+        /// The serial of the failed request.
+        serial: u32,
+        /// Human-readable detail, empty when there is none.
+        message: String,
+    }
+
+    /// Registers the entity and negotiates the protocol version.
+    request ProtocolEntityRegister => EntityRegisterRequest {
+        class: String,
+        title: Utf16String,
+        min_version: u32,
+        max_version: u32,
+        author: String,
+        description: String,
+        extensions: Vec<String>,
+    }
+    reply EntityRegisterReply {
+        version: u32,
+        capabilities: Vec<String>,
+    }
+
+    /// Lists the GPUs of the display.
+    request ProtocolEnumerateGPUS => EnumerateGPUsRequest {}
+    reply EnumerateGPUsReply {
+        gpus: Vec<i32>,
+    }
+
+    /// Describes a GPU.
+    request ProtocolGPUInfo => GPUInfoRequest {
+        gpu: i32,
+    }
+    reply GPUInfoReply {
+        gpu: i32,
+        vendor: u32,
+        vendor_name: String,
+        model: u32,
+    }
+
+    /// Lists the screens of a GPU.
+    request ProtocolEnumerateScreens => EnumerateScreensRequest {
+        gpu: i32,
+    }
+    reply EnumerateScreensReply {
+        screens: Vec<u32>,
+    }
+
+    /// Describes a screen.
+    request ProtocolScreenInfo => ScreenInfoRequest {
+        gpu: i32,
+        screen: u32,
+    }
+    reply ScreenInfoReply {
+        screen: u32,
+        width: u32,
+        height: u32,
+        refresh: u32,
+        subpixel: u32,
+        connector_type: u32,
+        mm_width: u32,
+        mm_height: u32,
+        buffer_count: u32,
+    }
+
+    /// A display mode of a screen.
+    record ModeInfo {
+        /// Index to pass to `ProtocolSetMode`.
+        index: u32,
+        width: u32,
+        height: u32,
+        refresh: u32,
+        /// `MODE_FLAG_*` bits.
+        flags: u32,
+    }
+
+    /// Lists the modes of a screen.
+    request ProtocolEnumerateModes => EnumerateModesRequest {
+        gpu: i32,
+        screen: u32,
+    }
+    reply EnumerateModesReply {
+        current: u32,
+        modes: Vec<ModeInfo>,
+    }
+
+    /// Switches a screen to another mode.
+    request ProtocolSetMode => SetModeRequest {
+        gpu: i32,
+        screen: u32,
+        mode: u32,
+    }
+    reply SetModeReply {
+        mode: ModeInfo,
+    }
+
+    /// Sent to the other entities when a screen changed mode.
+    event ProtocolScreenModeChanged => ScreenModeChangedEvent {
+        gpu: i32,
+        screen: u32,
+        mode: ModeInfo,
+    }
+
+    /// Sets the cursor image of a screen, an empty image hides the cursor.
+    request ProtocolSetCursor => SetCursorRequest {
+        gpu: i32,
+        screen: u32,
+        width: u32,
+        height: u32,
+        hot_x: i32,
+        hot_y: i32,
+        pixels: Vec<u32>,
+    }
+    reply SetCursorReply {
+        hardware: bool,
+    }
+
+    /// Moves the hotspot of the cursor of a screen.
+    request ProtocolMoveCursor => MoveCursorRequest {
+        gpu: i32,
+        screen: u32,
+        x: i32,
+        y: i32,
+    }
+    reply MoveCursorReply {}
+
+    /// Sent when a monitor was plugged in.
+    event ProtocolScreenAdded => ScreenAddedEvent {
+        gpu: i32,
+        screen: u32,
+    }
+
+    /// Sent when a monitor was unplugged.
+    event ProtocolScreenRemoved => ScreenRemovedEvent {
+        gpu: i32,
+        screen: u32,
+    }
+
+    /// Sent when another monitor was plugged in the connector of a screen.
+    event ProtocolScreenChanged => ScreenChangedEvent {
+        gpu: i32,
+        screen: u32,
+    }
+
+    /// A detailed timing of a monitor.
+    record TimingInfo {
+        width: u32,
+        height: u32,
+        refresh: f32,
+        /// In kHz.
+        pixel_clock: u32,
+    }
+
+    /// Describes the monitor of a screen, from its EDID.
+    request ProtocolScreenDetails => ScreenDetailsRequest {
+        gpu: i32,
+        screen: u32,
+    }
+    reply ScreenDetailsReply {
+        has_edid: bool,
+        identity: String,
+        manufacturer: String,
+        name: String,
+        serial: String,
+        product: u32,
+        week: u32,
+        year: u32,
+        bit_depth: u32,
+        gamma: f32,
+        chromaticity: Vec<f32>,
+        timings: Vec<TimingInfo>,
+        colorimetry: u32,
+        hdr_eotfs: u32,
+        max_luminance: f32,
+        max_frame_average: f32,
+        min_luminance: f32,
+    }
+
+    /// Reads the gamma ramp of a screen.
+    request ProtocolGetGamma => GetGammaRequest {
+        gpu: i32,
+        screen: u32,
+    }
+    reply GetGammaReply {
+        red: Vec<u16>,
+        green: Vec<u16>,
+        blue: Vec<u16>,
+    }
+
+    /// Programs the gamma ramp of a screen.
+    request ProtocolSetGamma => SetGammaRequest {
+        gpu: i32,
+        screen: u32,
+        red: Vec<u16>,
+        green: Vec<u16>,
+        blue: Vec<u16>,
+    }
+    reply SetGammaReply {}
+
+    /// Shifts the white point of a screen, for night light.
+    request ProtocolSetColorTemperature => SetColorTemperatureRequest {
+        gpu: i32,
+        screen: u32,
+        temperature: u32,
+        gamma: f32,
+        brightness: f32,
+    }
+    reply SetColorTemperatureReply {}
+
+    /// Reads the power state of a screen.
+    request ProtocolGetPowerState => GetPowerStateRequest {
+        gpu: i32,
+        screen: u32,
+    }
+    reply GetPowerStateReply {
+        state: u32,
+    }
+
+    /// Powers a screen on or down.
+    request ProtocolSetPowerState => SetPowerStateRequest {
+        gpu: i32,
+        screen: u32,
+        state: u32,
+    }
+    reply SetPowerStateReply {}
+
+    /// Sets the idle timeout of the display, `0` disables it.
+    request ProtocolSetIdleTimeout => SetIdleTimeoutRequest {
+        seconds: u32,
+    }
+    reply SetIdleTimeoutReply {}
+
+    /// Broadcast when the power state of a screen changed.
+    event ProtocolPowerStateChanged => PowerStateChangedEvent {
+        gpu: i32,
+        screen: u32,
+        state: u32,
+    }
+
+    /// The part of the desktop a screen shows.
+    record ScreenRegion {
+        gpu: i32,
+        screen: u32,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        scale: f32,
+        /// As in `ProtocolSetTransform`.
+        transform: u32,
+        mirroring: bool,
+        mirror_gpu: i32,
+        mirror_screen: u32,
+    }
+
+    /// Reads the layout of the desktop.
+    request ProtocolGetLayout => GetLayoutRequest {}
+    reply GetLayoutReply {
+        screens: Vec<ScreenRegion>,
+    }
+
+    /// Moves a screen on the desktop.
+    request ProtocolSetScreenPosition => SetScreenPositionRequest {
+        gpu: i32,
+        screen: u32,
+        x: i32,
+        y: i32,
+        scale: f32,
+    }
+    reply SetScreenPositionReply {}
+
+    /// Makes a screen mirror another one.
+    request ProtocolSetMirror => SetMirrorRequest {
+        gpu: i32,
+        screen: u32,
+        mirroring: bool,
+        source_gpu: i32,
+        source_screen: u32,
+    }
+    reply SetMirrorReply {}
+
+    /// Broadcast when screens moved on the desktop.
+    event ProtocolLayoutChanged => LayoutChangedEvent {}
+
+    /// Rotates or flips the picture of a screen.
+    request ProtocolSetTransform => SetTransformRequest {
+        gpu: i32,
+        screen: u32,
+        transform: u32,
+    }
+    reply SetTransformReply {
+        hardware: bool,
+        width: u32,
+        height: u32,
+    }
+
+    /// Maps shared memory sent along with the request.
+    request ProtocolCreatePool => CreatePoolRequest {
+        size: u32,
+        fd: Fd,
+    }
+    reply CreatePoolReply {
+        pool: u32,
+    }
+
+    request ProtocolDestroyPool => DestroyPoolRequest {
+        pool: u32,
+    }
+    reply DestroyPoolReply {}
+
+    /// Carves a buffer of pixels out of a pool.
+    request ProtocolCreateBuffer => CreateBufferRequest {
+        pool: u32,
+        offset: u32,
+        width: u32,
+        height: u32,
+        stride: u32,
+        format: u32,
+    }
+    reply CreateBufferReply {
+        buffer: u32,
+    }
+
+    request ProtocolDestroyBuffer => DestroyBufferRequest {
+        buffer: u32,
+    }
+    reply DestroyBufferReply {}
+
+    /// Shows the content of a buffer on a screen.
+    request ProtocolAttachBuffer => AttachBufferRequest {
+        gpu: i32,
+        screen: u32,
+        buffer: u32,
+        x: i32,
+        y: i32,
+    }
+    reply AttachBufferReply {}
+
+    /// A plane of a DMA-BUF.
+    record DmabufPlane {
+        fd: Fd,
+        offset: u32,
+        stride: u32,
+    }
+
+    /// Imports a buffer drawn by the entity on a GPU.
+    request ProtocolCreateDmabufBuffer => CreateDmabufBufferRequest {
+        gpu: i32,
+        width: u32,
+        height: u32,
+        format: u32,
+        modifier: u64,
+        planes: Vec<DmabufPlane>,
+    }
+    reply CreateDmabufBufferReply {
+        buffer: u32,
+    }
+}
@@ -1,14 +1,61 @@
 
 pub const PROTOCOL_VERSION_1_0_0: u32 = 100;
+pub const PROTOCOL_VERSION_1_1_0: u32 = 110;
+pub const PROTOCOL_VERSION_1_2_0: u32 = 120;
 
-#[derive(Debug)]
+/// Oldest protocol version spoken by this build.
+pub const PROTOCOL_VERSION_MIN: u32 = PROTOCOL_VERSION_1_0_0;
+/// Newest protocol version spoken by this build.
+pub const PROTOCOL_VERSION_MAX: u32 = PROTOCOL_VERSION_1_2_0;
+
+/// Extension importing buffers drawn on a GPU, offered when a GPU of the display can import DMA-BUFs.
+pub const EXTENSION_DMABUF: &str = "exodus_dmabuf";
+
+/// Picks the highest version inside both the client range and `PROTOCOL_VERSION_MIN..=PROTOCOL_VERSION_MAX`.
+/// 
+/// Returns `None` when the ranges do not overlap.
+pub fn negotiate_version(min_version: u32, max_version: u32) -> Option<u32> {
+    let version = max_version.min(PROTOCOL_VERSION_MAX);
+
+    if version < min_version.max(PROTOCOL_VERSION_MIN) {
+        return None;
+    }
+
+    Some(version)
+}
+
+/// Codes of the protocol messages.
+/// 
+/// The payload of each code is declared once in `messages`, the field lists below describe it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum ProtocolCode {
+    /// Failure of a request, sent as its reply and echoing its serial.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `error` - Number of 32 bits, the `ErrorKind` code.
+    /// 
+    ///       Example: 10 (GPU_NOT_FOUND)
+    /// 
+    /// * `code` - Number of 32 bits, the `ProtocolCode` of the failed request.
+    /// 
+    ///       Example: 3 (ProtocolGPUInfo)
+    /// 
+    /// * `serial` - Number of 32 bits, the serial of the failed request.
+    /// 
+    /// * `message` - String utf8, human-readable detail, empty when there is none.
+    /// 
+    ///       Example: "GPU not found."
+    /// 
     ProtocolError = -1,
     /// None protocol.
     ProtocolNone = 0x0,
 
-    /// Register entity in display server.
+    /// Register entity in display server and negotiate the protocol version.
+    /// 
+    /// Must be the first request of an entity, any other request is rejected
+    /// with `PROTOCOL_UNSUPPORTED` until the handshake succeeds.
     /// 
     /// Post: `ProtocolEntityRegister`
     /// 
@@ -22,7 +69,11 @@ pub enum ProtocolCode {
     /// 
     ///       Example: "Mozilla Firefox - Web Browser"
     /// 
-    /// * `version` - Number of 32 bits, the version of protocol. 
+    /// * `min_version` - Number of 32 bits, the oldest protocol version supported by the entity. 
+    /// 
+    ///       Example: 1.0.0 = 100.
+    /// 
+    /// * `max_version` - Number of 32 bits, the newest protocol version supported by the entity. 
     /// 
     ///       Example: 1.0.0 = 100.
     /// 
@@ -34,9 +85,21 @@ pub enum ProtocolCode {
     /// 
     ///       Example: "Mozilla Firefox is a free and open-source web browser developed by the Mozilla Foundation and its subsidiary, the Mozilla Corporation."
     ///
+    /// * `extension_count` - Number of 32 bits, the count of optional extensions requested.
+    /// 
+    /// * `extensions` - List of String utf8, the names of the extensions.
+    /// 
     /// ### Returns
     /// 
-    /// No returns.
+    /// * `version` - Number of 32 bits, the version chosen by the server.
+    /// 
+    ///       Example: 1.0.0 = 100.
+    /// 
+    /// * `capability_count` - Number of 32 bits, the count of extensions supported by the server.
+    /// 
+    /// * `capabilities` - List of String utf8, the names of the extensions supported by the server.
+    /// 
+    /// Fails with `PROTOCOL_VERSION_UNSUPPORTED` when the version ranges do not overlap.
     ProtocolEntityRegister,
 
     /// List all GPUs found in the system.
@@ -163,4 +226,665 @@ pub enum ProtocolCode {
     ///       Example: 2
     /// 
     ProtocolScreenInfo,
-}
\ No newline at end of file
+
+    /// List the display modes of a screen.
+    /// 
+    /// Post: `ProtocolEnumerateModes`, since 1.1.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `current` - Number of 32 bits, the index of the current mode.
+    /// 
+    /// * `mode_count` - Number of 32 bits, the count of modes.
+    /// 
+    /// * `modes` - List of modes, each one `index`, `width`, `height`, `refresh`
+    ///   and `flags`, numbers of 32 bits. `flags` holds `MODE_FLAG_PREFERRED` (0x1)
+    ///   and `MODE_FLAG_INTERLACED` (0x2).
+    /// 
+    ///       Example: [{0, 1920, 1080, 60, 0x1}, {1, 1280, 720, 60, 0x0}]
+    /// 
+    ProtocolEnumerateModes,
+
+    /// Switch a screen to another display mode.
+    /// 
+    /// The buffers of the screen are reallocated for the new resolution and the
+    /// other entities receive `ProtocolScreenModeChanged`.
+    /// 
+    /// Post: `ProtocolSetMode`, since 1.1.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `mode` - Number of 32 bits, the index of the mode.
+    /// 
+    ///       Example: 1
+    /// 
+    /// ### Returns
+    /// 
+    /// * `mode` - The mode now used, laid out as in `ProtocolEnumerateModes`.
+    /// 
+    /// Fails with `MODE_NOT_FOUND` for an unknown index.
+    ProtocolSetMode,
+
+    /// Event sent when a screen changed mode, with serial 0.
+    /// 
+    /// Post: `ProtocolScreenModeChanged`, since 1.1.0.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `mode` - The new mode, laid out as in `ProtocolEnumerateModes`.
+    /// 
+    ProtocolScreenModeChanged,
+
+    /// Set the cursor image of a screen.
+    /// 
+    /// The cursor plane shows the image when the screen has one, otherwise the
+    /// cursor is composited into every frame. A `0`x`0` image hides the cursor,
+    /// images are at most 64x64 pixels.
+    /// 
+    /// Post: `ProtocolSetCursor`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `width` - Number of 32 bits, the width of the image.
+    /// 
+    /// * `height` - Number of 32 bits, the height of the image.
+    /// 
+    /// * `hot_x` - Signed number of 32 bits, the hotspot from the left of the image.
+    /// 
+    /// * `hot_y` - Signed number of 32 bits, the hotspot from the top of the image.
+    /// 
+    /// * `pixels` - List of `width` * `height` ARGB pixels, numbers of 32 bits.
+    /// 
+    ///       Example: 16, 16, 0, 0, [0xff000000, ...]
+    /// 
+    /// ### Returns
+    /// 
+    /// * `hardware` - Boolean, whether the cursor plane shows the cursor.
+    /// 
+    /// Fails with `CURSOR_INVALID` when the image is empty in only one direction
+    /// or larger than 64x64, and with `BUFFER_INVALID_PIXELS` when the pixel
+    /// count does not match the size.
+    ProtocolSetCursor,
+
+    /// Move the hotspot of the cursor of a screen.
+    /// 
+    /// Post: `ProtocolMoveCursor`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `x` - Signed number of 32 bits, the position from the left of the screen.
+    /// 
+    /// * `y` - Signed number of 32 bits, the position from the top of the screen.
+    /// 
+    ///       Example: 100, 200
+    /// 
+    ProtocolMoveCursor,
+
+    /// Event sent when a monitor was plugged in, with serial 0.
+    /// 
+    /// Post: `ProtocolScreenAdded`, since 1.2.0.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of the new screen.
+    /// 
+    ProtocolScreenAdded,
+
+    /// Event sent when a monitor was unplugged, with serial 0.
+    /// 
+    /// Requests on the screen then fail with `SCREEN_DISCONNECTED`.
+    /// 
+    /// Post: `ProtocolScreenRemoved`, since 1.2.0.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of the removed screen.
+    /// 
+    ProtocolScreenRemoved,
+
+    /// Event sent when another monitor was plugged in the connector of a screen, with serial 0.
+    /// 
+    /// The modes and size of the screen may differ, its mode is reset.
+    /// 
+    /// Post: `ProtocolScreenChanged`, since 1.2.0.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of the screen.
+    /// 
+    ProtocolScreenChanged,
+
+    /// Describe the monitor of a screen, from its EDID.
+    /// 
+    /// The identity stays the same across connectors and reboots, to remember
+    /// per-monitor settings.
+    /// 
+    /// Post: `ProtocolScreenDetails`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `has_edid` - Boolean, `false` when the monitor provided no valid EDID,
+    ///   the other fields are then empty.
+    /// 
+    /// * `identity` - String, the manufacturer, product code and serial number.
+    /// 
+    ///       Example: "DEL-a0b1-ABC123"
+    /// 
+    /// * `manufacturer`, `name`, `serial` - Strings, `name` and `serial` may be empty.
+    /// 
+    /// * `product`, `week`, `year`, `bit_depth` - Numbers of 32 bits, `0` when unknown.
+    /// 
+    /// * `gamma` - Float of 32 bits, `0` when unknown.
+    /// 
+    /// * `chromaticity` - List of floats of 32 bits, the x and y coordinates of
+    ///   the red, green and blue primaries and of the white point.
+    /// 
+    /// * `timings` - List of detailed timings, each one `width`, `height`,
+    ///   numbers of 32 bits, `refresh`, float of 32 bits, and `pixel_clock`,
+    ///   number of 32 bits in kHz. The native resolution comes first.
+    /// 
+    /// * `colorimetry` - Number of 32 bits, the CTA-861 colorimetry bits.
+    /// 
+    /// * `hdr_eotfs` - Number of 32 bits, the HDR transfer function bits, `0` without HDR.
+    /// 
+    /// * `max_luminance`, `max_frame_average`, `min_luminance` - Floats of 32
+    ///   bits in cd/m², `0` when unknown.
+    /// 
+    ProtocolScreenDetails,
+
+    /// Get the gamma ramp of a screen.
+    /// 
+    /// Post: `ProtocolGetGamma`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `red`, `green`, `blue` - Lists of numbers of 16 bits, as many as the
+    ///   gamma size of the screen.
+    /// 
+    ///       Example: [0, 257, ..., 65535]
+    /// 
+    /// Fails with `GAMMA_FAILED` when the screen has no gamma ramp.
+    ProtocolGetGamma,
+
+    /// Set the gamma ramp of a screen, the original one is put back when the display stops.
+    /// 
+    /// Post: `ProtocolSetGamma`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `red`, `green`, `blue` - Lists of numbers of 16 bits, as many as
+    ///   returned by `ProtocolGetGamma`.
+    /// 
+    /// Fails with `GAMMA_FAILED` when the sizes do not match.
+    ProtocolSetGamma,
+
+    /// Shift the white point of a screen and apply a brightness curve, for night light.
+    /// 
+    /// Post: `ProtocolSetColorTemperature`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `temperature` - Number of 32 bits, the white point in kelvin, 6500 is neutral.
+    /// 
+    /// * `gamma` - Float of 32 bits, 1.0 leaves the curve linear.
+    /// 
+    /// * `brightness` - Float of 32 bits, from 0.0 to 1.0.
+    /// 
+    ///       Example: 3400, 1.0, 0.8
+    /// 
+    ProtocolSetColorTemperature,
+
+    /// Get the power state of a screen.
+    /// 
+    /// Post: `ProtocolGetPowerState`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `state` - Number of 32 bits, 0 on, 1 standby, 2 suspend or 3 off.
+    /// 
+    ProtocolGetPowerState,
+
+    /// Set the power state of a screen, frames drawn while it is off show once it is on again.
+    /// 
+    /// Powering a screen on also restarts the idle timeout. Atomic drivers do not
+    /// tell standby and suspend apart from off.
+    /// 
+    /// Post: `ProtocolSetPowerState`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `state` - Number of 32 bits, 0 on, 1 standby, 2 suspend or 3 off.
+    /// 
+    ///       Example: 3
+    /// 
+    /// Fails with `POWER_STATE_INVALID` for other states, and with `POWER_FAILED`
+    /// when the screen cannot be powered down.
+    ProtocolSetPowerState,
+
+    /// Set how long the display waits without cursor activity before powering every screen off.
+    /// 
+    /// The screens are powered on again by the next cursor activity.
+    /// 
+    /// Post: `ProtocolSetIdleTimeout`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `seconds` - Number of 32 bits, `0` never powers the screens off.
+    /// 
+    ///       Example: 600
+    /// 
+    ProtocolSetIdleTimeout,
+
+    /// Event sent when the power state of a screen changed, with serial 0.
+    /// 
+    /// Post: `ProtocolPowerStateChanged`, since 1.2.0.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `state` - Number of 32 bits, as in `ProtocolGetPowerState`.
+    /// 
+    ProtocolPowerStateChanged,
+
+    /// Get the part of the desktop each screen shows, every GPU sharing one coordinate space.
+    /// 
+    /// Post: `ProtocolGetLayout`, since 1.2.0.
+    /// 
+    /// ### Returns
+    /// 
+    /// * `screens` - List of screens, each laid out as:
+    /// 
+    ///   * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    ///   * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    ///   * `x`, `y` - Signed numbers of 32 bits, the position on the desktop.
+    /// 
+    ///   * `width`, `height` - Numbers of 32 bits, the size on the desktop, the mode turned by
+    ///     `transform` and divided by `scale`.
+    /// 
+    ///   * `scale` - Float of 32 bits, pixels per desktop unit.
+    /// 
+    ///   * `transform` - Number of 32 bits, as in `ProtocolSetTransform`.
+    /// 
+    ///   * `mirroring` - Boolean, whether the screen shows the position of another one.
+    /// 
+    ///   * `mirror_gpu`, `mirror_screen` - Numbers of 32 bits, the mirrored screen when `mirroring`.
+    /// 
+    ///       Example: [{0, 1, 0, 0, 1280, 720, 2.0, 0, false, 0, 0}, ...]
+    /// 
+    ProtocolGetLayout,
+
+    /// Move a screen on the desktop, the position is kept for its monitor across restarts.
+    /// 
+    /// The screen stops mirroring.
+    /// 
+    /// Post: `ProtocolSetScreenPosition`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `x`, `y` - Signed numbers of 32 bits, the position on the desktop.
+    /// 
+    /// * `scale` - Float of 32 bits, pixels per desktop unit.
+    /// 
+    ///       Example: 1920, 0, 1.5
+    /// 
+    /// Fails with `LAYOUT_INVALID` when the scale is not positive.
+    ProtocolSetScreenPosition,
+
+    /// Make a screen show the picture of another screen of the same GPU, or its own again.
+    /// 
+    /// The connector of the screen is driven by the CRTC of the source, as
+    /// a hardware clone, and takes its position on the desktop.
+    /// 
+    /// Post: `ProtocolSetMirror`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `mirroring` - Boolean, `false` shows the own position of the screen again.
+    /// 
+    /// * `source_gpu`, `source_screen` - Numbers of 32 bits, the screen to mirror.
+    /// 
+    ///       Example: 0, 2, true, 0, 1
+    /// 
+    /// Fails with `LAYOUT_INVALID` when the screens are on different GPUs, the
+    /// encoders cannot be cloned or the mirrors would be chained.
+    ProtocolSetMirror,
+
+    /// Event sent when screens moved on the desktop, with serial 0.
+    /// 
+    /// Post: `ProtocolLayoutChanged`, since 1.2.0.
+    /// 
+    ProtocolLayoutChanged,
+
+    /// Rotate or flip the picture of a screen, the size of the screen and the
+    /// positions on it are then in the new orientation.
+    /// 
+    /// The primary plane turns the picture when the hardware can, the display
+    /// composites it otherwise. The other entities receive a `ProtocolLayoutChanged` event.
+    /// 
+    /// Post: `ProtocolSetTransform`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `transform` - Number of 32 bits, `0` normal, `1`, `2` and `3` rotated by 90, 180 and 270
+    ///   degrees counter-clockwise, `4` and `5` flipped horizontally and vertically, `6` and `7`
+    ///   flipped horizontally and vertically then rotated by 90 degrees.
+    /// 
+    ///       Example: 0, 1, 1
+    /// 
+    /// ### Returns
+    /// 
+    /// * `hardware` - Boolean, whether the primary plane turns the picture.
+    /// 
+    /// * `width`, `height` - Numbers of 32 bits, the size of the screen in the new orientation.
+    /// 
+    ///       Example: true, 1080, 1920
+    /// 
+    /// Fails with `TRANSFORM_INVALID` for other transforms.
+    ProtocolSetTransform,
+
+    /// Create a pool of shared memory.
+    /// 
+    /// The display maps the file read-only, the entity draws into it and keeps
+    /// it at least `size` bytes long. Reading a truncated file fails with
+    /// `SHM_TRUNCATED` and the pool stays unusable.
+    /// 
+    /// Post: `ProtocolCreatePool`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `size` - Number of 32 bits, the size of the pool in bytes.
+    /// 
+    /// * `fd` - File descriptor of the memory, a memfd for example, see `Fd`.
+    /// 
+    ///       Example: 8294400, fd
+    /// 
+    /// ### Returns
+    /// 
+    /// * `pool` - Number of 32 bits, the id of the pool.
+    /// 
+    ///       Example: 1
+    /// 
+    /// Fails with `SHM_INVALID` when no file descriptor was sent, the file is
+    /// smaller than `size`, or the entity already holds 64 pools.
+    ProtocolCreatePool,
+
+    /// Destroy a pool, the buffers created from it stay usable until destroyed.
+    /// 
+    /// Post: `ProtocolDestroyPool`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `pool` - Number of 32 bits, the id of the pool.
+    /// 
+    ///       Example: 1
+    /// 
+    ProtocolDestroyPool,
+
+    /// Describe a buffer of pixels inside a pool.
+    /// 
+    /// Post: `ProtocolCreateBuffer`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `pool` - Number of 32 bits, the id of the pool.
+    /// 
+    /// * `offset` - Number of 32 bits, the position of the first pixel in the pool, in bytes.
+    /// 
+    /// * `width`, `height` - Numbers of 32 bits, the size of the buffer in pixels.
+    /// 
+    /// * `stride` - Number of 32 bits, the distance between two rows, in bytes.
+    /// 
+    /// * `format` - Number of 32 bits, the `PixelFormat`, `0` XRGB8888 and `1` ARGB8888.
+    /// 
+    ///       Example: 1, 0, 1920, 1080, 7680, 0
+    /// 
+    /// ### Returns
+    /// 
+    /// * `buffer` - Number of 32 bits, the id of the buffer.
+    /// 
+    ///       Example: 2
+    /// 
+    /// Fails with `PIXEL_FORMAT_UNSUPPORTED` for the other formats, and with
+    /// `SHM_INVALID` when the buffer does not fit in the pool, the offset or
+    /// stride is not a multiple of the pixel size, or the entity already holds
+    /// 256 buffers.
+    ProtocolCreateBuffer,
+
+    /// Destroy a buffer.
+    /// 
+    /// Post: `ProtocolDestroyBuffer`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `buffer` - Number of 32 bits, the id of the buffer.
+    /// 
+    ///       Example: 2
+    /// 
+    ProtocolDestroyBuffer,
+
+    /// Show the content of a buffer on a screen, the part outside the screen is clipped.
+    /// 
+    /// Shared memory pixels are copied when the request is handled, the entity
+    /// may draw into the buffer again once the reply arrives. Buffers created by
+    /// `ProtocolCreateDmabufBuffer` are scanned out by an overlay plane when the
+    /// screen has a free one that can, until the entity attaches another buffer
+    /// to the screen or disconnects, and are copied otherwise.
+    /// 
+    /// Post: `ProtocolAttachBuffer`, since 1.2.0.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU.
+    /// 
+    /// * `screen` - Number of 32 bits, the id of screen.
+    /// 
+    /// * `buffer` - Number of 32 bits, the id of the buffer.
+    /// 
+    /// * `x`, `y` - Signed numbers of 32 bits, the position of the buffer on the screen.
+    /// 
+    ///       Example: 0, 1, 2, 0, 0
+    /// 
+    /// Buffers created by `ProtocolCreateDmabufBuffer` can only be shown on
+    /// screens of the GPU they were imported on, `GPU_NOT_FOUND` otherwise.
+    ProtocolAttachBuffer,
+
+    /// Import a buffer drawn by the entity on a GPU, its planes are DMA-BUF file
+    /// descriptors sent along with the request.
+    /// 
+    /// The buffer can be attached like shared memory buffers and destroyed with
+    /// `ProtocolDestroyBuffer`. The entity must not draw into it again before
+    /// the reply of `ProtocolAttachBuffer` arrives.
+    /// 
+    /// Post: `ProtocolCreateDmabufBuffer`, since 1.2.0, requires the `exodus_dmabuf` extension.
+    /// 
+    /// ### Arguments
+    /// 
+    /// * `gpu` - Number of 32 bits, the id of GPU importing the buffer.
+    /// 
+    /// * `width`, `height` - Numbers of 32 bits, the size of the buffer in pixels.
+    /// 
+    /// * `format` - Number of 32 bits, the DRM fourcc code, `XR24` and `AR24` are supported.
+    /// 
+    /// * `modifier` - Number of 64 bits, the DRM format modifier, `0x00ffffffffffffff` when implicit.
+    /// 
+    /// * `planes` - List of `DmabufPlane`, between 1 and 4, each with:
+    /// 
+    ///   * `fd` - File descriptor of the plane, see `Fd`.
+    ///   * `offset` - Number of 32 bits, the position of the plane in the file, in bytes.
+    ///   * `stride` - Number of 32 bits, the distance between two rows, in bytes.
+    /// 
+    ///       Example: 0, 1920, 1080, 0x34325258, 0, [(fd, 0, 7680)]
+    /// 
+    /// ### Returns
+    /// 
+    /// * `buffer` - Number of 32 bits, the id of the buffer.
+    /// 
+    ///       Example: 2
+    /// 
+    /// Fails with `PIXEL_FORMAT_UNSUPPORTED` for the other formats, and with
+    /// `DMABUF_IMPORT_FAILED` when the GPU cannot import the format or modifier,
+    /// a plane has no file descriptor, or a single plane buffer without
+    /// modifier does not start at offset `0`. Fails with `SHM_INVALID` when the
+    /// entity already holds 256 buffers.
+    ProtocolCreateDmabufBuffer,
+}
+
+impl ProtocolCode {
+    /// Protocol version that introduced the request.
+    pub fn since(&self) -> u32 {
+        match self {
+            ProtocolCode::ProtocolEnumerateModes
+            | ProtocolCode::ProtocolSetMode
+            | ProtocolCode::ProtocolScreenModeChanged => PROTOCOL_VERSION_1_1_0,
+            ProtocolCode::ProtocolSetCursor
+            | ProtocolCode::ProtocolMoveCursor
+            | ProtocolCode::ProtocolScreenAdded
+            | ProtocolCode::ProtocolScreenRemoved
+            | ProtocolCode::ProtocolScreenChanged
+            | ProtocolCode::ProtocolScreenDetails
+            | ProtocolCode::ProtocolGetGamma
+            | ProtocolCode::ProtocolSetGamma
+            | ProtocolCode::ProtocolSetColorTemperature
+            | ProtocolCode::ProtocolGetPowerState
+            | ProtocolCode::ProtocolSetPowerState
+            | ProtocolCode::ProtocolSetIdleTimeout
+            | ProtocolCode::ProtocolPowerStateChanged
+            | ProtocolCode::ProtocolGetLayout
+            | ProtocolCode::ProtocolSetScreenPosition
+            | ProtocolCode::ProtocolSetMirror
+            | ProtocolCode::ProtocolLayoutChanged
+            | ProtocolCode::ProtocolSetTransform
+            | ProtocolCode::ProtocolCreatePool
+            | ProtocolCode::ProtocolDestroyPool
+            | ProtocolCode::ProtocolCreateBuffer
+            | ProtocolCode::ProtocolDestroyBuffer
+            | ProtocolCode::ProtocolAttachBuffer
+            | ProtocolCode::ProtocolCreateDmabufBuffer => PROTOCOL_VERSION_1_2_0,
+            _ => PROTOCOL_VERSION_1_0_0,
+        }
+    }
+
+    /// Extension that must be negotiated before the request is accepted, `None` for core requests.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            ProtocolCode::ProtocolCreateDmabufBuffer => Some(EXTENSION_DMABUF),
+            _ => None,
+        }
+    }
+}
+
+impl From<i32> for ProtocolCode {
+    fn from(code: i32) -> Self {
+        match code {
+            -1 => ProtocolCode::ProtocolError,
+            1 => ProtocolCode::ProtocolEntityRegister,
+            2 => ProtocolCode::ProtocolEnumerateGPUS,
+            3 => ProtocolCode::ProtocolGPUInfo,
+            4 => ProtocolCode::ProtocolEnumerateScreens,
+            5 => ProtocolCode::ProtocolScreenInfo,
+            6 => ProtocolCode::ProtocolEnumerateModes,
+            7 => ProtocolCode::ProtocolSetMode,
+            8 => ProtocolCode::ProtocolScreenModeChanged,
+            9 => ProtocolCode::ProtocolSetCursor,
+            10 => ProtocolCode::ProtocolMoveCursor,
+            11 => ProtocolCode::ProtocolScreenAdded,
+            12 => ProtocolCode::ProtocolScreenRemoved,
+            13 => ProtocolCode::ProtocolScreenChanged,
+            14 => ProtocolCode::ProtocolScreenDetails,
+            15 => ProtocolCode::ProtocolGetGamma,
+            16 => ProtocolCode::ProtocolSetGamma,
+            17 => ProtocolCode::ProtocolSetColorTemperature,
+            18 => ProtocolCode::ProtocolGetPowerState,
+            19 => ProtocolCode::ProtocolSetPowerState,
+            20 => ProtocolCode::ProtocolSetIdleTimeout,
+            21 => ProtocolCode::ProtocolPowerStateChanged,
+            22 => ProtocolCode::ProtocolGetLayout,
+            23 => ProtocolCode::ProtocolSetScreenPosition,
+            24 => ProtocolCode::ProtocolSetMirror,
+            25 => ProtocolCode::ProtocolLayoutChanged,
+            26 => ProtocolCode::ProtocolSetTransform,
+            27 => ProtocolCode::ProtocolCreatePool,
+            28 => ProtocolCode::ProtocolDestroyPool,
+            29 => ProtocolCode::ProtocolCreateBuffer,
+            30 => ProtocolCode::ProtocolDestroyBuffer,
+            31 => ProtocolCode::ProtocolAttachBuffer,
+            32 => ProtocolCode::ProtocolCreateDmabufBuffer,
+            _ => ProtocolCode::ProtocolNone,
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{collections::VecDeque, os::fd::OwnedFd, sync::Arc};
+use exodus_errors::ErrorKind;
+
+use crate::messages::*;
+use crate::protocol_code::ProtocolCode;
+use crate::wire::*;
+
+/// Minimal little-endian buffer standing in for `NetworkMessage`.
+#[derive(Default)]
+struct Buffer {
+    index: usize,
+    bytes: Vec<u8>,
+    fds: VecDeque<Arc<OwnedFd>>,
+}
+
+impl Buffer {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ErrorKind> {
+        if self.index + N > self.bytes.len() {
+            return Err(ErrorKind::NETWORKMESSAGE_EMPTY);
+        }
+
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(&self.bytes[self.index..self.index + N]);
+        self.index += N;
+        Ok(bytes)
+    }
+}
+
+impl WireWriter for Buffer {
+    fn write_u8(&mut self, value: u8) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_u16(&mut self, value: u16) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_u32(&mut self, value: u32) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_u64(&mut self, value: u64) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_i8(&mut self, value: i8) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_i16(&mut self, value: i16) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_i32(&mut self, value: i32) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_i64(&mut self, value: i64) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_f32(&mut self, value: f32) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+    fn write_f64(&mut self, value: f64) { self.bytes.extend_from_slice(&value.to_le_bytes()) }
+
+    fn write_string_utf8(&mut self, value: &str) {
+        self.write_u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn write_string_utf16(&mut self, value: &str) {
+        let units = value.encode_utf16().collect::<Vec<u16>>();
+        self.write_u32(units.len() as u32);
+        units.iter().for_each(|unit| self.write_u16(*unit));
+    }
+
+    fn write_fd(&mut self, fd: Arc<OwnedFd>) { self.fds.push_back(fd) }
+}
+
+impl WireReader for Buffer {
+    fn read_u8(&mut self) -> Result<u8, ErrorKind> { Ok(u8::from_le_bytes(self.take()?)) }
+    fn read_u16(&mut self) -> Result<u16, ErrorKind> { Ok(u16::from_le_bytes(self.take()?)) }
+    fn read_u32(&mut self) -> Result<u32, ErrorKind> { Ok(u32::from_le_bytes(self.take()?)) }
+    fn read_u64(&mut self) -> Result<u64, ErrorKind> { Ok(u64::from_le_bytes(self.take()?)) }
+    fn read_i8(&mut self) -> Result<i8, ErrorKind> { Ok(i8::from_le_bytes(self.take()?)) }
+    fn read_i16(&mut self) -> Result<i16, ErrorKind> { Ok(i16::from_le_bytes(self.take()?)) }
+    fn read_i32(&mut self) -> Result<i32, ErrorKind> { Ok(i32::from_le_bytes(self.take()?)) }
+    fn read_i64(&mut self) -> Result<i64, ErrorKind> { Ok(i64::from_le_bytes(self.take()?)) }
+    fn read_f32(&mut self) -> Result<f32, ErrorKind> { Ok(f32::from_le_bytes(self.take()?)) }
+    fn read_f64(&mut self) -> Result<f64, ErrorKind> { Ok(f64::from_le_bytes(self.take()?)) }
+
+    fn read_string_utf8(&mut self) -> Result<String, ErrorKind> {
+        let length = self.read_u32()? as usize;
+        let bytes = (0..length).map(|_| self.read_u8()).collect::<Result<Vec<u8>, ErrorKind>>()?;
+        String::from_utf8(bytes).map_err(|_| ErrorKind::NETWORKMESSAGE_FAILED)
+    }
+
+    fn read_string_utf16(&mut self) -> Result<String, ErrorKind> {
+        let length = self.read_u32()? as usize;
+        let units = (0..length).map(|_| self.read_u16()).collect::<Result<Vec<u16>, ErrorKind>>()?;
+        String::from_utf16(&units).map_err(|_| ErrorKind::NETWORKMESSAGE_FAILED)
+    }
+
+    fn read_fd(&mut self) -> Result<Arc<OwnedFd>, ErrorKind> {
+        self.fds.pop_front().ok_or(ErrorKind::NETWORKMESSAGE_FDS_INVALID)
+    }
+}
+
+fn roundtrip<M: Message + PartialEq + std::fmt::Debug>(message: &M) -> Buffer {
+    let mut buffer = Buffer::default();
+    message.encode(&mut buffer);
+
+    let decoded = M::decode(&mut buffer).unwrap();
+    assert_eq!(&decoded, message);
+    assert_eq!(buffer.index, buffer.bytes.len());
+
+    buffer
+}
+
+#[test]
+fn messages_entity_register_roundtrip() {
+
+    let request = EntityRegisterRequest {
+        class: "Mozilla Firefox".to_string(),
+        title: Utf16String::from("Mozilla Firefox - Web Browser"),
+        min_version: 100,
+        max_version: 100,
+        author: "Mozilla Foundation".to_string(),
+        description: String::new(),
+        extensions: vec!["exodus_test".to_string()],
+    };
+
+    roundtrip(&request);
+    assert_eq!(EntityRegisterRequest::CODE, ProtocolCode::ProtocolEntityRegister);
+    assert_eq!(<EntityRegisterRequest as Request>::Reply::CODE, ProtocolCode::ProtocolEntityRegister);
+}
+
+#[test]
+fn messages_layout_follows_declaration_order() {
+
+    let reply = EnumerateGPUsReply { gpus: vec![5, 9] };
+    let buffer = roundtrip(&reply);
+
+    assert_eq!(buffer.bytes, [2, 0, 0, 0, 5, 0, 0, 0, 9, 0, 0, 0]);
+}
+
+#[test]
+fn messages_empty_request() {
+
+    let buffer = roundtrip(&EnumerateGPUsRequest {});
+    assert!(buffer.bytes.is_empty());
+}
+
+#[test]
+fn messages_truncated_payload() {
+
+    let mut buffer = Buffer::default();
+    ScreenInfoRequest { gpu: 5, screen: 2 }.encode(&mut buffer);
+    buffer.bytes.truncate(6);
+
+    assert_eq!(ScreenInfoRequest::decode(&mut buffer), Err(ErrorKind::NETWORKMESSAGE_EMPTY));
+}
+
+#[test]
+fn messages_nested_records() {
+
+    let reply = EnumerateModesReply {
+        current: 1,
+        modes: vec![
+            ModeInfo { index: 0, width: 1920, height: 1080, refresh: 60, flags: MODE_FLAG_PREFERRED },
+            ModeInfo { index: 1, width: 1280, height: 720, refresh: 60, flags: 0 },
+        ],
+    };
+    let buffer = roundtrip(&reply);
+
+    assert_eq!(buffer.bytes.len(), 4 + 4 + 2 * 20);
+    assert_eq!(&buffer.bytes[8..12], &[0, 0, 0, 0]);
+    assert_eq!(&buffer.bytes[12..16], &1920u32.to_le_bytes());
+}
+
+#[test]
+fn messages_fd_roundtrip() {
+
+    let file = std::fs::File::open("/dev/null").unwrap();
+    let request = CreatePoolRequest { size: 4096, fd: Fd::new(OwnedFd::from(file)) };
+
+    let buffer = roundtrip(&request);
+    assert_eq!(buffer.bytes, [0, 16, 0, 0, 1]);
+    assert!(buffer.fds.is_empty());
+
+    let mut buffer = Buffer::default();
+    request.encode(&mut buffer);
+    buffer.fds.clear();
+    assert_eq!(CreatePoolRequest::decode(&mut buffer), Err(ErrorKind::NETWORKMESSAGE_FDS_INVALID));
+
+    roundtrip(&CreatePoolRequest { size: 0, fd: Fd::default() });
+}
+
+#[test]
+fn messages_dmabuf_roundtrip() {
+
+    let plane = |offset| DmabufPlane { fd: Fd::new(OwnedFd::from(std::fs::File::open("/dev/null").unwrap())), offset, stride: 7680 };
+    let request = CreateDmabufBufferRequest {
+        gpu: 0,
+        width: 1920,
+        height: 1080,
+        format: u32::from_le_bytes(*b"XR24"),
+        modifier: 0x00ffffffffffffff,
+        planes: vec![plane(0), plane(8294400)],
+    };
+
+    let buffer = roundtrip(&request);
+    assert_eq!(&buffer.bytes[16..24], &0x00ffffffffffffffu64.to_le_bytes());
+
+    let mut buffer = Buffer::default();
+    request.encode(&mut buffer);
+    assert_eq!(buffer.fds.len(), 2);
+
+    buffer.fds.pop_back();
+    assert_eq!(CreateDmabufBufferRequest::decode(&mut buffer), Err(ErrorKind::NETWORKMESSAGE_FDS_INVALID));
+    assert_eq!(ProtocolCode::from(32), ProtocolCode::ProtocolCreateDmabufBuffer);
+}
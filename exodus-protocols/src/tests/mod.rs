@@ -0,0 +1,4 @@
+#[cfg(test)]
+pub mod protocol_code;
+#[cfg(test)]
+pub mod messages;
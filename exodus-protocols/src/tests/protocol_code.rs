@@ -0,0 +1,36 @@
+use crate::protocol_code::*;
+
+#[test]
+fn protocol_code_from_i32() {
+
+    assert_eq!(ProtocolCode::from(ProtocolCode::ProtocolScreenInfo as i32), ProtocolCode::ProtocolScreenInfo);
+    assert_eq!(ProtocolCode::from(ProtocolCode::ProtocolError as i32), ProtocolCode::ProtocolError);
+    assert_eq!(ProtocolCode::from(ProtocolCode::ProtocolScreenModeChanged as i32), ProtocolCode::ProtocolScreenModeChanged);
+    assert_eq!(ProtocolCode::from(ProtocolCode::ProtocolPowerStateChanged as i32), ProtocolCode::ProtocolPowerStateChanged);
+    assert_eq!(ProtocolCode::from(i32::MAX), ProtocolCode::ProtocolNone);
+}
+
+#[test]
+fn protocol_code_since() {
+
+    assert_eq!(ProtocolCode::ProtocolScreenInfo.since(), PROTOCOL_VERSION_1_0_0);
+    assert_eq!(ProtocolCode::ProtocolSetMode.since(), PROTOCOL_VERSION_1_1_0);
+    assert_eq!(ProtocolCode::ProtocolSetCursor.since(), PROTOCOL_VERSION_1_2_0);
+    assert_eq!(negotiate_version(PROTOCOL_VERSION_1_0_0, PROTOCOL_VERSION_1_0_0), Some(PROTOCOL_VERSION_1_0_0));
+}
+
+#[test]
+fn protocol_negotiate_version() {
+
+    assert_eq!(negotiate_version(PROTOCOL_VERSION_MIN, PROTOCOL_VERSION_MAX), Some(PROTOCOL_VERSION_MAX));
+    assert_eq!(negotiate_version(0, u32::MAX), Some(PROTOCOL_VERSION_MAX));
+    assert_eq!(negotiate_version(PROTOCOL_VERSION_MAX + 1, u32::MAX), None);
+    assert_eq!(negotiate_version(0, PROTOCOL_VERSION_MIN - 1), None);
+}
+
+#[test]
+fn protocol_code_extension() {
+
+    assert_eq!(ProtocolCode::ProtocolCreateDmabufBuffer.extension(), Some(EXTENSION_DMABUF));
+    assert_eq!(ProtocolCode::ProtocolCreateBuffer.extension(), None);
+}
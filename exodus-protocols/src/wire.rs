@@ -0,0 +1,204 @@
+use std::{os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd}, sync::Arc};
+use exodus_errors::ErrorKind;
+use crate::protocol_code::ProtocolCode;
+
+/// Sink for the primitive types of the wire format, little-endian.
+pub trait WireWriter {
+    fn write_u8(&mut self, value: u8);
+    fn write_u16(&mut self, value: u16);
+    fn write_u32(&mut self, value: u32);
+    fn write_u64(&mut self, value: u64);
+    fn write_i8(&mut self, value: i8);
+    fn write_i16(&mut self, value: i16);
+    fn write_i32(&mut self, value: i32);
+    fn write_i64(&mut self, value: i64);
+    fn write_f32(&mut self, value: f32);
+    fn write_f64(&mut self, value: f64);
+    fn write_string_utf8(&mut self, value: &str);
+    fn write_string_utf16(&mut self, value: &str);
+    /// Attaches a file descriptor to the message, sent as ancillary data.
+    fn write_fd(&mut self, fd: Arc<OwnedFd>);
+}
+
+/// Source for the primitive types of the wire format, little-endian.
+pub trait WireReader {
+    fn read_u8(&mut self) -> Result<u8, ErrorKind>;
+    fn read_u16(&mut self) -> Result<u16, ErrorKind>;
+    fn read_u32(&mut self) -> Result<u32, ErrorKind>;
+    fn read_u64(&mut self) -> Result<u64, ErrorKind>;
+    fn read_i8(&mut self) -> Result<i8, ErrorKind>;
+    fn read_i16(&mut self) -> Result<i16, ErrorKind>;
+    fn read_i32(&mut self) -> Result<i32, ErrorKind>;
+    fn read_i64(&mut self) -> Result<i64, ErrorKind>;
+    fn read_f32(&mut self) -> Result<f32, ErrorKind>;
+    fn read_f64(&mut self) -> Result<f64, ErrorKind>;
+    fn read_string_utf8(&mut self) -> Result<String, ErrorKind>;
+    fn read_string_utf16(&mut self) -> Result<String, ErrorKind>;
+    /// Takes the next file descriptor received with the message.
+    fn read_fd(&mut self) -> Result<Arc<OwnedFd>, ErrorKind>;
+}
+
+/// A type with a fixed encoding on the wire.
+pub trait Wire: Sized {
+    fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W);
+    fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind>;
+}
+
+/// A message payload bound to its `ProtocolCode`.
+pub trait Message: Wire {
+    const CODE: ProtocolCode;
+}
+
+/// A message sent by an entity and answered by `Reply`.
+pub trait Request: Message {
+    type Reply: Message;
+}
+
+/// A message sent by the server without being asked for.
+pub trait Event: Message {}
+
+/// String sent as utf16 code units instead of utf8.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Utf16String(pub String);
+
+impl From<&str> for Utf16String {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl std::ops::Deref for Utf16String {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// File descriptor sent alongside a message, `None` when the message carries none.
+///
+/// The payload only holds a presence byte, the descriptor itself travels as
+/// `SCM_RIGHTS` ancillary data and is taken in order when decoding.
+#[derive(Debug, Clone, Default)]
+pub struct Fd(pub Option<Arc<OwnedFd>>);
+
+impl Fd {
+    pub fn new(fd: OwnedFd) -> Self {
+        Self(Some(Arc::new(fd)))
+    }
+
+    /// Duplicates `fd`, the duplicate is closed once the message is sent.
+    pub fn dup(fd: BorrowedFd) -> Result<Self, ErrorKind> {
+        fd.try_clone_to_owned().map(Self::new).map_err(|_| ErrorKind::NETWORKMESSAGE_FDS_INVALID)
+    }
+
+    pub fn as_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.0.as_ref().map(|fd| fd.as_fd())
+    }
+
+    /// The descriptor, duplicated when the message is still shared.
+    pub fn into_owned(self) -> Option<OwnedFd> {
+        let fd = self.0?;
+        Arc::try_unwrap(fd).or_else(|fd| fd.try_clone()).ok()
+    }
+}
+
+impl PartialEq for Fd {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_ref().map(|fd| fd.as_raw_fd()) == other.0.as_ref().map(|fd| fd.as_raw_fd())
+    }
+}
+
+impl Wire for Fd {
+    fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write_u8(self.0.is_some() as u8);
+        if let Some(fd) = &self.0 {
+            writer.write_fd(fd.clone());
+        }
+    }
+
+    fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind> {
+        match reader.read_u8()? {
+            0 => Ok(Self(None)),
+            _ => Ok(Self(Some(reader.read_fd()?))),
+        }
+    }
+}
+
+macro_rules! wire_primitive {
+    ($($ty:ty => $write:ident, $read:ident;)*) => {
+        $(
+            impl Wire for $ty {
+                fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W) {
+                    writer.$write(*self);
+                }
+
+                fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind> {
+                    reader.$read()
+                }
+            }
+        )*
+    };
+}
+
+wire_primitive! {
+    u8  => write_u8,  read_u8;
+    u16 => write_u16, read_u16;
+    u32 => write_u32, read_u32;
+    u64 => write_u64, read_u64;
+    i8  => write_i8,  read_i8;
+    i16 => write_i16, read_i16;
+    i32 => write_i32, read_i32;
+    i64 => write_i64, read_i64;
+    f32 => write_f32, read_f32;
+    f64 => write_f64, read_f64;
+}
+
+impl Wire for bool {
+    fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write_u8(*self as u8);
+    }
+
+    fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind> {
+        Ok(reader.read_u8()? != 0)
+    }
+}
+
+impl Wire for String {
+    fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write_string_utf8(self);
+    }
+
+    fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind> {
+        reader.read_string_utf8()
+    }
+}
+
+impl Wire for Utf16String {
+    fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write_string_utf16(&self.0);
+    }
+
+    fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind> {
+        Ok(Self(reader.read_string_utf16()?))
+    }
+}
+
+/// Lists are sent as a 32 bits count followed by the items.
+impl<T: Wire> Wire for Vec<T> {
+    fn encode<W: WireWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write_u32(self.len() as u32);
+        for item in self {
+            item.encode(writer);
+        }
+    }
+
+    fn decode<R: WireReader + ?Sized>(reader: &mut R) -> Result<Self, ErrorKind> {
+        let count = reader.read_u32()? as usize;
+        let mut items = Vec::with_capacity(count.min(0x100));
+        for _ in 0..count {
+            items.push(T::decode(reader)?);
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,96 @@
+use std::str::FromStr;
+use exodus_common::{consts::{EXODUS_BACKEND, EXODUS_HEADLESS_SCREENS}, info, warn};
+use exodus_errors::ErrorKind;
+use crate::device::GPU;
+
+/// The hardware the display drives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Kernel mode setting through `/dev/dri/card*`.
+    Drm,
+    /// A software GPU with one virtual screen per mode, for machines without a GPU.
+    Headless(Vec<HeadlessMode>),
+}
+
+impl Backend {
+    /// Selects the backend from `EXODUS_BACKEND` (`drm` or `headless`).
+    /// 
+    /// When unset, DRM is used if a card is present and headless otherwise.
+    /// Headless screens are read from `EXODUS_HEADLESS_SCREENS`, e.g. `1920x1080@60,1280x720`.
+    pub fn from_env() -> Self {
+        match std::env::var(EXODUS_BACKEND).as_deref() {
+            Ok("drm") => return Backend::Drm,
+            Ok("headless") => return Backend::Headless(Self::headless_modes()),
+            Ok(other) => warn!("Unknown backend \"{}\", detecting one.", other),
+            Err(_) => (),
+        }
+
+        if GPU::available() {
+            return Backend::Drm;
+        }
+
+        info!("No GPU available, using the headless backend.");
+        Backend::Headless(Self::headless_modes())
+    }
+
+    fn headless_modes() -> Vec<HeadlessMode> {
+        let mut modes = Vec::new();
+
+        if let Ok(screens) = std::env::var(EXODUS_HEADLESS_SCREENS) {
+            for screen in screens.split(',').filter(|x| !x.trim().is_empty()) {
+                match screen.parse() {
+                    Ok(mode) => modes.push(mode),
+                    Err(err) => warn!("Ignoring headless screen \"{}\". - ErrorKind: {:?}", screen, err),
+                }
+            }
+        }
+
+        if modes.is_empty() {
+            modes.push(HeadlessMode::default());
+        }
+
+        modes
+    }
+}
+
+/// Resolution and refresh rate of a virtual screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadlessMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh: u32,
+}
+
+impl HeadlessMode {
+    pub fn new(width: u32, height: u32, refresh: u32) -> Self {
+        Self { width, height, refresh }
+    }
+}
+
+impl Default for HeadlessMode {
+    fn default() -> Self {
+        Self::new(1920, 1080, 60)
+    }
+}
+
+/// Parses `WIDTHxHEIGHT` with an optional `@REFRESH`, which defaults to 60.
+impl FromStr for HeadlessMode {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size, refresh) = match s.trim().split_once('@') {
+            Some((size, refresh)) => (size, refresh.parse().map_err(|_| ErrorKind::HEADLESS_MODE_INVALID)?),
+            None => (s.trim(), 60),
+        };
+
+        let (width, height) = size.split_once('x').ok_or(ErrorKind::HEADLESS_MODE_INVALID)?;
+        let width: u32 = width.parse().map_err(|_| ErrorKind::HEADLESS_MODE_INVALID)?;
+        let height: u32 = height.parse().map_err(|_| ErrorKind::HEADLESS_MODE_INVALID)?;
+
+        if width == 0 || height == 0 || refresh == 0 {
+            return Err(ErrorKind::HEADLESS_MODE_INVALID);
+        }
+
+        Ok(Self::new(width, height, refresh))
+    }
+}
@@ -1,5 +1,16 @@
-use exodus_common::{net::{connection::Connection, network_message::NetworkMessage}, debug};
+use std::{collections::HashMap, os::fd::{AsRawFd, RawFd}, rc::Rc};
+use exodus_common::{graphics::buffer::Buffer, net::{connection::Connection, network_message::NetworkMessage}, debug, error};
 use exodus_errors::ErrorKind;
+use exodus_protocols::protocol_code::ProtocolCode;
+use crate::shm::{MAX_BUFFERS, MAX_POOLS, ShmBuffer, ShmPool};
+
+/// Content an entity can attach to a screen.
+#[derive(Debug)]
+pub enum EntityBuffer {
+    Shm(ShmBuffer),
+    /// Imported from DMA-BUFs on `gpu`, only its screens can show it.
+    Dmabuf { gpu: i32, buffer: Buffer },
+}
 
 #[derive(Debug)]
 pub struct Entity {
@@ -9,6 +20,13 @@ pub struct Entity {
     version: u32,
     author: String,
     description: String,
+    extensions: Vec<String>,
+    /// Shared memory pools and buffers of the entity, ids are shared by both.
+    pools: HashMap<u32, Rc<ShmPool>>,
+    buffers: HashMap<u32, EntityBuffer>,
+    next_object: u32,
+    /// Overlay planes showing imported buffers of the entity, by GPU and screen.
+    overlays: HashMap<(i32, u32), u32>,
 }
 
 impl Entity {
@@ -20,7 +38,12 @@ impl Entity {
             title: String::new(),
             version: 0,
             author: String::new(),
-            description: String::new()
+            description: String::new(),
+            extensions: Vec::new(),
+            pools: HashMap::new(),
+            buffers: HashMap::new(),
+            next_object: 0,
+            overlays: HashMap::new(),
         }
     }
     pub fn id(&self) -> u32 {
@@ -47,14 +70,43 @@ impl Entity {
         self.description = description;
     }
 
+    pub(crate) fn set_extensions(&mut self, extensions: Vec<String>) {
+        self.extensions = extensions;
+    }
+
     pub fn class(&self) -> &str {
         &self.class
     }
 
+    /// The negotiated protocol version, `0` until the entity is registered.
     pub fn version(&self) -> u32 {
         self.version
     }
 
+    #[inline]
+    pub fn is_registered(&self) -> bool {
+        self.version != 0
+    }
+
+    /// The extensions negotiated with the entity.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Returns `true` when the request is available in the negotiated version and extensions.
+    pub fn supports(&self, code: ProtocolCode) -> bool {
+        if code == ProtocolCode::ProtocolEntityRegister {
+            return true;
+        }
+
+        let extension = match code.extension() {
+            Some(name) => self.extensions.iter().any(|x| x == name),
+            None => true,
+        };
+
+        self.is_registered() && self.version >= code.since() && extension
+    }
+
     pub fn author(&self) -> &str {
         &self.author
     }
@@ -63,13 +115,85 @@ impl Entity {
         &self.description
     }
 
+    /// Keeps a pool for the entity, failing with `SHM_INVALID` once it holds `MAX_POOLS`.
+    pub(crate) fn add_pool(&mut self, pool: ShmPool) -> Result<u32, ErrorKind> {
+        if self.pools.len() >= MAX_POOLS {
+            let err = ErrorKind::SHM_INVALID;
+            error!("Too many pools. - EntityID: {} - Count: {} - ErrorKind: {:?}", self.id(), self.pools.len(), err);
+            return Err(err);
+        }
+
+        self.next_object += 1;
+        self.pools.insert(self.next_object, Rc::new(pool));
+        Ok(self.next_object)
+    }
+
+    /// Forgets a pool, its buffers keep it mapped until they are removed.
+    pub(crate) fn remove_pool(&mut self, id: u32) -> bool {
+        self.pools.remove(&id).is_some()
+    }
+
+    pub fn pool(&self, id: u32) -> Option<&Rc<ShmPool>> {
+        self.pools.get(&id)
+    }
+
+    /// Keeps a buffer for the entity, failing with `SHM_INVALID` once it holds `MAX_BUFFERS`.
+    pub(crate) fn add_buffer(&mut self, buffer: EntityBuffer) -> Result<u32, ErrorKind> {
+        if self.buffers.len() >= MAX_BUFFERS {
+            let err = ErrorKind::SHM_INVALID;
+            error!("Too many buffers. - EntityID: {} - Count: {} - ErrorKind: {:?}", self.id(), self.buffers.len(), err);
+            return Err(err);
+        }
+
+        self.next_object += 1;
+        self.buffers.insert(self.next_object, buffer);
+        Ok(self.next_object)
+    }
+
+    pub(crate) fn remove_buffer(&mut self, id: u32) -> bool {
+        self.buffers.remove(&id).is_some()
+    }
+
+    pub fn buffer(&self, id: u32) -> Option<&EntityBuffer> {
+        self.buffers.get(&id)
+    }
+
+    /// Records the overlay plane showing a buffer of the entity on a screen,
+    /// returning the plane it replaces.
+    pub(crate) fn replace_overlay(&mut self, gpu: i32, screen: u32, plane: Option<u32>) -> Option<u32> {
+        match plane {
+            Some(plane) => self.overlays.insert((gpu, screen), plane),
+            None => self.overlays.remove(&(gpu, screen)),
+        }
+    }
+
+    /// Planes showing buffers of the entity, as `((gpu, screen), plane)`.
+    pub(crate) fn overlays(&self) -> impl Iterator<Item = ((i32, u32), u32)> + '_ {
+        self.overlays.iter().map(|(key, plane)| (*key, *plane))
+    }
+
     pub(crate) fn recv_message(&mut self) -> Result<Option<NetworkMessage>, ErrorKind> {
         self.conn.buffer()
     }
 
-    pub fn send(&mut self, msg: NetworkMessage) {
+    pub fn send(&mut self, msg: NetworkMessage) -> Result<(), ErrorKind> {
         self.conn.send(msg)
     }
+
+    pub(crate) fn flush(&mut self) -> Result<(), ErrorKind> {
+        self.conn.flush()
+    }
+
+    /// Returns `true` when replies are still waiting for the socket to drain.
+    pub(crate) fn has_pending_output(&self) -> bool {
+        self.conn.has_pending_output()
+    }
+}
+
+impl AsRawFd for Entity {
+    fn as_raw_fd(&self) -> RawFd {
+        self.conn.as_raw_fd()
+    }
 }
 
 impl Drop for Entity {
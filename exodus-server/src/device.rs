@@ -1,104 +1,69 @@
 #![allow(dead_code)]
 
 use std::fs::OpenOptions;
-use std::{fs::File, os::fd::AsRawFd};
+use std::{rc::Rc, sync::Arc};
+use std::{fs::File, os::fd::{AsRawFd, RawFd}};
 use std::os::unix::fs::OpenOptionsExt;
 use drm::*;
 use exodus_common::consts::DRI_DIRECTORY;
 use exodus_common::enums::{Vendor, ScreenFlags};
+use exodus_common::graphics::backend::{BufferAllocator, GraphicsDevice, Mode, Output};
 use exodus_common::graphics::device::{DeviceRef, Device, GPUID};
 use exodus_common::*;
 use exodus_errors::ErrorKind;
-use crate::*;
-use crate::screen::Screen;
-
+use crate::screen::{self, Kms, PreparedMode, Screen};
+use crate::backend::HeadlessMode;
+use crate::headless::HeadlessDevice;
+
+
+/// How the screens of a GPU changed when its outputs were probed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenChange {
+    Added(u32),
+    Removed(u32),
+    /// Still connected, but to a different monitor or offering other modes.
+    Changed(u32),
+}
 
+/// A GPU and the screens of its outputs, on any [`GraphicsDevice`].
 #[derive(Debug)]
 pub struct GPU {
-    card:       File,
-    vendor:     Vendor,
-    model:      u32,
-    width:      u32,
-    height:     u32,
-    device:     Option<DeviceRef>,
-    screens:    Vec<Screen>,
+    screens:        Vec<Screen>,
+    device:         Box<dyn GraphicsDevice>,
+    flags:          Vec<ScreenFlags>,
+    /// Ids of the screens unplugged since the GPU was loaded.
+    disconnected:   Vec<u32>,
+    /// Screens driven as clones, as `(screen, source)`.
+    clones:         Vec<(u32, u32)>,
 }
 
 impl GPU {
-    
-    fn load(path: &str) -> Result<Self, ErrorKind> {
-        info!("Loading gpu: \"{}\"", path);
 
-        let card = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .custom_flags(libc::O_RDWR | libc::O_CLOEXEC | libc::O_NONBLOCK)
-            .open(path);
+    /// Creates a screen for each output of the device.
+    pub fn new(mut device: Box<dyn GraphicsDevice>, flags: &[ScreenFlags]) -> Result<Self, ErrorKind> {
+        let allocator = device.allocator();
 
-        if let Err(_) = card {
-            let err = ErrorKind::GPU_LOAD_FAILED;
-            error!("Failed to load gpu: \"{}\" - ErrorKind: {:?}", path, err);
-            return Err(err);
-        }
-
-        let card = card.unwrap();
-        let gpu = card.as_raw_fd();
-        let vendor = unsafe { Self::get_card_vendor(gpu) };
-        let vendor = Vendor::from(vendor);
-        let model = unsafe { Self::get_card_model(gpu) };
-
-        debug!("Getting gpu resources...");
-        let resources_ptr: *mut drm::_drmModeRes = unsafe { drmModeGetResources(gpu) };
-        if resources_ptr.is_null() {
-            let err = ErrorKind::GPU_RESOURCES_FAILED;
-            error!("Failed to get gpu resources. - ErrorKind: {:?}", err);
-            return Err(err);
+        let mut screens = Vec::new();
+        for output in device.outputs(flags)? {
+            screens.push(Screen::new(allocator.clone(), output, flags)?);
         }
 
-        let device = Device::new(gpu)?;
-        let resources = unsafe { resources_ptr.as_ref().unwrap() };
-        let screens = Screen::enumerate_screens(&device, resources,&[ScreenFlags::DoubleBuffered])?;
-
-        unsafe { drmModeFreeResources(resources_ptr) };
-
-        debug!("GPU loaded successfully. - GPUID: {} - Vendor: {:?} ", gpu, vendor);
-        
-        Ok(GPU {
-            card,
-            width: resources.max_width,
-            height: resources.max_height,
-            device: Some(device),
-            vendor,
-            screens,
-            model,
-        })
+        debug!("GPU loaded successfully. - GPUID: {} - Vendor: {:?} ", device.id(), device.vendor());
+        Ok(Self { screens, device, flags: flags.to_vec(), disconnected: Vec::new(), clones: Vec::new() })
     }
 
-    #[inline]
-    pub fn id(&self) -> i32 {
-        self.card.as_raw_fd()
+    /// Creates a software GPU driving one virtual screen per mode.
+    pub fn headless(id: GPUID, modes: &[HeadlessMode]) -> Result<Self, ErrorKind> {
+        info!("Loading headless gpu. - GPUID: {} - Screens: {}", id, modes.len());
+        Self::new(Box::new(HeadlessDevice::new(id, modes)), &[ScreenFlags::TripleBuffered])
     }
 
-    unsafe fn get_card_vendor(gpu: GPUID) -> u16 {
-        let mut device_ptr = std::ptr::null_mut();
-        drmGetDevice(gpu, &mut device_ptr);
-
-        let drm_device = device_ptr.as_ref().unwrap() ;
-        let vendor_id = drm_device.deviceinfo.pci.as_ref().unwrap().vendor_id;
-
-        drmFreeDevice(&mut device_ptr);
-        return vendor_id;
-    }
-
-    unsafe fn get_card_model(gpu: GPUID) -> u32 {
-        let mut device_ptr = std::ptr::null_mut();
-        drmGetDevice(gpu, &mut device_ptr);
-
-        let drm_device = device_ptr.as_ref().unwrap() ;
-        let device_id = drm_device.deviceinfo.pci.as_ref().unwrap().device_id;
-
-        drmFreeDevice(&mut device_ptr);
-        return device_id as u32;
+    /// Returns `true` when a DRM card can be loaded.
+    pub fn available() -> bool {
+        match std::fs::read_dir(DRI_DIRECTORY) {
+            Ok(entries) => entries.flatten().any(|e| e.file_name().to_string_lossy().contains("card")),
+            Err(_) => false,
+        }
     }
 
     pub fn enumerate_gpus() -> Result<Vec<GPU>, ErrorKind> {
@@ -128,8 +93,9 @@ impl GPU {
 
         let mut gpus = Vec::new();
         for path in cards.iter().filter(|x| x.contains("card")) {
-            let gpu = GPU::load(path)?;
-            info!("GPU detected. - GPUID: {} - Vendor: {:?}", gpu.id(), gpu.vendor);
+            let device = DrmDevice::load(path)?;
+            let gpu = GPU::new(Box::new(device), &[ScreenFlags::TripleBuffered])?;
+            info!("GPU detected. - GPUID: {} - Vendor: {:?}", gpu.id(), gpu.vendor());
             gpus.push(gpu);
         }
 
@@ -143,12 +109,214 @@ impl GPU {
         Ok(gpus)
     }
 
-    pub fn device(&self) -> Option<DeviceRef> {
-        self.device.clone()
+    #[inline]
+    pub fn id(&self) -> i32 {
+        self.device.id()
+    }
+
+    pub fn device(&self) -> &dyn GraphicsDevice {
+        self.device.as_ref()
+    }
+
+    /// File descriptor signalling completed presentations, see [`GraphicsDevice::event_fd`].
+    pub fn event_fd(&self) -> Option<RawFd> {
+        self.device.event_fd()
+    }
+
+    /// Processes completed presentations, once `event_fd` is readable.
+    pub fn dispatch_events(&mut self) -> Result<(), ErrorKind> {
+        self.device.dispatch_events()
     }
 
     pub fn vendor(&self) -> Vendor {
-        self.vendor
+        self.device.vendor()
+    }
+
+    /// Probes the outputs again after a hotplug, creating screens for new ones and
+    /// tearing down those that went away.
+    /// 
+    /// A screen whose output offers other modes is created again and reported as
+    /// changed, or as removed when it cannot be driven anymore. Outputs that fail
+    /// to initialize are skipped.
+    pub fn rescan(&mut self) -> Result<Vec<ScreenChange>, ErrorKind> {
+        let outputs = self.device.outputs(&self.flags)?;
+        let allocator = self.device.allocator();
+        let mut changes = Vec::new();
+
+        let connected: Vec<u32> = outputs.iter().map(|output| output.id()).collect();
+
+        // Clones involving a screen that goes away or is created again are undone first.
+        let stale: Vec<u32> = self.screens.iter()
+            .filter(|screen| match outputs.iter().find(|output| output.id() == screen.id()) {
+                Some(output) => screen.modes() != output.modes() || screen.edid() != output.edid(),
+                None => true,
+            })
+            .map(|screen| screen.id())
+            .collect();
+
+        for id in stale {
+            self.release_clones(id);
+        }
+
+        let disconnected = &mut self.disconnected;
+        self.screens.retain(|screen| {
+            if connected.contains(&screen.id()) {
+                return true;
+            }
+
+            info!("Screen disconnected. - ScreenID: {}", screen.id());
+            disconnected.push(screen.id());
+            changes.push(ScreenChange::Removed(screen.id()));
+            false
+        });
+
+        for output in outputs {
+            let id = output.id();
+            let change = match self.screens.iter().position(|screen| screen.id() == id) {
+                Some(index) if self.screens[index].modes() != output.modes() || self.screens[index].edid() != output.edid() => {
+                    self.screens.remove(index);
+                    ScreenChange::Changed(id)
+                },
+                Some(_) => continue,
+                None => ScreenChange::Added(id),
+            };
+
+            let screen = match Screen::new(allocator.clone(), output, &self.flags) {
+                Ok(screen) => screen,
+                Err(err) => {
+                    error!("Failed to initialize screen, skipping it. - ScreenID: {} - ErrorKind: {:?}", id, err);
+                    if change == ScreenChange::Changed(id) {
+                        self.disconnected.push(id);
+                        changes.push(ScreenChange::Removed(id));
+                    }
+                    continue;
+                }
+            };
+
+            info!("Screen connected. - ScreenID: {} - Change: {:?}", id, change);
+            self.disconnected.retain(|screen| *screen != id);
+            self.screens.push(screen);
+            changes.push(change);
+        }
+
+        Ok(changes)
+    }
+
+    /// Drives the screen `id` from the CRTC of the screen `source`, as a clone, or from its own again when `None`.
+    /// 
+    /// On failure the screen keeps showing its own picture.
+    pub fn set_clone(&mut self, id: u32, source: Option<u32>) -> Result<(), ErrorKind> {
+        let previous = self.clones.iter().find(|(screen, _)| *screen == id).map(|(_, source)| *source);
+        if previous == source {
+            return Ok(());
+        }
+
+        if let Some(previous) = previous {
+            self.clones.retain(|(screen, _)| *screen != id);
+            self.update_clones(previous)?;
+
+            if let Some(screen) = self.get_screen_mut(id) {
+                screen.set_cloned(false)?;
+            }
+        }
+
+        let source = match source {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+
+        self.get_screen_mut(id).ok_or(ErrorKind::SCREEN_NOT_FOUND)?.set_cloned(true)?;
+        self.clones.push((id, source));
+
+        if let Err(err) = self.update_clones(source) {
+            self.clones.retain(|(screen, _)| *screen != id);
+            if let Some(screen) = self.get_screen_mut(id) {
+                screen.set_cloned(false).unwrap_or_default();
+            }
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Hands the screen `source` the clones it drives, a source that went away has none to drive.
+    fn update_clones(&mut self, source: u32) -> Result<(), ErrorKind> {
+        let clones: Vec<u32> = self.clones.iter().filter(|(_, x)| *x == source).map(|(screen, _)| *screen).collect();
+
+        match self.get_screen_mut(source) {
+            Some(screen) => screen.set_clones(&clones),
+            None => Ok(()),
+        }
+    }
+
+    /// Undoes every clone the screen `id` drives or is driven as.
+    fn release_clones(&mut self, id: u32) {
+        let screens: Vec<u32> = self.clones.iter()
+            .filter(|(screen, source)| *screen == id || *source == id)
+            .map(|(screen, _)| *screen)
+            .collect();
+
+        for screen in screens {
+            if let Err(err) = self.set_clone(screen, None) {
+                error!("Failed to release clone. - ScreenID: {} - ErrorKind: {:?}", screen, err);
+                self.clones.retain(|(x, _)| *x != screen);
+            }
+        }
+    }
+
+    /// Returns `true` when the screen `id` was unplugged and did not come back.
+    pub fn is_disconnected(&self, id: u32) -> bool {
+        self.disconnected.contains(&id)
+    }
+
+    /// Switches several screens to new modes as a unit, given `(screen, mode)` pairs.
+    /// 
+    /// The buffers of every mode are allocated first, then the modesets are
+    /// validated together before any is applied when the device supports it.
+    /// The screens keep their buffers until the new modes are shown, on
+    /// failure they go back to their previous modes and pictures.
+    pub fn set_modes(&mut self, modes: &[(u32, u32)]) -> Result<Vec<Mode>, ErrorKind> {
+        let mut prepared = Vec::with_capacity(modes.len());
+        for (screen, mode) in modes {
+            let screen = self.get_screen(*screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?;
+            prepared.push((screen.id(), screen.prepare_mode(*mode)?));
+        }
+
+        self.device.begin()?;
+
+        let mut staged = Vec::with_capacity(prepared.len());
+        let result = self.stage_modes(&prepared, &mut staged).and_then(|_| self.device.commit());
+        if let Err(err) = result {
+            error!("Failed to set modes, restoring the previous ones. - GPUID: {} - ErrorKind: {:?}", self.id(), err);
+            self.device.abort();
+
+            for screen in staged {
+                if let Some(screen) = self.get_screen_mut(screen) {
+                    screen.cancel_mode();
+                }
+            }
+
+            return Err(err);
+        }
+
+        let mut applied = Vec::with_capacity(prepared.len());
+        for (screen, prepared) in prepared {
+            let screen = self.get_screen_mut(screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?;
+            applied.push(screen.finish_mode(prepared));
+        }
+
+        Ok(applied)
+    }
+
+    /// Stages the prepared modes, recording in `staged` the screens to roll back on failure.
+    fn stage_modes(&mut self, prepared: &[(u32, PreparedMode)], staged: &mut Vec<u32>) -> Result<(), ErrorKind> {
+        for (screen, mode) in prepared {
+            let screen = self.get_screen_mut(*screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?;
+            staged.push(screen.id());
+            screen.stage_mode(mode)?;
+        }
+
+        Ok(())
     }
 
     pub fn get_screen(&self, id: u32) -> Option<&Screen> {
@@ -168,7 +336,7 @@ impl GPU {
     }
 
     pub fn model(&self) -> u32 {
-        self.model
+        self.device.model()
     }
 
     pub(crate) fn dispose(&mut self) {
@@ -182,4 +350,126 @@ impl Drop for GPU {
     fn drop(&mut self) {
         self.dispose();
     }
-}
\ No newline at end of file
+}
+
+
+/// A DRM card, with GBM allocating its buffers.
+#[derive(Debug)]
+pub struct DrmDevice {
+    kms:        Rc<Kms>,
+    device:     DeviceRef,
+    card:       File,
+    vendor:     Vendor,
+    model:      u32,
+}
+
+impl DrmDevice {
+    
+    pub fn load(path: &str) -> Result<Self, ErrorKind> {
+        info!("Loading gpu: \"{}\"", path);
+
+        let card = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_RDWR | libc::O_CLOEXEC | libc::O_NONBLOCK)
+            .open(path);
+
+        if let Err(_) = card {
+            let err = ErrorKind::GPU_LOAD_FAILED;
+            error!("Failed to load gpu: \"{}\" - ErrorKind: {:?}", path, err);
+            return Err(err);
+        }
+
+        let card = card.unwrap();
+        let gpu = card.as_raw_fd();
+        let vendor = unsafe { Self::get_card_vendor(gpu) };
+        let vendor = Vendor::from(vendor);
+        let model = unsafe { Self::get_card_model(gpu) };
+        let device = Device::new(gpu)?;
+        let kms = Rc::new(Kms::new(gpu));
+
+        Ok(DrmDevice {
+            kms,
+            device,
+            card,
+            vendor,
+            model,
+        })
+    }
+
+    unsafe fn get_card_vendor(gpu: GPUID) -> u16 {
+        let mut device_ptr = std::ptr::null_mut();
+        drmGetDevice(gpu, &mut device_ptr);
+
+        let drm_device = device_ptr.as_ref().unwrap() ;
+        let vendor_id = drm_device.deviceinfo.pci.as_ref().unwrap().vendor_id;
+
+        drmFreeDevice(&mut device_ptr);
+        return vendor_id;
+    }
+
+    unsafe fn get_card_model(gpu: GPUID) -> u32 {
+        let mut device_ptr = std::ptr::null_mut();
+        drmGetDevice(gpu, &mut device_ptr);
+
+        let drm_device = device_ptr.as_ref().unwrap() ;
+        let device_id = drm_device.deviceinfo.pci.as_ref().unwrap().device_id;
+
+        drmFreeDevice(&mut device_ptr);
+        return device_id as u32;
+    }
+}
+
+impl GraphicsDevice for DrmDevice {
+    fn id(&self) -> GPUID {
+        self.card.as_raw_fd()
+    }
+
+    fn vendor(&self) -> Vendor {
+        self.vendor
+    }
+
+    fn model(&self) -> u32 {
+        self.model
+    }
+
+    fn allocator(&self) -> Arc<dyn BufferAllocator> {
+        self.device.clone()
+    }
+
+    fn outputs(&mut self, flags: &[ScreenFlags]) -> Result<Vec<Box<dyn Output>>, ErrorKind> {
+        debug!("Getting gpu resources...");
+        let resources_ptr: *mut drm::_drmModeRes = unsafe { drmModeGetResources(self.id()) };
+        if resources_ptr.is_null() {
+            let err = ErrorKind::GPU_RESOURCES_FAILED;
+            error!("Failed to get gpu resources. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        let resources = unsafe { resources_ptr.as_ref().unwrap() };
+        let outputs = Screen::enumerate_outputs(&self.device, &self.kms, resources, flags);
+
+        unsafe { drmModeFreeResources(resources_ptr) };
+        outputs
+    }
+
+    fn event_fd(&self) -> Option<RawFd> {
+        Some(self.card.as_raw_fd())
+    }
+
+    fn dispatch_events(&mut self) -> Result<(), ErrorKind> {
+        screen::handle_events(self.id())
+    }
+
+    fn begin(&mut self) -> Result<(), ErrorKind> {
+        self.kms.begin()
+    }
+
+    fn commit(&mut self) -> Result<(), ErrorKind> {
+        self.kms.commit()
+    }
+
+    fn abort(&mut self) {
+        self.kms.abort()
+    }
+}
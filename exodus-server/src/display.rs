@@ -1,19 +1,35 @@
-use exodus_common::{consts::{EXODUS_DIRECTORY, EXODUS_LOG}, logger, info, net::connection::Connection, error, debug, memory::Allocator};
+use exodus_common::{consts::{EXODUS_DIRECTORY, EXODUS_IDLE_TIMEOUT, EXODUS_LAYOUT, EXODUS_LOG}, enums::{PowerState, SurfaceTransform}, logger, info, net::{connection::Connection, network_message::NetworkMessage}, error, debug, memory::Allocator};
 use exodus_errors::ErrorKind;
-use std::{os::unix::net::UnixListener, path};
-use crate::{client::Entity, device::GPU};
+use std::{os::{fd::{AsRawFd, RawFd}, unix::net::UnixListener}, path::{self, PathBuf}, time::{Duration, Instant}};
+use exodus_protocols::{protocol_code::EXTENSION_DMABUF, messages::{LayoutChangedEvent, PowerStateChangedEvent, ScreenAddedEvent, ScreenChangedEvent, ScreenRemovedEvent}};
+use crate::{backend::Backend, client::Entity, device::{GPU, ScreenChange}, layout::{Head, Layout, Region}};
 
+/// Screens are powered off after `idle_timeout` without activity, see [`Display::activity`].
+/// 
+/// The screens of every GPU share one desktop coordinate space, see [`Layout`].
 #[derive(Debug)]
 pub struct Display {
-    id:         i32,
-    listener:   UnixListener,
-    gpus:       Vec<GPU>,
-    allocator:  Allocator,
+    id:             i32,
+    listener:       UnixListener,
+    gpus:           Vec<GPU>,
+    allocator:      Allocator,
+    events:         Vec<(u32, NetworkMessage)>,
+    idle_timeout:   Option<Duration>,
+    active:         Instant,
+    /// Screens powered off by the idle timeout, as `(gpu, screen)`, `None` while not idle.
+    blanked:        Option<Vec<(i32, u32)>>,
+    layout:         Layout,
 }
 
 impl Display {
 
+    /// Creates a display on the backend selected by [`Backend::from_env`].
     pub fn new(cache: usize) -> Result<Self, ErrorKind> {
+        Self::with_backend(cache, None)
+    }
+
+    /// Creates a display on the given backend, or the one selected by the environment when `None`.
+    pub fn with_backend(cache: usize, backend: Option<Backend>) -> Result<Self, ErrorKind> {
         if !path::Path::new(EXODUS_DIRECTORY).exists() {
             std::fs::create_dir_all(EXODUS_DIRECTORY).unwrap();
         }
@@ -36,10 +52,34 @@ impl Display {
         info!("Initializing display...");
         let listener: UnixListener = Self::create_display_listener(&dpy)?;
 
-        let gpus = GPU::enumerate_gpus()?;
+        // Virtual screens only keep their layout when asked to.
+        let (gpus, layout) = match backend.unwrap_or_else(Backend::from_env) {
+            Backend::Drm => (GPU::enumerate_gpus()?, Layout::default_path()),
+            Backend::Headless(modes) => (vec![GPU::headless(0, &modes)?], std::env::var(EXODUS_LAYOUT).ok().map(PathBuf::from)),
+        };
+
+        // Seconds, `0` or unset leaves the screens on.
+        let idle_timeout = std::env::var(EXODUS_IDLE_TIMEOUT).ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
 
         info!("Display initialized successfully.");
-        Ok(Self { id, listener, allocator: Allocator::with_capacity(cache), gpus })
+        let mut display = Self {
+            id,
+            listener,
+            allocator: Allocator::with_capacity(cache),
+            gpus,
+            events: Vec::new(),
+            idle_timeout,
+            active: Instant::now(),
+            blanked: None,
+            layout: Layout::new(layout),
+        };
+
+        display.update_layout();
+        display.apply_mirrors();
+        Ok(display)
     }
 
     pub fn accept(&self) -> Option<Entity> {
@@ -56,6 +96,17 @@ impl Display {
         self.id
     }
 
+    /// Names of the optional protocol extensions this display supports.
+    pub fn capabilities(&self) -> Vec<&'static str> {
+        let mut capabilities = Vec::new();
+
+        if self.gpus.iter().any(|gpu| gpu.device().allocator().can_import()) {
+            capabilities.push(EXTENSION_DMABUF);
+        }
+
+        capabilities
+    }
+
     fn create_display_listener(path: &str) -> Result<UnixListener, ErrorKind> {
         if let Ok(listener) = UnixListener::bind(path) {
             listener.set_nonblocking(true).unwrap();
@@ -106,9 +157,248 @@ impl Display {
     pub fn gpus_mut(&mut self) -> &mut Vec<GPU> {
         &mut self.gpus
     }
+
+    /// Probes the outputs of every GPU again and queues the screen events, then the layout event, for the entities.
+    /// 
+    /// Returns the changes, as `(gpu, change)` pairs.
+    pub fn hotplug(&mut self) -> Vec<(i32, ScreenChange)> {
+        let mut changes = Vec::new();
+
+        for gpu in self.gpus.iter_mut() {
+            match gpu.rescan() {
+                Ok(gpu_changes) => changes.extend(gpu_changes.into_iter().map(|change| (gpu.id(), change))),
+                Err(err) => error!("Failed to probe screens. - GPUID: {} - ErrorKind: {:?}", gpu.id(), err),
+            }
+        }
+
+        for (gpu, change) in changes.iter().copied() {
+            let event = match change {
+                ScreenChange::Added(screen) => NetworkMessage::encode_event(0, &ScreenAddedEvent { gpu, screen }),
+                ScreenChange::Removed(screen) => NetworkMessage::encode_event(0, &ScreenRemovedEvent { gpu, screen }),
+                ScreenChange::Changed(screen) => NetworkMessage::encode_event(0, &ScreenChangedEvent { gpu, screen }),
+            };
+            self.broadcast(0, event);
+        }
+
+        // The layout event follows the screen events, so entities know the screens it places.
+        if !changes.is_empty() {
+            self.update_layout();
+            self.apply_mirrors();
+            self.broadcast(0, NetworkMessage::encode_event(0, &LayoutChangedEvent {}));
+        }
+
+        changes
+    }
+
+    /// The part of the desktop each connected screen shows, at the current size of the screens.
+    pub fn layout(&self) -> Vec<Region> {
+        self.layout.regions(&self.heads())
+    }
+
+    /// Moves a screen to `x`, `y` of the desktop at `scale` and queues a layout event for the entities except `origin`.
+    pub fn set_screen_position(&mut self, origin: u32, gpu: i32, screen: u32, x: i32, y: i32, scale: f32) -> Result<(), ErrorKind> {
+        self.update_layout();
+        self.layout.set_position(gpu, screen, x, y, scale)?;
+        self.broadcast(origin, NetworkMessage::encode_event(0, &LayoutChangedEvent {}));
+        Ok(())
+    }
+
+    /// Makes a screen mirror `source`, a screen of the same GPU whose encoder can be cloned with its own.
+    /// 
+    /// The connector of the screen is driven by the CRTC of `source` and the
+    /// screen takes its desktop position. `None` gives the screen its own CRTC
+    /// and position again. Queues a layout event for the entities except `origin`.
+    pub fn set_mirror(&mut self, origin: u32, gpu: i32, screen: u32, source: Option<(i32, u32)>) -> Result<(), ErrorKind> {
+        if let Some(source) = source {
+            self.check_mirror(gpu, screen, source)?;
+        }
+
+        self.update_layout();
+        let previous = self.layout.get(gpu, screen).and_then(|region| region.mirror);
+        self.layout.set_mirror(gpu, screen, source)?;
+
+        let result = self.get_gpu_mut(gpu).ok_or(ErrorKind::GPU_NOT_FOUND)
+            .and_then(|device| device.set_clone(screen, source.map(|(_, source)| source)));
+
+        if let Err(err) = result {
+            self.layout.set_mirror(gpu, screen, previous).unwrap_or_default();
+            return Err(err);
+        }
+
+        self.broadcast(origin, NetworkMessage::encode_event(0, &LayoutChangedEvent {}));
+        Ok(())
+    }
+
+    /// Checks that the encoder of `source` can drive the screen as a clone.
+    fn check_mirror(&self, gpu: i32, screen: u32, source: (i32, u32)) -> Result<(), ErrorKind> {
+        let (source_gpu, source_screen) = source;
+        let source = self.get_gpu(source_gpu).and_then(|x| x.get_screen(source_screen)).ok_or(ErrorKind::SCREEN_NOT_FOUND)?;
+        let target = self.get_gpu(gpu).ok_or(ErrorKind::GPU_NOT_FOUND)?.get_screen(screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?;
+
+        if source_gpu != gpu {
+            let err = ErrorKind::LAYOUT_INVALID;
+            error!("Mirrored screens must share a GPU. - GPUID: {} - ScreenID: {} - SourceGPUID: {} - ErrorKind: {:?}", gpu, screen, source_gpu, err);
+            return Err(err);
+        }
+
+        if !source.can_clone(target) {
+            let err = ErrorKind::LAYOUT_INVALID;
+            error!("Encoders cannot be cloned. - GPUID: {} - ScreenID: {} - Source: {} - ErrorKind: {:?}", gpu, screen, source_screen, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Drives the mirrors of the layout as clones once the screens changed, dropping those that cannot be driven.
+    fn apply_mirrors(&mut self) {
+        for region in self.layout() {
+            let result = match region.mirror {
+                Some(source) => self.check_mirror(region.gpu, region.screen, source),
+                None => Ok(()),
+            };
+
+            let result = result.and_then(|_| self.get_gpu_mut(region.gpu).ok_or(ErrorKind::GPU_NOT_FOUND)?
+                .set_clone(region.screen, region.mirror.map(|(_, source)| source)));
+
+            if let Err(err) = result {
+                error!("Failed to mirror screen, it shows its own picture. - GPUID: {} - ScreenID: {} - ErrorKind: {:?}", region.gpu, region.screen, err);
+                self.layout.set_mirror(region.gpu, region.screen, None).unwrap_or_default();
+            }
+        }
+    }
+
+    /// Rotates or flips a screen and queues a layout event for the entities except `origin`.
+    /// 
+    /// Returns whether the hardware turns the picture, see [`crate::screen::Screen::set_transform`].
+    pub fn set_transform(&mut self, origin: u32, gpu: i32, screen: u32, transform: SurfaceTransform) -> Result<bool, ErrorKind> {
+        let hardware = self.get_gpu_mut(gpu).ok_or(ErrorKind::GPU_NOT_FOUND)?
+            .get_screen_mut(screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?
+            .set_transform(transform)?;
+
+        self.update_layout();
+        self.broadcast(origin, NetworkMessage::encode_event(0, &LayoutChangedEvent {}));
+        Ok(hardware)
+    }
+
+    /// Hands the connected screens and their current size to the layout.
+    fn update_layout(&mut self) {
+        let heads = self.heads();
+        self.layout.update(heads);
+    }
+
+    /// The connected screens and their current size.
+    fn heads(&self) -> Vec<Head> {
+        self.gpus.iter().flat_map(|gpu| gpu.screens().iter().map(move |screen| Head {
+            gpu: gpu.id(),
+            screen: screen.id(),
+            identity: match screen.edid() {
+                Some(edid) => edid.identity(),
+                None => format!("{:?}-{}-{}", screen.connector_type(), gpu.id(), screen.id()),
+            },
+            width: screen.width(),
+            height: screen.height(),
+        })).collect()
+    }
+
+    /// Sets the power state of a screen and queues a power event for the entities except `origin`.
+    pub fn set_power_state(&mut self, origin: u32, gpu: i32, screen: u32, state: PowerState) -> Result<(), ErrorKind> {
+        let target = self.get_gpu_mut(gpu).ok_or(ErrorKind::GPU_NOT_FOUND)?
+            .get_screen_mut(screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?;
+
+        if target.power_state() == state {
+            return Ok(());
+        }
+
+        target.set_power_state(state)?;
+
+        // A state set explicitly is not undone by the end of the idle period.
+        if let Some(blanked) = self.blanked.as_mut() {
+            blanked.retain(|x| *x != (gpu, screen));
+        }
+
+        let event = PowerStateChangedEvent { gpu, screen, state: state as u32 };
+        self.broadcast(origin, NetworkMessage::encode_event(0, &event));
+        Ok(())
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// Sets how long the display waits without activity before powering the screens off, `None` never does.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        info!("Idle timeout set. - Timeout: {:?}", timeout);
+        self.idle_timeout = timeout;
+        self.activity();
+    }
+
+    /// Restarts the idle period, powering on the screens the idle timeout powered off.
+    pub fn activity(&mut self) {
+        self.active = Instant::now();
+
+        for (gpu, screen) in self.blanked.take().unwrap_or_default() {
+            if let Err(err) = self.set_power_state(0, gpu, screen, PowerState::On) {
+                error!("Failed to wake screen. - GPUID: {} - ScreenID: {} - ErrorKind: {:?}", gpu, screen, err);
+            }
+        }
+    }
+
+    /// Time left before the screens are powered off, `None` when there is no timeout or they already are.
+    pub fn idle_remaining(&self) -> Option<Duration> {
+        match self.blanked {
+            Some(_) => None,
+            None => self.idle_timeout.map(|timeout| timeout.saturating_sub(self.active.elapsed())),
+        }
+    }
+
+    /// Powers off the screens that are on once the idle timeout elapsed.
+    /// 
+    /// Returns whether the display became idle.
+    pub fn idle(&mut self) -> bool {
+        if self.idle_remaining() != Some(Duration::ZERO) {
+            return false;
+        }
+
+        let screens: Vec<(i32, u32)> = self.gpus.iter()
+            .flat_map(|gpu| gpu.screens().iter()
+                .filter(|screen| screen.power_state() == PowerState::On)
+                .map(move |screen| (gpu.id(), screen.id())))
+            .collect();
+
+        info!("Display idle, powering off {} screens.", screens.len());
+        self.blanked = Some(Vec::new());
+
+        for (gpu, screen) in screens {
+            match self.set_power_state(0, gpu, screen, PowerState::Off) {
+                Ok(()) => self.blanked.get_or_insert_with(Vec::new).push((gpu, screen)),
+                Err(err) => error!("Failed to power off screen. - GPUID: {} - ScreenID: {} - ErrorKind: {:?}", gpu, screen, err),
+            }
+        }
+
+        true
+    }
+
+    /// Queues an event for every registered entity except `origin`, the entity that caused it.
+    /// 
+    /// Events raised by the display itself use origin `0`, no entity has that id.
+    pub fn broadcast(&mut self, origin: u32, event: NetworkMessage) {
+        self.events.push((origin, event));
+    }
+
+    /// Takes the events queued by `broadcast`.
+    pub(crate) fn take_events(&mut self) -> Vec<(u32, NetworkMessage)> {
+        std::mem::take(&mut self.events)
+    }
     
 }
 
+impl AsRawFd for Display {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
 impl Drop for Display {
     fn drop(&mut self) {
         self.dispose();
@@ -0,0 +1,151 @@
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+use exodus_errors::ErrorKind;
+
+/// Readiness reported for a registered file descriptor.
+#[derive(Debug, Clone, Copy)]
+pub struct Event {
+    pub token: u64,
+    pub readable: bool,
+    pub writable: bool,
+    pub hangup: bool,
+}
+
+/// Thin wrapper around epoll.
+#[derive(Debug)]
+pub struct EventLoop {
+    fd: OwnedFd,
+    events: Vec<libc::epoll_event>,
+}
+
+impl EventLoop {
+    pub fn new(capacity: usize) -> Result<Self, ErrorKind> {
+        let fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if fd < 0 {
+            return Err(ErrorKind::EVENT_LOOP_FAILED);
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            events: vec![libc::epoll_event { events: 0, u64: 0 }; capacity.max(1)],
+        })
+    }
+
+    /// Watches `fd` for input, and for output too when `writable` is set.
+    pub fn add(&self, fd: RawFd, token: u64, writable: bool) -> Result<(), ErrorKind> {
+        self.control(libc::EPOLL_CTL_ADD, fd, token, writable)
+    }
+
+    /// Changes the interest of an already watched `fd`.
+    pub fn modify(&self, fd: RawFd, token: u64, writable: bool) -> Result<(), ErrorKind> {
+        self.control(libc::EPOLL_CTL_MOD, fd, token, writable)
+    }
+
+    pub fn remove(&self, fd: RawFd) -> Result<(), ErrorKind> {
+        let result = unsafe { libc::epoll_ctl(self.fd.as_raw_fd(), libc::EPOLL_CTL_DEL, fd, std::ptr::null_mut()) };
+        if result < 0 {
+            return Err(ErrorKind::EVENT_LOOP_FAILED);
+        }
+
+        Ok(())
+    }
+
+    fn control(&self, op: i32, fd: RawFd, token: u64, writable: bool) -> Result<(), ErrorKind> {
+        let mut interest = (libc::EPOLLIN | libc::EPOLLRDHUP) as u32;
+        if writable {
+            interest |= libc::EPOLLOUT as u32;
+        }
+
+        let mut event = libc::epoll_event { events: interest, u64: token };
+        let result = unsafe { libc::epoll_ctl(self.fd.as_raw_fd(), op, fd, &mut event) };
+        if result < 0 {
+            return Err(ErrorKind::EVENT_LOOP_FAILED);
+        }
+
+        Ok(())
+    }
+
+    /// Waits for events, forever when `timeout` is `None`.
+    /// 
+    /// An interrupted wait returns no events.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<Vec<Event>, ErrorKind> {
+        let timeout = timeout.map(|x| x.as_millis().min(i32::MAX as u128) as i32).unwrap_or(-1);
+        let count = unsafe { libc::epoll_wait(self.fd.as_raw_fd(), self.events.as_mut_ptr(), self.events.len() as i32, timeout) };
+
+        if count < 0 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(ErrorKind::EVENT_LOOP_FAILED);
+        }
+
+        let events = self.events[..count as usize].iter().map(|event| {
+            let flags = event.events;
+            Event {
+                token: event.u64,
+                readable: flags & libc::EPOLLIN as u32 != 0,
+                writable: flags & libc::EPOLLOUT as u32 != 0,
+                hangup: flags & (libc::EPOLLHUP | libc::EPOLLRDHUP | libc::EPOLLERR) as u32 != 0,
+            }
+        }).collect();
+
+        Ok(events)
+    }
+}
+
+impl AsRawFd for EventLoop {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Delivers signals as readable events instead of asynchronous handlers.
+/// 
+/// The signals are blocked for the calling thread, so it must be created
+/// before other threads are spawned.
+#[derive(Debug)]
+pub struct SignalFd {
+    fd: OwnedFd,
+}
+
+impl SignalFd {
+    pub fn new(signals: &[i32]) -> Result<Self, ErrorKind> {
+        unsafe {
+            let mut mask: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut mask);
+            for signal in signals {
+                libc::sigaddset(&mut mask, *signal);
+            }
+
+            if libc::pthread_sigmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut()) != 0 {
+                return Err(ErrorKind::SIGNAL_HANDLER_FAILED);
+            }
+
+            let fd = libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC);
+            if fd < 0 {
+                return Err(ErrorKind::SIGNAL_HANDLER_FAILED);
+            }
+
+            Ok(Self { fd: OwnedFd::from_raw_fd(fd) })
+        }
+    }
+
+    /// Returns the next pending signal, if any.
+    pub fn read(&self) -> Option<i32> {
+        let mut info: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::signalfd_siginfo>();
+        let read = unsafe { libc::read(self.fd.as_raw_fd(), &mut info as *mut _ as *mut libc::c_void, size) };
+
+        if read as usize != size {
+            return None;
+        }
+
+        Some(info.ssi_signo as i32)
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
@@ -1,7 +1,7 @@
-use drm::{drmModeAddFB, drmModeRmFB};
+use drm::{DRM_MODE_FB_MODIFIERS, drmModeAddFB2WithModifiers, drmModeRmFB};
 use exodus_errors::ErrorKind;
 
-use exodus_common::{*, graphics::{device::GPUID, buffer::Buffer}};
+use exodus_common::{*, enums::PixelFormat, graphics::{device::GPUID, buffer::{Buffer, DRM_FORMAT_MOD_INVALID, MAX_DMABUF_PLANES}}};
 
 
 /// Framebuffer is a wrapper around a DRM framebuffer.
@@ -15,19 +15,42 @@ pub struct Framebuffer {
 
 impl Framebuffer {
 
-    /// Creates a new framebuffer.
+    /// Creates a new framebuffer, with every plane of the buffer and its format modifier.
     pub fn new(gpu: GPUID, buffer: &Buffer) -> Result<Self, ErrorKind> {
-        debug!("Creating framebuffer. - GPUID: {} - Buffer: {:?}", gpu, buffer);
+        Self::create(gpu, buffer, buffer.format())
+    }
+
+    /// Creates a framebuffer ignoring the alpha of the buffer, primary planes often cannot blend.
+    pub fn opaque(gpu: GPUID, buffer: &Buffer) -> Result<Self, ErrorKind> {
+        Self::create(gpu, buffer, buffer.format().opaque())
+    }
+
+    fn create(gpu: GPUID, buffer: &Buffer, format: PixelFormat) -> Result<Self, ErrorKind> {
+        debug!("Creating framebuffer. - GPUID: {} - Format: {:?} - Buffer: {:?}", gpu, format, buffer);
         unsafe {
             let mut id = 0;
-            let width = buffer.width();
-            let height = buffer.height();
-            let depth = 24;
-            let bpp = buffer.bpp() as u8;
-            let pitch = buffer.stride();
-            let handle = buffer.handle();
-            
-            if drmModeAddFB(gpu, width, height, depth, bpp, pitch, handle, &mut id) != 0 {
+            let mut handles = [0u32; MAX_DMABUF_PLANES];
+            let mut pitches = [0u32; MAX_DMABUF_PLANES];
+            let mut offsets = [0u32; MAX_DMABUF_PLANES];
+            let mut modifiers = [0u64; MAX_DMABUF_PLANES];
+
+            for (index, plane) in buffer.layout().iter().enumerate().take(MAX_DMABUF_PLANES) {
+                handles[index] = plane.handle;
+                pitches[index] = plane.stride;
+                offsets[index] = plane.offset;
+                modifiers[index] = buffer.modifier();
+            }
+
+            // Without a modifier the driver infers the layout, as with `drmModeAddFB`.
+            let flags = match buffer.modifier() {
+                DRM_FORMAT_MOD_INVALID => 0,
+                _ => DRM_MODE_FB_MODIFIERS,
+            };
+
+            let result = drmModeAddFB2WithModifiers(gpu, buffer.width(), buffer.height(), format.fourcc(),
+                handles.as_ptr(), pitches.as_ptr(), offsets.as_ptr(), modifiers.as_ptr(), &mut id, flags);
+
+            if result != 0 {
                 error!("Failed to create framebuffer. - ErrorKind: {:?}", ErrorKind::FRAMEBUFFER_CREATE_FAILED);
                 return Err(ErrorKind::FRAMEBUFFER_CREATE_FAILED);
             }
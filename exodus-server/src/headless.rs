@@ -0,0 +1,218 @@
+use std::sync::Arc;
+use exodus_common::{graphics::{backend::{BufferAllocator, GraphicsDevice, MemoryAllocator, Mode, Output, Presenter}, buffer::Buffer, device::GPUID, gamma::GammaRamp}, enums::*};
+use exodus_errors::ErrorKind;
+use crate::backend::HeadlessMode;
+
+/// A software GPU with one virtual output per mode.
+#[derive(Debug)]
+pub struct HeadlessDevice {
+    id:     GPUID,
+    modes:  Vec<HeadlessMode>,
+}
+
+impl HeadlessDevice {
+    pub fn new(id: GPUID, modes: &[HeadlessMode]) -> Self {
+        Self { id, modes: modes.to_vec() }
+    }
+}
+
+impl GraphicsDevice for HeadlessDevice {
+    fn id(&self) -> GPUID {
+        self.id
+    }
+
+    fn vendor(&self) -> Vendor {
+        Vendor::Unknown
+    }
+
+    fn model(&self) -> u32 {
+        0
+    }
+
+    fn allocator(&self) -> Arc<dyn BufferAllocator> {
+        Arc::new(MemoryAllocator)
+    }
+
+    fn outputs(&mut self, _flags: &[ScreenFlags]) -> Result<Vec<Box<dyn Output>>, ErrorKind> {
+        Ok(self.modes.iter().enumerate()
+            .map(|(index, mode)| Box::new(HeadlessOutput::new(index as u32 + 1, *mode)) as Box<dyn Output>)
+            .collect())
+    }
+}
+
+/// Entries of the gamma ramp of virtual outputs, as for 8 bit channels.
+const HEADLESS_GAMMA_SIZE: usize = 256;
+
+/// Common resolutions offered besides the configured one.
+const HEADLESS_FALLBACK_MODES: [(u32, u32); 4] = [(1280, 720), (1024, 768), (800, 600), (640, 480)];
+
+/// A virtual output, presenting only means the buffer becomes the front buffer.
+/// 
+/// The configured mode is the preferred one, the common resolutions smaller than
+/// it are offered as alternatives. The gamma ramp and power state are only kept, not applied.
+#[derive(Debug)]
+pub struct HeadlessOutput {
+    id:         u32,
+    modes:      Vec<HeadlessMode>,
+    mode:       u32,
+    displayed:  Option<usize>,
+    gamma:      GammaRamp,
+    power:      PowerState,
+    /// Mode and displayed buffer kept by `checkpoint`.
+    saved:      Option<(u32, Option<usize>)>,
+}
+
+impl HeadlessOutput {
+    pub fn new(id: u32, mode: HeadlessMode) -> Self {
+        let mut modes = vec![mode];
+        for (width, height) in HEADLESS_FALLBACK_MODES {
+            if width < mode.width && height < mode.height {
+                modes.push(HeadlessMode::new(width, height, mode.refresh));
+            }
+        }
+
+        Self { id, modes, mode: 0, displayed: None, gamma: GammaRamp::linear(HEADLESS_GAMMA_SIZE), power: PowerState::On, saved: None }
+    }
+
+    fn current(&self) -> &HeadlessMode {
+        &self.modes[self.mode as usize]
+    }
+}
+
+impl Presenter for HeadlessOutput {
+    fn attach(&mut self, _buffers: &[Buffer]) -> Result<(), ErrorKind> {
+        self.displayed = None;
+        Ok(())
+    }
+
+    fn present(&mut self, index: usize) -> Result<(), ErrorKind> {
+        self.displayed = Some(index);
+        Ok(())
+    }
+
+    fn displayed(&self) -> Option<usize> {
+        self.displayed
+    }
+
+    fn restore(&mut self) {
+        self.gamma = GammaRamp::linear(HEADLESS_GAMMA_SIZE);
+        self.power = PowerState::On;
+    }
+}
+
+impl Output for HeadlessOutput {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn connector_type(&self) -> ConnectorType {
+        ConnectorType::VIRTUAL
+    }
+
+    fn mm_width(&self) -> u32 {
+        0
+    }
+
+    fn mm_height(&self) -> u32 {
+        0
+    }
+
+    fn subpixel(&self) -> u32 {
+        SubPixel::None as u32
+    }
+
+    fn width(&self) -> u32 {
+        self.current().width
+    }
+
+    fn height(&self) -> u32 {
+        self.current().height
+    }
+
+    fn refresh(&self) -> u32 {
+        self.current().refresh
+    }
+
+    fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    fn modes(&self) -> Vec<Mode> {
+        self.modes.iter().enumerate().map(|(index, mode)| Mode {
+            width: mode.width,
+            height: mode.height,
+            refresh: mode.refresh,
+            preferred: index == 0,
+            interlaced: false,
+        }).collect()
+    }
+
+    fn gamma_size(&self) -> usize {
+        HEADLESS_GAMMA_SIZE
+    }
+
+    fn gamma(&self) -> Result<GammaRamp, ErrorKind> {
+        Ok(self.gamma.clone())
+    }
+
+    fn set_gamma(&mut self, ramp: &GammaRamp) -> Result<(), ErrorKind> {
+        if ramp.size() != HEADLESS_GAMMA_SIZE {
+            return Err(ErrorKind::GAMMA_FAILED);
+        }
+
+        self.gamma = ramp.clone();
+        Ok(())
+    }
+
+    fn power_state(&self) -> PowerState {
+        self.power
+    }
+
+    fn encoder_mask(&self) -> u32 {
+        1 << (self.id % 32)
+    }
+
+    /// Virtual outputs can all show the same picture.
+    fn possible_clones(&self) -> u32 {
+        u32::MAX
+    }
+
+    fn set_clones(&mut self, _clones: &[u32]) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    fn set_cloned(&mut self, _cloned: bool) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+
+    fn set_power_state(&mut self, state: PowerState) -> Result<(), ErrorKind> {
+        self.power = state;
+        Ok(())
+    }
+
+    fn set_mode(&mut self, index: u32) -> Result<(), ErrorKind> {
+        if index as usize >= self.modes.len() {
+            return Err(ErrorKind::MODE_NOT_FOUND);
+        }
+
+        self.mode = index;
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> Result<(), ErrorKind> {
+        self.saved = Some((self.mode, self.displayed));
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        self.saved = None;
+    }
+
+    fn rollback(&mut self) -> Result<(), ErrorKind> {
+        if let Some((mode, displayed)) = self.saved.take() {
+            (self.mode, self.displayed) = (mode, displayed);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+use exodus_common::error;
+use exodus_errors::ErrorKind;
+
+/// How often the outputs are probed when kernel uevents cannot be received.
+pub const HOTPLUG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Multicast group of the uevents sent by the kernel, udev rebroadcasts them on another one.
+const UEVENT_KERNEL_GROUP: u32 = 1;
+const UEVENT_BUFFER_SIZE: usize = 8192;
+
+/// Listens to the kernel uevents announcing that a DRM connector changed.
+#[derive(Debug)]
+pub struct HotplugMonitor {
+    fd:     OwnedFd,
+    buffer: Vec<u8>,
+}
+
+impl HotplugMonitor {
+    pub fn new() -> Result<Self, ErrorKind> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, libc::NETLINK_KOBJECT_UEVENT)
+        };
+
+        if fd < 0 {
+            let err = ErrorKind::HOTPLUG_FAILED;
+            error!("Failed to open uevent socket. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as u16;
+        address.nl_groups = UEVENT_KERNEL_GROUP;
+
+        let size = std::mem::size_of::<libc::sockaddr_nl>() as u32;
+        if unsafe { libc::bind(fd.as_raw_fd(), &address as *const _ as *const libc::sockaddr, size) } < 0 {
+            let err = ErrorKind::HOTPLUG_FAILED;
+            error!("Failed to bind uevent socket. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        Ok(Self { fd, buffer: vec![0; UEVENT_BUFFER_SIZE] })
+    }
+
+    /// Reads the queued uevents, returns `true` when one of them is a DRM hotplug.
+    pub fn read(&mut self) -> bool {
+        let mut hotplug = false;
+
+        loop {
+            let read = unsafe { libc::recv(self.fd.as_raw_fd(), self.buffer.as_mut_ptr() as *mut libc::c_void, self.buffer.len(), 0) };
+            if read <= 0 {
+                return hotplug;
+            }
+
+            hotplug |= is_drm_hotplug(&self.buffer[..read as usize]);
+        }
+    }
+}
+
+impl AsRawFd for HotplugMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Parses a uevent, `ACTION@DEVPATH` followed by `KEY=VALUE` fields, all null terminated.
+pub fn is_drm_hotplug(uevent: &[u8]) -> bool {
+    let mut fields = uevent.split(|byte| *byte == 0);
+    let (mut drm, mut hotplug) = (false, false);
+
+    fields.next();
+    for field in fields {
+        match field {
+            b"SUBSYSTEM=drm" => drm = true,
+            b"HOTPLUG=1" => hotplug = true,
+            _ => (),
+        }
+    }
+
+    drm && hotplug
+}
@@ -0,0 +1,229 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+use exodus_common::{consts::EXODUS_LAYOUT, debug, error};
+use exodus_errors::ErrorKind;
+
+/// A connected screen and its monitor, as seen by the layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    pub gpu:        i32,
+    pub screen:     u32,
+    /// Identity of the monitor, see `Edid::identity`, or of the connector when it has no EDID.
+    pub identity:   String,
+    /// Size of the current mode, in pixels.
+    pub width:      u32,
+    pub height:     u32,
+}
+
+/// Where a monitor sits on the desktop, kept across hotplugs and restarts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub x:      i32,
+    pub y:      i32,
+    /// Pixels per desktop unit.
+    pub scale:  f32,
+    /// Identity of the monitor this one mirrors.
+    pub mirror: Option<String>,
+}
+
+/// The part of the desktop a connected screen shows, in desktop units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub gpu:    i32,
+    pub screen: u32,
+    pub x:      i32,
+    pub y:      i32,
+    pub width:  u32,
+    pub height: u32,
+    pub scale:  f32,
+    /// `(gpu, screen)` of the screen this one mirrors, which it shares its position with.
+    pub mirror: Option<(i32, u32)>,
+}
+
+/// Places the screens of every GPU in one desktop coordinate space.
+///
+/// Positions are kept by monitor identity, so a monitor finds its place again
+/// whatever connector or GPU it is plugged in. New monitors are placed right
+/// of the desktop. A screen mirroring a monitor that is not connected is shown
+/// at its own position.
+#[derive(Debug, Default)]
+pub struct Layout {
+    path:       Option<PathBuf>,
+    heads:      Vec<Head>,
+    positions:  HashMap<String, Position>,
+}
+
+impl Layout {
+    /// Loads the positions saved at `path`, `None` keeps the layout in memory.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let positions = path.as_deref().map(Self::load).unwrap_or_default();
+        Self { path, heads: Vec::new(), positions }
+    }
+
+    /// `EXODUS_LAYOUT`, or `exodus/layout` in the configuration directory of the user.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Ok(path) = std::env::var(EXODUS_LAYOUT) {
+            return Some(PathBuf::from(path));
+        }
+
+        let config = std::env::var("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".config")))
+            .ok()?;
+
+        Some(config.join("exodus").join("layout"))
+    }
+
+    /// Tracks the connected screens, placing the monitors without a position right of the desktop.
+    pub fn update(&mut self, heads: Vec<Head>) {
+        self.heads = heads;
+
+        for index in 0..self.heads.len() {
+            if self.positions.contains_key(&self.heads[index].identity) {
+                continue;
+            }
+
+            let x = self.screens().iter().map(|screen| screen.x + screen.width as i32).max().unwrap_or_default();
+            let head = &self.heads[index];
+
+            debug!("Screen placed. - GPUID: {} - ScreenID: {} - Identity: {} - X: {}", head.gpu, head.screen, head.identity, x);
+            self.positions.insert(head.identity.clone(), Position { x, y: 0, scale: 1.0, mirror: None });
+        }
+    }
+
+    /// The connected screens that have a position.
+    pub fn screens(&self) -> Vec<Region> {
+        self.regions(&self.heads)
+    }
+
+    /// Places `heads` at the positions of their monitors, without tracking them.
+    ///
+    /// Heads whose monitor has no position yet are left out, see `update`.
+    pub fn regions(&self, heads: &[Head]) -> Vec<Region> {
+        heads.iter().filter_map(|head| self.place(heads, head)).collect()
+    }
+
+    pub fn get(&self, gpu: i32, screen: u32) -> Option<Region> {
+        self.place(&self.heads, self.head(gpu, screen)?)
+    }
+
+    /// Position of the monitor `identity`, connected or not.
+    pub fn position(&self, identity: &str) -> Option<&Position> {
+        self.positions.get(identity)
+    }
+
+    /// Moves a screen to `x`, `y` of the desktop at `scale` pixels per unit, it stops mirroring.
+    pub fn set_position(&mut self, gpu: i32, screen: u32, x: i32, y: i32, scale: f32) -> Result<(), ErrorKind> {
+        if !scale.is_finite() || scale <= 0.0 {
+            let err = ErrorKind::LAYOUT_INVALID;
+            error!("Invalid screen scale. - GPUID: {} - ScreenID: {} - Scale: {} - ErrorKind: {:?}", gpu, screen, scale, err);
+            return Err(err);
+        }
+
+        let identity = self.head(gpu, screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?.identity.clone();
+        self.positions.insert(identity, Position { x, y, scale, mirror: None });
+        self.save();
+        Ok(())
+    }
+
+    /// Makes a screen show the part of the desktop of `source`, or its own again when `None`.
+    ///
+    /// Mirrors cannot be chained, `source` must not mirror a screen and `screen` must not be mirrored.
+    pub fn set_mirror(&mut self, gpu: i32, screen: u32, source: Option<(i32, u32)>) -> Result<(), ErrorKind> {
+        let identity = self.head(gpu, screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?.identity.clone();
+
+        let mirror = match source {
+            Some((source_gpu, source_screen)) => {
+                let source = self.head(source_gpu, source_screen).ok_or(ErrorKind::SCREEN_NOT_FOUND)?.identity.clone();
+                let chained = source == identity
+                    || self.positions.get(&source).is_some_and(|x| x.mirror.is_some())
+                    || self.positions.values().any(|x| x.mirror.as_ref() == Some(&identity));
+
+                if chained {
+                    let err = ErrorKind::LAYOUT_INVALID;
+                    error!("Mirrors cannot be chained. - GPUID: {} - ScreenID: {} - Source: {} - ErrorKind: {:?}", gpu, screen, source, err);
+                    return Err(err);
+                }
+
+                Some(source)
+            },
+            None => None,
+        };
+
+        if let Some(position) = self.positions.get_mut(&identity) {
+            position.mirror = mirror;
+        }
+
+        self.save();
+        Ok(())
+    }
+
+    fn head(&self, gpu: i32, screen: u32) -> Option<&Head> {
+        self.heads.iter().find(|head| head.gpu == gpu && head.screen == screen)
+    }
+
+    fn place(&self, heads: &[Head], head: &Head) -> Option<Region> {
+        let position = self.positions.get(&head.identity)?;
+        let source = position.mirror.as_ref()
+            .and_then(|identity| heads.iter().find(|x| &x.identity == identity))
+            .and_then(|x| Some((x, self.positions.get(&x.identity)?)));
+
+        let (x, y) = match source {
+            Some((_, source)) => (source.x, source.y),
+            None => (position.x, position.y),
+        };
+
+        Some(Region {
+            gpu: head.gpu,
+            screen: head.screen,
+            x,
+            y,
+            width: (head.width as f32 / position.scale).round() as u32,
+            height: (head.height as f32 / position.scale).round() as u32,
+            scale: position.scale,
+            mirror: source.map(|(source, _)| (source.gpu, source.screen)),
+        })
+    }
+
+    /// Reads `identity x y scale mirror` lines, tab separated, `mirror` is empty when not mirroring.
+    fn load(path: &Path) -> HashMap<String, Position> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return HashMap::new(),
+        };
+
+        content.lines().filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 5 {
+                return None;
+            }
+
+            let position = Position {
+                x: fields[1].parse().ok()?,
+                y: fields[2].parse().ok()?,
+                scale: fields[3].parse().ok().filter(|scale: &f32| scale.is_finite() && *scale > 0.0)?,
+                mirror: Some(fields[4].to_string()).filter(|x| !x.is_empty()),
+            };
+
+            Some((fields[0].to_string(), position))
+        }).collect()
+    }
+
+    fn save(&self) {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut identities: Vec<&String> = self.positions.keys().collect();
+        identities.sort();
+
+        let content: String = identities.into_iter().map(|identity| {
+            let position = &self.positions[identity];
+            format!("{}\t{}\t{}\t{}\t{}\n", identity, position.x, position.y, position.scale, position.mirror.as_deref().unwrap_or_default())
+        }).collect();
+
+        let result = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(path, content));
+        if let Err(err) = result {
+            error!("Failed to save layout. - Path: {} - Error: {}", path.display(), err);
+        }
+    }
+}
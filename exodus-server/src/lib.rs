@@ -3,11 +3,18 @@
 extern crate drm;
 extern crate gbm;
 
+pub mod backend;
 pub mod display;
 pub mod client;
 pub mod device;
+pub mod headless;
 pub mod screen;
 pub mod protocol_handler;
+pub mod event_loop;
+pub mod hotplug;
+pub mod layout;
+pub mod server;
+pub mod shm;
 
 mod framebuffer;
 
@@ -16,7 +23,19 @@ mod framebuffer;
 mod tests {
     use libc::rand;
 
+    use std::{cell::RefCell, rc::Rc, sync::Arc, time::Duration};
+    use exodus_common::{enums::{ConnectorType, PixelFormat, PowerState, ScreenFlags, SurfaceTransform, Vendor}, graphics::{backend::{BufferAllocator, GraphicsDevice, MemoryAllocator, Output, Presenter}, buffer::Buffer, device::GPUID, gamma::GammaRamp}};
+    use exodus_errors::ErrorKind;
+
+    use crate::backend::{Backend, HeadlessMode};
+    use crate::device::{GPU, ScreenChange};
     use crate::display::Display;
+    use crate::hotplug::is_drm_hotplug;
+    use crate::layout::{Head, Layout};
+    use crate::headless::HeadlessOutput;
+    use crate::screen::{Placement, Screen};
+    use crate::shm::{ShmBuffer, ShmPool};
+    use crate::event_loop::EventLoop;
 
 
     #[test]
@@ -62,5 +81,519 @@ mod tests {
         display.dispose();
 
     }
-}
 
+    #[test]
+    fn event_loop_reports_readiness() {
+        use std::{io::Write, os::{fd::AsRawFd, unix::net::UnixStream}, time::Duration};
+
+        let (mut a, b) = UnixStream::pair().unwrap();
+        let mut events = EventLoop::new(8).unwrap();
+        events.add(b.as_raw_fd(), 7, false).unwrap();
+
+        assert!(events.wait(Some(Duration::from_millis(0))).unwrap().is_empty());
+
+        a.write_all(&[1]).unwrap();
+        let ready = events.wait(Some(Duration::from_millis(100))).unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].token, 7);
+        assert!(ready[0].readable);
+
+        drop(a);
+        let ready = events.wait(Some(Duration::from_millis(100))).unwrap();
+        assert!(ready[0].hangup);
+    }
+
+    #[test]
+    fn headless_mode_parse() {
+        assert_eq!("1280x720".parse(), Ok(HeadlessMode::new(1280, 720, 60)));
+        assert_eq!(" 640x480@75".parse(), Ok(HeadlessMode::new(640, 480, 75)));
+        assert!("1280".parse::<HeadlessMode>().is_err());
+        assert!("0x720".parse::<HeadlessMode>().is_err());
+        assert!("1280x720@".parse::<HeadlessMode>().is_err());
+    }
+
+    #[test]
+    fn headless_scanout() {
+        let modes = vec![HeadlessMode::new(64, 48, 60), HeadlessMode::new(32, 32, 30)];
+        let mut display = Display::with_backend(16, Some(Backend::Headless(modes))).unwrap();
+
+        let gpu = display.gpus_mut().first_mut().unwrap();
+        assert_eq!(gpu.screens().len(), 2);
+
+        let screen = gpu.screens_mut().first_mut().unwrap();
+        assert_eq!((screen.width(), screen.height(), screen.refresh()), (64, 48, 60));
+
+        // Nothing is scanned out before the first swap.
+        assert_eq!(screen.scanout().unwrap(), None);
+
+        screen.clear_color(0x00ff0000);
+        screen.swap_buffers().unwrap();
+
+        let pixels = screen.scanout().unwrap().unwrap();
+        assert_eq!(pixels.len(), 64 * 48);
+        assert!(pixels.iter().all(|x| *x == 0x00ff0000));
+
+        // Drawing leaves the scanned out frame as it is until the next swap shows it.
+        screen.clear_color(0x0000ff00);
+        assert!(screen.scanout().unwrap().unwrap().iter().all(|x| *x == 0x00ff0000));
+        screen.swap_buffers().unwrap();
+        assert!(screen.scanout().unwrap().unwrap().iter().all(|x| *x == 0x0000ff00));
+
+        display.dispose();
+    }
+
+    #[derive(Debug)]
+    struct RecordingOutput {
+        output: HeadlessOutput,
+        attached: Rc<RefCell<usize>>,
+        presented: Rc<RefCell<Vec<usize>>>,
+    }
+
+    impl Presenter for RecordingOutput {
+        fn attach(&mut self, buffers: &[Buffer]) -> Result<(), ErrorKind> {
+            *self.attached.borrow_mut() = buffers.len();
+            Ok(())
+        }
+
+        fn present(&mut self, index: usize) -> Result<(), ErrorKind> {
+            self.presented.borrow_mut().push(index);
+            self.output.present(index)
+        }
+
+        fn displayed(&self) -> Option<usize> {
+            self.output.displayed()
+        }
+
+        fn restore(&mut self) {}
+    }
+
+    impl Output for RecordingOutput {
+        fn id(&self) -> u32 { self.output.id() }
+        fn connector_type(&self) -> ConnectorType { self.output.connector_type() }
+        fn mm_width(&self) -> u32 { self.output.mm_width() }
+        fn mm_height(&self) -> u32 { self.output.mm_height() }
+        fn subpixel(&self) -> u32 { self.output.subpixel() }
+        fn width(&self) -> u32 { self.output.width() }
+        fn height(&self) -> u32 { self.output.height() }
+        fn refresh(&self) -> u32 { self.output.refresh() }
+        fn mode(&self) -> u32 { self.output.mode() }
+    }
+
+    #[test]
+    fn swap_buffers_cycles_presented_buffers() {
+        let attached = Rc::new(RefCell::new(0));
+        let presented = Rc::new(RefCell::new(Vec::new()));
+        let output = RecordingOutput {
+            output: HeadlessOutput::new(1, HeadlessMode::new(8, 8, 60)),
+            attached: attached.clone(),
+            presented: presented.clone(),
+        };
+
+        let mut screen = Screen::new(Arc::new(MemoryAllocator), Box::new(output), &[ScreenFlags::DoubleBuffered]).unwrap();
+        assert_eq!(*attached.borrow(), 2);
+
+        for _ in 0..3 {
+            screen.swap_buffers().unwrap();
+        }
+        assert_eq!(*presented.borrow(), vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn screen_can_clone() {
+        let screen = |output: Box<dyn Output>| Screen::new(Arc::new(MemoryAllocator), output, &[ScreenFlags::DoubleBuffered]).unwrap();
+        let first = screen(Box::new(HeadlessOutput::new(1, HeadlessMode::new(8, 8, 60))));
+        let second = screen(Box::new(HeadlessOutput::new(2, HeadlessMode::new(8, 8, 60))));
+
+        // Outputs without encoders cannot clone nor be cloned.
+        let recording = screen(Box::new(RecordingOutput {
+            output: HeadlessOutput::new(3, HeadlessMode::new(8, 8, 60)),
+            attached: Rc::new(RefCell::new(0)),
+            presented: Rc::new(RefCell::new(Vec::new())),
+        }));
+
+        assert!(first.can_clone(&second));
+        assert!(!recording.can_clone(&first));
+        assert!(!first.can_clone(&recording));
+    }
+
+    #[test]
+    fn headless_set_mode() {
+        let modes = vec![HeadlessMode::new(1920, 1080, 60)];
+        let mut display = Display::with_backend(16, Some(Backend::Headless(modes))).unwrap();
+        let screen = display.gpus_mut().first_mut().unwrap().screens_mut().first_mut().unwrap();
+
+        let modes = screen.modes();
+        assert_eq!(modes.len(), 5);
+        assert!(modes[0].preferred);
+        assert_eq!((modes[1].width, modes[1].height), (1280, 720));
+
+        let mode = screen.set_mode(1).unwrap();
+        assert_eq!((screen.width(), screen.height(), screen.mode()), (1280, 720, 1));
+        assert_eq!(mode, modes[1]);
+
+        screen.clear_color(0x000000ff);
+        screen.swap_buffers().unwrap();
+        assert_eq!(screen.scanout().unwrap().unwrap().len(), 1280 * 720);
+
+        assert_eq!(screen.set_mode(99), Err(ErrorKind::MODE_NOT_FOUND));
+        assert_eq!(screen.mode(), 1);
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_set_modes_as_unit() {
+        let modes = vec![HeadlessMode::new(1920, 1080, 60), HeadlessMode::new(1280, 1024, 60)];
+        let mut display = Display::with_backend(16, Some(Backend::Headless(modes))).unwrap();
+        let gpu = display.gpus_mut().first_mut().unwrap();
+
+        let applied = gpu.set_modes(&[(1, 1), (2, 1)]).unwrap();
+        assert_eq!((applied[0].width, applied[1].width), (1280, 1024));
+        assert_eq!(gpu.get_screen(2).unwrap().scanout().unwrap().unwrap().len(), 1024 * 768);
+
+        // The second change fails, so the first one is undone.
+        assert_eq!(gpu.set_modes(&[(1, 2), (2, 99)]), Err(ErrorKind::MODE_NOT_FOUND));
+        assert_eq!(gpu.get_screen(1).unwrap().mode(), 1);
+        assert_eq!(gpu.get_screen(2).unwrap().mode(), 1);
+        assert_eq!(gpu.get_screen(1).unwrap().scanout().unwrap().unwrap().len(), 1280 * 720);
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_overlay_composited() {
+        let mut display = Display::with_backend(16, Some(Backend::Headless(vec![HeadlessMode::new(8, 8, 60)]))).unwrap();
+        let screen = display.gpus_mut().first_mut().unwrap().screens_mut().first_mut().unwrap();
+        assert!(screen.planes().is_empty());
+
+        let mut overlay = Buffer::memory(4, 4, PixelFormat::ARGB8888).unwrap();
+        overlay.write(0, 0, 4, 4, &[0x00ff00ff; 16]).unwrap();
+
+        // Without planes the overlay is drawn into the frame, clipped to the screen.
+        screen.clear_color(0);
+        assert_eq!(screen.show_overlay(&overlay, -2, 6).unwrap(), Placement::Composited);
+        screen.swap_buffers().unwrap();
+
+        let pixels = screen.scanout().unwrap().unwrap();
+        for (index, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (index % 8, index / 8);
+            let expected = if x < 2 && y >= 6 { 0x00ff00ff } else { 0 };
+            assert_eq!(*pixel, expected, "pixel {}x{}", x, y);
+        }
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_software_cursor() {
+        let mut display = Display::with_backend(16, Some(Backend::Headless(vec![HeadlessMode::new(8, 8, 60)]))).unwrap();
+        let screen = display.gpus_mut().first_mut().unwrap().screens_mut().first_mut().unwrap();
+        let cursor_at = |pixels: &[u32], left: usize, top: usize| {
+            pixels.iter().enumerate().all(|(index, pixel)| {
+                let (x, y) = (index % 8, index / 8);
+                let inside = (left..left + 2).contains(&x) && (top..top + 2).contains(&y);
+                *pixel == if inside { 0xff0000ff } else { 0 }
+            })
+        };
+
+        // Headless outputs have no cursor plane, the cursor is composited.
+        assert!(!screen.set_cursor(2, 2, &[0xff0000ff; 4], 1, 1).unwrap());
+
+        // Sizes come from entities, they are checked before anything is allocated.
+        assert_eq!(screen.set_cursor(0, 2, &[], 0, 0), Err(ErrorKind::CURSOR_INVALID));
+        assert_eq!(screen.set_cursor(65, 1, &[0; 65], 0, 0), Err(ErrorKind::CURSOR_INVALID));
+        assert_eq!(screen.set_cursor(u32::MAX, u32::MAX, &[], 0, 0), Err(ErrorKind::CURSOR_INVALID));
+        assert_eq!(screen.set_cursor(2, 2, &[0; 3], 0, 0), Err(ErrorKind::BUFFER_INVALID_PIXELS));
+
+        screen.move_cursor(3, 3).unwrap();
+        assert_eq!(screen.cursor_position(), Some((3, 3)));
+
+        screen.clear_color(0);
+        screen.swap_buffers().unwrap();
+        assert!(cursor_at(&screen.scanout().unwrap().unwrap(), 2, 2));
+
+        // Once every buffer was drawn again, the old position is erased.
+        screen.move_cursor(5, 5).unwrap();
+        for _ in 0..screen.buffer_count() {
+            screen.swap_buffers().unwrap();
+        }
+        assert!(cursor_at(&screen.scanout().unwrap().unwrap(), 4, 4));
+
+        screen.hide_cursor().unwrap();
+        assert_eq!(screen.cursor_position(), None);
+        for _ in 0..screen.buffer_count() {
+            screen.swap_buffers().unwrap();
+        }
+        assert!(screen.scanout().unwrap().unwrap().iter().all(|pixel| *pixel == 0));
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_gamma() {
+        let mut display = Display::with_backend(16, Some(Backend::Headless(vec![HeadlessMode::new(8, 8, 60)]))).unwrap();
+        let screen = display.gpus_mut().first_mut().unwrap().screens_mut().first_mut().unwrap();
+        let size = screen.gamma_size();
+        assert_eq!(screen.gamma().unwrap(), GammaRamp::linear(size));
+
+        // Warmer white points dim blue first, red is left untouched.
+        screen.set_color_temperature(3400, 1.0, 1.0).unwrap();
+        let ramp = screen.gamma().unwrap();
+        assert_eq!(ramp.red[size - 1], u16::MAX);
+        assert!(ramp.blue[size - 1] < ramp.green[size - 1]);
+
+        assert_eq!(screen.set_gamma(&GammaRamp::linear(size / 2)), Err(ErrorKind::GAMMA_FAILED));
+        assert_eq!(screen.gamma().unwrap(), ramp);
+
+        screen.dispose();
+        assert_eq!(screen.gamma().unwrap(), GammaRamp::linear(size));
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_idle_power_state() {
+        let modes = vec![HeadlessMode::new(8, 8, 60), HeadlessMode::new(8, 8, 60)];
+        let mut display = Display::with_backend(16, Some(Backend::Headless(modes))).unwrap();
+        let gpu = display.gpus()[0].id();
+        let (first, second) = (display.gpus()[0].screens()[0].id(), display.gpus()[0].screens()[1].id());
+        let state = |display: &Display, screen: u32| display.get_gpu(gpu).unwrap().get_screen(screen).unwrap().power_state();
+
+        assert_eq!(display.idle_remaining(), None);
+        assert!(!display.idle());
+
+        display.set_idle_timeout(Some(Duration::ZERO));
+        assert!(display.idle());
+        assert_eq!(state(&display, first), PowerState::Off);
+        assert_eq!(state(&display, second), PowerState::Off);
+        assert_eq!(display.take_events().len(), 2);
+        assert!(!display.idle());
+
+        // A state set explicitly is kept when the activity resumes.
+        display.set_power_state(1, gpu, second, PowerState::Standby).unwrap();
+        display.set_idle_timeout(None);
+        assert_eq!(state(&display, first), PowerState::On);
+        assert_eq!(state(&display, second), PowerState::Standby);
+        assert_eq!(display.take_events().iter().map(|(origin, _)| *origin).collect::<Vec<_>>(), vec![1, 0]);
+
+        // Swaps made while powered down are shown once powered on.
+        let screen = display.get_gpu_mut(gpu).unwrap().get_screen_mut(second).unwrap();
+        screen.clear_color(0xff00ff00);
+        screen.swap_buffers().unwrap();
+        screen.set_power_state(PowerState::On).unwrap();
+        assert!(screen.scanout().unwrap().unwrap().iter().all(|pixel| *pixel == 0xff00ff00));
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_layout() {
+        let modes = vec![HeadlessMode::new(16, 8, 60), HeadlessMode::new(8, 8, 60)];
+        let mut display = Display::with_backend(16, Some(Backend::Headless(modes))).unwrap();
+        let gpu = display.gpus()[0].id();
+        let (first, second) = (display.gpus()[0].screens()[0].id(), display.gpus()[0].screens()[1].id());
+
+        // New screens are placed right of the desktop.
+        let layout = display.layout();
+        assert_eq!((layout[0].x, layout[0].width), (0, 16));
+        assert_eq!((layout[1].x, layout[1].width), (16, 8));
+
+        display.set_screen_position(1, gpu, second, -4, 2, 2.0).unwrap();
+        let layout = display.layout();
+        assert_eq!((layout[1].x, layout[1].y, layout[1].width, layout[1].height), (-4, 2, 4, 4));
+        assert_eq!(display.set_screen_position(1, gpu, second, 0, 0, 0.0), Err(ErrorKind::LAYOUT_INVALID));
+
+        // A mirror shows the position of its source, mirrors are not chained.
+        display.set_mirror(1, gpu, first, Some((gpu, second))).unwrap();
+        let layout = display.layout();
+        assert_eq!((layout[0].x, layout[0].y, layout[0].mirror), (-4, 2, Some((gpu, second))));
+        assert_eq!(display.set_mirror(1, gpu, second, Some((gpu, first))), Err(ErrorKind::LAYOUT_INVALID));
+        assert_eq!(display.set_mirror(1, gpu, first, Some((gpu + 1, second))), Err(ErrorKind::SCREEN_NOT_FOUND));
+
+        display.set_mirror(1, gpu, first, None).unwrap();
+        assert_eq!((display.layout()[0].x, display.layout()[0].mirror), (0, None));
+        assert_eq!(display.take_events().len(), 3);
+
+        display.dispose();
+    }
+
+    #[test]
+    fn headless_transform() {
+        let mut display = Display::with_backend(16, Some(Backend::Headless(vec![HeadlessMode::new(16, 8, 60)]))).unwrap();
+        let gpu = display.gpus()[0].id();
+        let id = display.gpus()[0].screens()[0].id();
+
+        // Headless outputs cannot turn the picture, it is composited.
+        assert!(!display.set_transform(1, gpu, id, SurfaceTransform::Rotate90).unwrap());
+        assert_eq!((display.layout()[0].width, display.layout()[0].height), (8, 16));
+        assert_eq!(display.take_events().len(), 1);
+
+        let screen = display.get_gpu_mut(gpu).unwrap().get_screen_mut(id).unwrap();
+        assert_eq!((screen.width(), screen.height()), (8, 16));
+
+        screen.clear_color(0);
+        screen.rect(7, 0, 1, 2, &[0xff0000ff, 0xff00ff00]).unwrap();
+        assert!(screen.rect(0, 15, 1, 2, &[0; 2]).is_err());
+        screen.set_cursor(1, 1, &[0xffff0000], 0, 0).unwrap();
+        screen.move_cursor(0, 15).unwrap();
+        screen.swap_buffers().unwrap();
+
+        let pixels = screen.scanout().unwrap().unwrap();
+        assert_eq!(pixels.len(), 8 * 16);
+        assert_eq!((pixels[7], pixels[8 + 7], pixels[15 * 8]), (0xff0000ff, 0xff00ff00, 0xffff0000));
+        assert_eq!(pixels.iter().filter(|pixel| **pixel != 0).count(), 3);
+
+        // The top right corner of the picture is shown top left on the panel.
+        assert_eq!(screen.map_input(0, 0), Some((7, 0)));
+        assert_eq!(screen.map_input(15, 7), Some((0, 15)));
+        assert_eq!(screen.map_input(16, 0), None);
+
+        display.set_transform(1, gpu, id, SurfaceTransform::Normal).unwrap();
+        let screen = display.get_gpu(gpu).unwrap().get_screen(id).unwrap();
+        assert_eq!((screen.width(), screen.height(), screen.transform()), (16, 8, SurfaceTransform::Normal));
+
+        display.dispose();
+    }
+
+    fn memfd(pixels: &[u32]) -> std::os::fd::OwnedFd {
+        use std::os::fd::{AsRawFd, FromRawFd};
+
+        let fd = unsafe { std::os::fd::OwnedFd::from_raw_fd(libc::memfd_create(c"exodus-test".as_ptr(), libc::MFD_CLOEXEC)) };
+        let written = unsafe { libc::write(fd.as_raw_fd(), pixels.as_ptr() as *const libc::c_void, pixels.len() * 4) };
+        assert_eq!(written, pixels.len() as isize * 4);
+        fd
+    }
+
+    #[test]
+    fn headless_shm_buffer_attached() {
+        let mut display = Display::with_backend(16, Some(Backend::Headless(vec![HeadlessMode::new(4, 4, 60)]))).unwrap();
+        let gpu = display.gpus()[0].id();
+        let id = display.gpus()[0].screens()[0].id();
+
+        // A 2x2 buffer at offset 4 of a pool of 3 pixels wide rows.
+        let pool = Rc::new(ShmPool::new(memfd(&[0, 1, 2, 0, 3, 4, 0, 0, 0]), 36).unwrap());
+        assert_eq!(ShmBuffer::new(pool.clone(), 4, 2, 2, 12, PixelFormat::XRGB8888).unwrap().read(0, 0, 2, 2).unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(ShmBuffer::new(pool.clone(), 20, 2, 2, 12, PixelFormat::XRGB8888).unwrap_err(), ErrorKind::SHM_INVALID);
+        assert_eq!(ShmBuffer::new(pool.clone(), 2, 2, 2, 12, PixelFormat::XRGB8888).unwrap_err(), ErrorKind::SHM_INVALID);
+        assert_eq!(ShmBuffer::new(pool.clone(), 0, 4, 1, 12, PixelFormat::XRGB8888).unwrap_err(), ErrorKind::SHM_INVALID);
+
+        let buffer = ShmBuffer::new(pool, 4, 2, 2, 12, PixelFormat::XRGB8888).unwrap();
+        let screen = display.get_gpu_mut(gpu).unwrap().get_screen_mut(id).unwrap();
+        screen.clear_color(0);
+        screen.attach(&buffer, 3, -1).unwrap();
+        screen.swap_buffers().unwrap();
+
+        let pixels = screen.scanout().unwrap().unwrap();
+        assert_eq!(pixels[3], 3);
+        assert_eq!(pixels.iter().filter(|pixel| **pixel != 0).count(), 1);
+
+        display.dispose();
+    }
+
+    #[test]
+    fn shm_pool_truncated() {
+        use std::os::fd::AsRawFd;
+
+        let fd = memfd(&[0xffffffff; 4096]);
+        assert_eq!(ShmPool::new(fd.try_clone().unwrap(), 8 * 4096).unwrap_err(), ErrorKind::SHM_INVALID);
+
+        let pool = Rc::new(ShmPool::new(fd.try_clone().unwrap(), 4 * 4096).unwrap());
+        let buffer = ShmBuffer::new(pool, 0, 64, 64, 256, PixelFormat::ARGB8888).unwrap();
+        assert_eq!(buffer.read(0, 63, 64, 1).unwrap(), vec![0xffffffff; 64]);
+
+        // Reading past the end of the shrunk file raises SIGBUS, the pool is then unusable.
+        unsafe { libc::ftruncate(fd.as_raw_fd(), 4096) };
+        assert_eq!(buffer.read(0, 63, 64, 1).unwrap_err(), ErrorKind::SHM_TRUNCATED);
+        assert_eq!(buffer.read(0, 0, 1, 1).unwrap_err(), ErrorKind::SHM_TRUNCATED);
+    }
+
+    #[test]
+    fn layout_persisted_by_identity() {
+        let path = std::env::temp_dir().join(format!("exodus-layout-{}", std::process::id()));
+        let heads = vec![
+            Head { gpu: 0, screen: 1, identity: "DEL-a0b1-1".to_string(), width: 1920, height: 1080 },
+            Head { gpu: 0, screen: 2, identity: "VIRTUAL-0-2".to_string(), width: 2560, height: 1440 },
+        ];
+
+        let mut layout = Layout::new(Some(path.clone()));
+        layout.update(heads.clone());
+        layout.set_position(0, 2, -1280, 0, 2.0).unwrap();
+        layout.set_mirror(0, 1, Some((0, 2))).unwrap();
+
+        // The monitor keeps its place when plugged in another connector.
+        let mut moved = heads;
+        moved[0].screen = 7;
+        moved.reverse();
+
+        let mut layout = Layout::new(Some(path.clone()));
+        layout.update(moved);
+        assert_eq!(layout.get(0, 7).unwrap().mirror, Some((0, 2)));
+        assert_eq!((layout.get(0, 2).unwrap().x, layout.get(0, 2).unwrap().width), (-1280, 1280));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn hotplug_uevent_parse() {
+        let uevent = b"change@/devices/pci0000:00/0000:00:02.0/drm/card0\0ACTION=change\0SUBSYSTEM=drm\0HOTPLUG=1\0SEQNUM=2048\0";
+        assert!(is_drm_hotplug(uevent));
+
+        let uevent = b"add@/devices/virtual/input/input7\0ACTION=add\0SUBSYSTEM=input\0SEQNUM=2049\0";
+        assert!(!is_drm_hotplug(uevent));
+
+        let uevent = b"change@/devices/pci0000:00/0000:00:02.0/drm/card0\0ACTION=change\0SUBSYSTEM=drm\0";
+        assert!(!is_drm_hotplug(uevent));
+    }
+
+    /// Outputs `index + 1` for each plugged mode, `None` when unplugged.
+    #[derive(Debug)]
+    struct PluggableDevice {
+        outputs: Rc<RefCell<Vec<Option<HeadlessMode>>>>,
+    }
+
+    impl GraphicsDevice for PluggableDevice {
+        fn id(&self) -> GPUID {
+            0
+        }
+
+        fn vendor(&self) -> Vendor {
+            Vendor::Unknown
+        }
+
+        fn model(&self) -> u32 {
+            0
+        }
+
+        fn allocator(&self) -> Arc<dyn BufferAllocator> {
+            Arc::new(MemoryAllocator)
+        }
+
+        fn outputs(&mut self, _flags: &[ScreenFlags]) -> Result<Vec<Box<dyn Output>>, ErrorKind> {
+            Ok(self.outputs.borrow().iter().enumerate()
+                .filter_map(|(index, mode)| mode.map(|mode| Box::new(HeadlessOutput::new(index as u32 + 1, mode)) as Box<dyn Output>))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn hotplug_rescan_screens() {
+        let outputs = Rc::new(RefCell::new(vec![Some(HeadlessMode::new(64, 48, 60)), Some(HeadlessMode::new(32, 32, 60))]));
+        let mut gpu = GPU::new(Box::new(PluggableDevice { outputs: outputs.clone() }), &[ScreenFlags::DoubleBuffered]).unwrap();
+        assert_eq!(gpu.rescan().unwrap(), vec![]);
+
+        outputs.borrow_mut()[1] = None;
+        assert_eq!(gpu.rescan().unwrap(), vec![ScreenChange::Removed(2)]);
+        assert!(gpu.get_screen(2).is_none());
+        assert!(gpu.is_disconnected(2));
+
+        // Another monitor on the first connector, and the second one back.
+        *outputs.borrow_mut() = vec![Some(HeadlessMode::new(16, 16, 30)), Some(HeadlessMode::new(32, 32, 60))];
+        assert_eq!(gpu.rescan().unwrap(), vec![ScreenChange::Changed(1), ScreenChange::Added(2)]);
+        assert!(!gpu.is_disconnected(2));
+
+        let screen = gpu.get_screen(1).unwrap();
+        assert_eq!((screen.width(), screen.height(), screen.buffer_count()), (16, 16, 2));
+
+        gpu.dispose();
+    }
+}
@@ -0,0 +1,27 @@
+use exodus_common::error;
+use exodus_server::{display::Display, server::Server};
+
+const DISPLAY_CACHE_SIZE: usize = 512;
+
+fn main() {
+    let display = match Display::new(DISPLAY_CACHE_SIZE) {
+        Ok(display) => display,
+        Err(err) => {
+            error!("Failed to create display. - ErrorKind: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut server = match Server::new(display) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("Failed to start server. - ErrorKind: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = server.run() {
+        error!("Server stopped with an error. - ErrorKind: {:?}", err);
+        std::process::exit(1);
+    }
+}
@@ -1,7 +1,8 @@
-use exodus_common::net::network_message::NetworkMessage;
+use std::time::Duration;
+use exodus_common::{enums::{BufferFlag, PixelFormat, PowerState, SurfaceTransform}, graphics::{backend::Mode, buffer::{DmaBuf, DmaBufPlane, MAX_DMABUF_PLANES}, edid::Edid, gamma::GammaRamp}, net::{network_message::NetworkMessage, protocol_error::ProtocolErrorReply}};
 use exodus_errors::ErrorKind;
-use exodus_protocols::protocol_code::ProtocolCode;
-use crate::{client::Entity, display::Display};
+use exodus_protocols::{protocol_code::{ProtocolCode, negotiate_version}, messages::*};
+use crate::{client::{Entity, EntityBuffer}, display::Display, screen::Placement, shm::{ShmBuffer, ShmPool}};
 
 pub type Handler = fn(&mut Display, &mut Entity, NetworkMessage) -> Result<(), ErrorKind>;
 
@@ -12,6 +13,27 @@ pub struct ProtocolHandler {
     proto_gpuinfo:              Handler,
     proto_enumerate_screen:     Handler,
     proto_screeninfo:           Handler,
+    proto_enumerate_modes:      Handler,
+    proto_set_mode:             Handler,
+    proto_set_cursor:           Handler,
+    proto_move_cursor:          Handler,
+    proto_screen_details:       Handler,
+    proto_get_gamma:            Handler,
+    proto_set_gamma:            Handler,
+    proto_set_temperature:      Handler,
+    proto_get_power_state:      Handler,
+    proto_set_power_state:      Handler,
+    proto_set_idle_timeout:     Handler,
+    proto_get_layout:           Handler,
+    proto_set_screen_position:  Handler,
+    proto_set_mirror:           Handler,
+    proto_set_transform:        Handler,
+    proto_create_pool:          Handler,
+    proto_destroy_pool:         Handler,
+    proto_create_buffer:        Handler,
+    proto_destroy_buffer:       Handler,
+    proto_attach_buffer:        Handler,
+    proto_create_dmabuf_buffer: Handler,
 }
 
 impl ProtocolHandler {
@@ -22,6 +44,27 @@ impl ProtocolHandler {
             proto_gpuinfo:              Self::protocol_gpuinfo,
             proto_enumerate_screen:     Self::protocol_enumerate_screen,
             proto_screeninfo:           Self::protocol_screeninfo,
+            proto_enumerate_modes:      Self::protocol_enumerate_modes,
+            proto_set_mode:             Self::protocol_set_mode,
+            proto_set_cursor:           Self::protocol_set_cursor,
+            proto_move_cursor:          Self::protocol_move_cursor,
+            proto_screen_details:       Self::protocol_screen_details,
+            proto_get_gamma:            Self::protocol_get_gamma,
+            proto_set_gamma:            Self::protocol_set_gamma,
+            proto_set_temperature:      Self::protocol_set_color_temperature,
+            proto_get_power_state:      Self::protocol_get_power_state,
+            proto_set_power_state:      Self::protocol_set_power_state,
+            proto_set_idle_timeout:     Self::protocol_set_idle_timeout,
+            proto_get_layout:           Self::protocol_get_layout,
+            proto_set_screen_position:  Self::protocol_set_screen_position,
+            proto_set_mirror:           Self::protocol_set_mirror,
+            proto_set_transform:        Self::protocol_set_transform,
+            proto_create_pool:          Self::protocol_create_pool,
+            proto_destroy_pool:         Self::protocol_destroy_pool,
+            proto_create_buffer:        Self::protocol_create_buffer,
+            proto_destroy_buffer:       Self::protocol_destroy_buffer,
+            proto_attach_buffer:        Self::protocol_attach_buffer,
+            proto_create_dmabuf_buffer: Self::protocol_create_dmabuf_buffer,
         }
     }
 
@@ -33,154 +76,738 @@ impl ProtocolHandler {
             ProtocolCode::ProtocolGPUInfo               => self.proto_gpuinfo           = callback,
             ProtocolCode::ProtocolEnumerateScreens      => self.proto_enumerate_screen  = callback,
             ProtocolCode::ProtocolScreenInfo            => self.proto_screeninfo        = callback,
-            _ => todo!(),
+            ProtocolCode::ProtocolEnumerateModes        => self.proto_enumerate_modes   = callback,
+            ProtocolCode::ProtocolSetMode               => self.proto_set_mode          = callback,
+            ProtocolCode::ProtocolSetCursor             => self.proto_set_cursor        = callback,
+            ProtocolCode::ProtocolMoveCursor            => self.proto_move_cursor       = callback,
+            ProtocolCode::ProtocolScreenDetails         => self.proto_screen_details    = callback,
+            ProtocolCode::ProtocolGetGamma              => self.proto_get_gamma         = callback,
+            ProtocolCode::ProtocolSetGamma              => self.proto_set_gamma         = callback,
+            ProtocolCode::ProtocolSetColorTemperature   => self.proto_set_temperature   = callback,
+            ProtocolCode::ProtocolGetPowerState         => self.proto_get_power_state   = callback,
+            ProtocolCode::ProtocolSetPowerState         => self.proto_set_power_state   = callback,
+            ProtocolCode::ProtocolSetIdleTimeout        => self.proto_set_idle_timeout  = callback,
+            ProtocolCode::ProtocolGetLayout             => self.proto_get_layout        = callback,
+            ProtocolCode::ProtocolSetScreenPosition     => self.proto_set_screen_position = callback,
+            ProtocolCode::ProtocolSetMirror             => self.proto_set_mirror        = callback,
+            ProtocolCode::ProtocolSetTransform          => self.proto_set_transform     = callback,
+            ProtocolCode::ProtocolCreatePool            => self.proto_create_pool       = callback,
+            ProtocolCode::ProtocolDestroyPool           => self.proto_destroy_pool      = callback,
+            ProtocolCode::ProtocolCreateBuffer          => self.proto_create_buffer     = callback,
+            ProtocolCode::ProtocolDestroyBuffer         => self.proto_destroy_buffer    = callback,
+            ProtocolCode::ProtocolAttachBuffer          => self.proto_attach_buffer     = callback,
+            ProtocolCode::ProtocolCreateDmabufBuffer    => self.proto_create_dmabuf_buffer = callback,
+            _ => return Err(ErrorKind::PROTOCOL_UNSUPPORTED),
         };
 
         Ok(())
     }
 
-    /// Handles the given protocol code.
+    /// Handles every complete message buffered on the entity connection.
     /// 
-    /// # Arguments
-    /// 
-    /// * `code` - The protocol code to handle.
-    /// * `message` - The message to handle.
+    /// Each message is dispatched by the code in its header. Requests the
+    /// entity is not allowed to make, and requests whose handler fails, are
+    /// answered with a `ProtocolError` reply.
     /// 
     /// # Returns
     /// 
-    /// Returns `Ok(())` if the protocol code was handled successfully.
-    /// Returns `Err(ErrorKind)` if the protocol code was not handled successfully.
-    /// 
-    /// # Examples
-    
-    pub fn handle(&mut self, display: &mut Display, entity: &mut Entity, code: ProtocolCode) -> Result<(), ErrorKind> {
-        let message = entity.recv_message()?;
-
-        if message.is_none() {
-            return Ok(());
+    /// Returns `Ok(())` once no complete message is left to handle.
+    /// Returns `Err(ErrorKind::CONNECTION_CLOSED)` when the entity disconnected.
+    pub fn handle(&mut self, display: &mut Display, entity: &mut Entity) -> Result<(), ErrorKind> {
+        while let Some(message) = entity.recv_message()? {
+            self.dispatch(display, entity, message)?;
         }
 
-        let message = message.unwrap();
+        Ok(())
+    }
+
+    fn dispatch(&mut self, display: &mut Display, entity: &mut Entity, message: NetworkMessage) -> Result<(), ErrorKind> {
+        let request = (message.code()?, message.serial());
+        let code = ProtocolCode::from(request.0);
 
-        match code {
+        if !entity.supports(code) {
+            let error = ProtocolErrorReply::new(ErrorKind::PROTOCOL_UNSUPPORTED, &message)
+                .with_message("Request not available in the negotiated protocol version.");
+            return Self::send_error(entity, error);
+        }
+
+        let result = match code {
             ProtocolCode::ProtocolEntityRegister    => (self.proto_register_entity)(display, entity, message),
             ProtocolCode::ProtocolEnumerateGPUS     => (self.proto_enumerate_gpus)(display, entity, message),
             ProtocolCode::ProtocolGPUInfo           => (self.proto_gpuinfo)(display, entity, message),
             ProtocolCode::ProtocolEnumerateScreens  => (self.proto_enumerate_screen)(display, entity, message),
-            _ => todo!(),
+            ProtocolCode::ProtocolScreenInfo        => (self.proto_screeninfo)(display, entity, message),
+            ProtocolCode::ProtocolEnumerateModes    => (self.proto_enumerate_modes)(display, entity, message),
+            ProtocolCode::ProtocolSetMode           => (self.proto_set_mode)(display, entity, message),
+            ProtocolCode::ProtocolSetCursor         => (self.proto_set_cursor)(display, entity, message),
+            ProtocolCode::ProtocolMoveCursor        => (self.proto_move_cursor)(display, entity, message),
+            ProtocolCode::ProtocolScreenDetails     => (self.proto_screen_details)(display, entity, message),
+            ProtocolCode::ProtocolGetGamma          => (self.proto_get_gamma)(display, entity, message),
+            ProtocolCode::ProtocolSetGamma          => (self.proto_set_gamma)(display, entity, message),
+            ProtocolCode::ProtocolSetColorTemperature => (self.proto_set_temperature)(display, entity, message),
+            ProtocolCode::ProtocolGetPowerState     => (self.proto_get_power_state)(display, entity, message),
+            ProtocolCode::ProtocolSetPowerState     => (self.proto_set_power_state)(display, entity, message),
+            ProtocolCode::ProtocolSetIdleTimeout    => (self.proto_set_idle_timeout)(display, entity, message),
+            ProtocolCode::ProtocolGetLayout         => (self.proto_get_layout)(display, entity, message),
+            ProtocolCode::ProtocolSetScreenPosition => (self.proto_set_screen_position)(display, entity, message),
+            ProtocolCode::ProtocolSetMirror         => (self.proto_set_mirror)(display, entity, message),
+            ProtocolCode::ProtocolSetTransform      => (self.proto_set_transform)(display, entity, message),
+            ProtocolCode::ProtocolCreatePool        => (self.proto_create_pool)(display, entity, message),
+            ProtocolCode::ProtocolDestroyPool       => (self.proto_destroy_pool)(display, entity, message),
+            ProtocolCode::ProtocolCreateBuffer      => (self.proto_create_buffer)(display, entity, message),
+            ProtocolCode::ProtocolDestroyBuffer     => (self.proto_destroy_buffer)(display, entity, message),
+            ProtocolCode::ProtocolAttachBuffer      => (self.proto_attach_buffer)(display, entity, message),
+            ProtocolCode::ProtocolCreateDmabufBuffer => (self.proto_create_dmabuf_buffer)(display, entity, message),
+            _ => Err(ErrorKind::PROTOCOL_UNSUPPORTED),
+        };
+
+        match result {
+            Err(ErrorKind::CONNECTION_CLOSED) => Err(ErrorKind::CONNECTION_CLOSED),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::from_request(err, request.0, request.1)),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Error for a request on a screen the GPU does not have, telling unplugged screens apart.
+    fn screen_error(display: &Display, gpu: i32, screen: u32, request: &NetworkMessage) -> ProtocolErrorReply {
+        match display.get_gpu(gpu).is_some_and(|gpu| gpu.is_disconnected(screen)) {
+            true => ProtocolErrorReply::new(ErrorKind::SCREEN_DISCONNECTED, request).with_message("Screen disconnected."),
+            false => ProtocolErrorReply::new(ErrorKind::SCREEN_NOT_FOUND, request).with_message("Screen not found."),
         }
     }
 
-    pub fn protocol_register_entity(_: &mut Display, entity: &mut Entity, mut message: NetworkMessage) -> Result<(), ErrorKind> {
-        let class = message.read_string_utf8()?;
-        let title = message.read_string_utf16()?;
-        let version = message.read_u32()?;
-        let author = message.read_string_utf8()?;
-        let description = message.read_string_utf8()?;
+    /// Answers a failed request with a `ProtocolError` reply.
+    fn send_error(entity: &mut Entity, error: ProtocolErrorReply) -> Result<(), ErrorKind> {
+        entity.send(error.encode())
+    }
+
+    pub fn protocol_register_entity(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let register: EntityRegisterRequest = request.decode()?;
 
-        entity.set_class(class);
-        entity.set_title(title);
+        let version = match negotiate_version(register.min_version, register.max_version) {
+            Some(version) => version,
+            None => {
+                let error = ProtocolErrorReply::new(ErrorKind::PROTOCOL_VERSION_UNSUPPORTED, &request)
+                    .with_message(&format!("Unsupported protocol version range {}..{}.", register.min_version, register.max_version));
+                return Self::send_error(entity, error);
+            }
+        };
+
+        let capabilities = display.capabilities();
+        let mut extensions = register.extensions;
+        extensions.retain(|name| capabilities.contains(&name.as_str()));
+
+        entity.set_class(register.class);
+        entity.set_title(register.title.0);
         entity.set_version(version);
-        entity.set_author(author);
-        entity.set_description(description);
+        entity.set_author(register.author);
+        entity.set_description(register.description);
+        entity.set_extensions(extensions);
 
-        Ok(())
+        let reply = EntityRegisterReply {
+            version,
+            capabilities: capabilities.iter().map(|x| x.to_string()).collect(),
+        };
+        entity.send(NetworkMessage::encode_reply(&request, &reply))
     }
 
-    pub fn protocol_enumerate_gpus(display: &mut Display, entity: &mut Entity, _: NetworkMessage) -> Result<(), ErrorKind> {
-        let mut message = NetworkMessage::new(ProtocolCode::ProtocolEnumerateGPUS);
-        let gpus = display.gpus();
+    pub fn protocol_enumerate_gpus(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let _: EnumerateGPUsRequest = request.decode()?;
 
-        message.write_u32(gpus.len() as u32);
+        let reply = EnumerateGPUsReply {
+            gpus: display.gpus().iter().map(|gpu| gpu.id()).collect(),
+        };
+        entity.send(NetworkMessage::encode_reply(&request, &reply))
+    }
 
-        for gpu in gpus {
-            message.write_i32(gpu.id());
+    pub fn protocol_gpuinfo(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let info: GPUInfoRequest = request.decode()?;
+        
+        if let Some(gpu) = display.get_gpu(info.gpu) {
+            let reply = GPUInfoReply {
+                gpu: gpu.id(),
+                vendor: gpu.vendor() as u32,
+                vendor_name: gpu.vendor().to_string(),
+                model: gpu.model(),
+            };
+            return entity.send(NetworkMessage::encode_reply(&request, &reply));
         }
 
-        entity.send(message);
+        Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("Failed to get GPU info."))
+    }
 
-        Ok(())
+    pub fn protocol_enumerate_screen(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let enumerate: EnumerateScreensRequest = request.decode()?;
+        let gpu = display.get_gpu(enumerate.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let reply = EnumerateScreensReply {
+            screens: gpu.unwrap().screens().iter().map(|screen| screen.id()).collect(),
+        };
+        entity.send(NetworkMessage::encode_reply(&request, &reply))
     }
 
-    pub fn protocol_gpuinfo(display: &mut Display, entity: &mut Entity, mut message: NetworkMessage) -> Result<(), ErrorKind> {
-        let gpu_id = message.read_i32()?;
-        
-        if let Some(gpu) = display.get_gpu(gpu_id) {
-            let mut message = NetworkMessage::new(ProtocolCode::ProtocolGPUInfo);
-            message.write_i32(gpu.id());
-            message.write_u32(gpu.vendor() as u32);
-            message.write_string_utf8(gpu.vendor().to_string().as_str());
-            message.write_u32(gpu.model());
-            entity.send(message);
+    pub fn protocol_screeninfo(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let info: ScreenInfoRequest = request.decode()?;
+        let gpu = display.get_gpu(info.gpu);
 
-            return Ok(());
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
         }
 
-        let mut message = NetworkMessage::new(ProtocolCode::ProtocolError);
-        message.write_string_utf8("Failed to get GPU info.");
-        entity.send(message);
+        let screen = gpu.unwrap().get_screen(info.screen);
 
-        Ok(())
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, info.gpu, info.screen, &request));
+        }
+
+        let screen = screen.unwrap();
+        let reply = ScreenInfoReply {
+            screen: screen.id(),
+            width: screen.width(),
+            height: screen.height(),
+            refresh: screen.refresh(),
+            subpixel: screen.subpixel(),
+            connector_type: screen.connector_type() as u32,
+            mm_width: screen.mmWidth(),
+            mm_height: screen.mmHeight(),
+            buffer_count: screen.buffer_count() as u32,
+        };
+        entity.send(NetworkMessage::encode_reply(&request, &reply))
+    }
+
+    pub fn protocol_enumerate_modes(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let enumerate: EnumerateModesRequest = request.decode()?;
+        let gpu = display.get_gpu(enumerate.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen(enumerate.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, enumerate.gpu, enumerate.screen, &request));
+        }
+
+        let screen = screen.unwrap();
+        let reply = EnumerateModesReply {
+            current: screen.mode(),
+            modes: screen.modes().iter().enumerate().map(|(index, mode)| Self::mode_info(index as u32, mode)).collect(),
+        };
+        entity.send(NetworkMessage::encode_reply(&request, &reply))
     }
 
-    pub fn protocol_enumerate_screen(display: &mut Display, entity: &mut Entity, _: NetworkMessage) -> Result<(), ErrorKind> {
-        let mut message = NetworkMessage::new(ProtocolCode::ProtocolEnumerateScreens);
-        let gpu = display.get_gpu(message.read_i32()?);
+    pub fn protocol_set_mode(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetModeRequest = request.decode()?;
+        let gpu = display.get_gpu_mut(set.gpu);
 
         if gpu.is_none() {
-            let mut message = NetworkMessage::new(ProtocolCode::ProtocolError);
-            message.write_string_utf8("GPU not found.");
-            entity.send(message);
-            return Ok(());
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
         }
 
         let gpu = gpu.unwrap();
-        let screens = gpu.screens();
-    
-        message.write_u32(screens.len() as u32);
 
-        for screen in screens {
-            message.write_u32(screen.id());
+        if gpu.get_screen(set.screen).is_none() {
+            return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request));
         }
 
-        entity.send(message);
+        let mode = match gpu.set_modes(&[(set.screen, set.mode)]) {
+            Ok(modes) => Self::mode_info(set.mode, &modes[0]),
+            Err(err) => {
+                let error = ProtocolErrorReply::new(err, &request).with_message(&format!("Failed to set mode {}.", set.mode));
+                return Self::send_error(entity, error);
+            }
+        };
+
+        let event = ScreenModeChangedEvent { gpu: set.gpu, screen: set.screen, mode: mode.clone() };
+        display.broadcast(entity.id(), NetworkMessage::encode_event(0, &event));
 
-        Ok(())
+        entity.send(NetworkMessage::encode_reply(&request, &SetModeReply { mode }))
     }
 
-    pub fn protocol_screeninfo(display: &mut Display, entity: &mut Entity, mut message: NetworkMessage) -> Result<(), ErrorKind> {
-        let gpu = display.get_gpu(message.read_i32()?);
+    pub fn protocol_set_cursor(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let cursor: SetCursorRequest = request.decode()?;
+        let gpu = display.get_gpu_mut(cursor.gpu);
 
         if gpu.is_none() {
-            let mut message = NetworkMessage::new(ProtocolCode::ProtocolError);
-            message.write_string_utf8("GPU not found.");
-            entity.send(message);
-            return Ok(());
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
         }
 
-        let gpu = gpu.unwrap();
-        let screen = gpu.get_screen(message.read_u32()?);
+        let screen = gpu.unwrap().get_screen_mut(cursor.screen);
 
         if screen.is_none() {
-            let mut message = NetworkMessage::new(ProtocolCode::ProtocolError);
-            message.write_string_utf8("Screen not found.");
-            entity.send(message);
-            return Ok(());
+            return Self::send_error(entity, Self::screen_error(display, cursor.gpu, cursor.screen, &request));
         }
 
         let screen = screen.unwrap();
-        let mut message = NetworkMessage::new(ProtocolCode::ProtocolScreenInfo);
-        message.write_u32(screen.id());
-        message.write_u32(screen.width());
-        message.write_u32(screen.height());
-        message.write_u32(screen.refresh());
-        message.write_u32(screen.subpixel() as u32);
-        message.write_u32(screen.connector_type() as u32);
-        message.write_u32(screen.mmWidth());
-        message.write_u32(screen.mmHeight());
-        message.write_u32(screen.buffer_count() as u32);
-        entity.send(message);
 
-        Ok(())
+        let hardware = match cursor.width == 0 && cursor.height == 0 {
+            true => screen.hide_cursor().map(|_| false),
+            false => screen.set_cursor(cursor.width, cursor.height, &cursor.pixels, cursor.hot_x, cursor.hot_y),
+        };
+        display.activity();
+
+        match hardware {
+            Ok(hardware) => entity.send(NetworkMessage::encode_reply(&request, &SetCursorReply { hardware })),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to set cursor.")),
+        }
+    }
+
+    pub fn protocol_move_cursor(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let position: MoveCursorRequest = request.decode()?;
+        let gpu = display.get_gpu_mut(position.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen_mut(position.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, position.gpu, position.screen, &request));
+        }
+
+        screen.unwrap().move_cursor(position.x, position.y)?;
+        display.activity();
+        entity.send(NetworkMessage::encode_reply(&request, &MoveCursorReply {}))
+    }
+
+    pub fn protocol_screen_details(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let details: ScreenDetailsRequest = request.decode()?;
+        let gpu = display.get_gpu(details.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen(details.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, details.gpu, details.screen, &request));
+        }
+
+        let reply = match screen.unwrap().edid() {
+            Some(edid) => Self::screen_details(edid),
+            None => ScreenDetailsReply::default(),
+        };
+        entity.send(NetworkMessage::encode_reply(&request, &reply))
+    }
+
+    pub fn protocol_get_gamma(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let get: GetGammaRequest = request.decode()?;
+        let gpu = display.get_gpu(get.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen(get.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, get.gpu, get.screen, &request));
+        }
+
+        match screen.unwrap().gamma() {
+            Ok(ramp) => entity.send(NetworkMessage::encode_reply(&request, &GetGammaReply { red: ramp.red, green: ramp.green, blue: ramp.blue })),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Screen has no gamma ramp.")),
+        }
+    }
+
+    pub fn protocol_set_gamma(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetGammaRequest = request.decode()?;
+        let gpu = display.get_gpu_mut(set.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen_mut(set.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request));
+        }
+
+        let result = GammaRamp::new(set.red, set.green, set.blue).and_then(|ramp| screen.unwrap().set_gamma(&ramp));
+
+        match result {
+            Ok(()) => entity.send(NetworkMessage::encode_reply(&request, &SetGammaReply {})),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to set gamma.")),
+        }
+    }
+
+    pub fn protocol_set_color_temperature(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetColorTemperatureRequest = request.decode()?;
+        let gpu = display.get_gpu_mut(set.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen_mut(set.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request));
+        }
+
+        match screen.unwrap().set_color_temperature(set.temperature, set.gamma, set.brightness) {
+            Ok(()) => entity.send(NetworkMessage::encode_reply(&request, &SetColorTemperatureReply {})),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to set color temperature.")),
+        }
+    }
+
+    pub fn protocol_get_power_state(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let get: GetPowerStateRequest = request.decode()?;
+        let gpu = display.get_gpu(get.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen(get.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, get.gpu, get.screen, &request));
+        }
+
+        let state = screen.unwrap().power_state() as u32;
+        entity.send(NetworkMessage::encode_reply(&request, &GetPowerStateReply { state }))
+    }
+
+    pub fn protocol_set_power_state(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetPowerStateRequest = request.decode()?;
+        let gpu = display.get_gpu(set.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        if gpu.unwrap().get_screen(set.screen).is_none() {
+            return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request));
+        }
+
+        let state = match PowerState::try_from(set.state) {
+            Ok(state) => state,
+            Err(err) => return Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message(&format!("Unknown power state {}.", set.state))),
+        };
+
+        if state == PowerState::On {
+            display.activity();
+        }
+
+        match display.set_power_state(entity.id(), set.gpu, set.screen, state) {
+            Ok(()) => entity.send(NetworkMessage::encode_reply(&request, &SetPowerStateReply {})),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to set power state.")),
+        }
+    }
+
+    pub fn protocol_set_idle_timeout(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetIdleTimeoutRequest = request.decode()?;
+
+        let timeout = match set.seconds {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds as u64)),
+        };
+
+        display.set_idle_timeout(timeout);
+        entity.send(NetworkMessage::encode_reply(&request, &SetIdleTimeoutReply {}))
+    }
+
+    pub fn protocol_get_layout(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let _: GetLayoutRequest = request.decode()?;
+
+        let regions = display.layout();
+        let screens = regions.into_iter().map(|region| ScreenRegion {
+            gpu: region.gpu,
+            screen: region.screen,
+            x: region.x,
+            y: region.y,
+            width: region.width,
+            height: region.height,
+            scale: region.scale,
+            transform: display.get_gpu(region.gpu)
+                .and_then(|gpu| gpu.get_screen(region.screen))
+                .map(|screen| screen.transform() as u32)
+                .unwrap_or_default(),
+            mirroring: region.mirror.is_some(),
+            mirror_gpu: region.mirror.map(|(gpu, _)| gpu).unwrap_or_default(),
+            mirror_screen: region.mirror.map(|(_, screen)| screen).unwrap_or_default(),
+        }).collect();
+
+        entity.send(NetworkMessage::encode_reply(&request, &GetLayoutReply { screens }))
+    }
+
+    pub fn protocol_set_screen_position(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetScreenPositionRequest = request.decode()?;
+
+        let gpu = display.get_gpu(set.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        if gpu.unwrap().get_screen(set.screen).is_none() {
+            return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request));
+        }
+
+        match display.set_screen_position(entity.id(), set.gpu, set.screen, set.x, set.y, set.scale) {
+            Ok(()) => entity.send(NetworkMessage::encode_reply(&request, &SetScreenPositionReply {})),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Invalid screen position.")),
+        }
+    }
+
+    pub fn protocol_set_mirror(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetMirrorRequest = request.decode()?;
+        let source = set.mirroring.then_some((set.source_gpu, set.source_screen));
+
+        let gpu = display.get_gpu(set.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let target = match gpu.unwrap().get_screen(set.screen) {
+            Some(target) => target,
+            None => return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request)),
+        };
+
+        if let Some((source_gpu, source_screen)) = source {
+            let refused = match display.get_gpu(source_gpu).and_then(|gpu| gpu.get_screen(source_screen)) {
+                None => Some((ErrorKind::SCREEN_NOT_FOUND, "Source screen not found.")),
+                Some(_) if source_gpu != set.gpu => Some((ErrorKind::LAYOUT_INVALID, "Mirrored screens must share a GPU.")),
+                Some(source) if !source.can_clone(target) => Some((ErrorKind::LAYOUT_INVALID, "Encoders cannot be cloned.")),
+                Some(_) => None,
+            };
+
+            if let Some((err, message)) = refused {
+                return Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message(message));
+            }
+        }
+
+        match display.set_mirror(entity.id(), set.gpu, set.screen, source) {
+            Ok(()) => entity.send(NetworkMessage::encode_reply(&request, &SetMirrorReply {})),
+            Err(ErrorKind::LAYOUT_INVALID) => Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::LAYOUT_INVALID, &request).with_message("Mirrors cannot be chained.")),
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to drive the screen as a clone.")),
+        }
+    }
+
+    pub fn protocol_set_transform(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let set: SetTransformRequest = request.decode()?;
+
+        let gpu = display.get_gpu(set.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        if gpu.unwrap().get_screen(set.screen).is_none() {
+            return Self::send_error(entity, Self::screen_error(display, set.gpu, set.screen, &request));
+        }
+
+        let transform = match SurfaceTransform::try_from(set.transform) {
+            Ok(transform) => transform,
+            Err(err) => return Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message(&format!("Unknown transform {}.", set.transform))),
+        };
+
+        let hardware = match display.set_transform(entity.id(), set.gpu, set.screen, transform) {
+            Ok(hardware) => hardware,
+            Err(err) => return Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to set transform.")),
+        };
+
+        let screen = display.get_gpu(set.gpu).and_then(|gpu| gpu.get_screen(set.screen)).unwrap();
+        entity.send(NetworkMessage::encode_reply(&request, &SetTransformReply { hardware, width: screen.width(), height: screen.height() }))
+    }
+
+    pub fn protocol_create_pool(_display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let create: CreatePoolRequest = request.decode()?;
+
+        let fd = match create.fd.into_owned() {
+            Some(fd) => fd,
+            None => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::SHM_INVALID, &request).with_message("No file descriptor sent with the pool.")),
+        };
+
+        match ShmPool::new(fd, create.size) {
+            Ok(pool) => match entity.add_pool(pool) {
+                Ok(pool) => entity.send(NetworkMessage::encode_reply(&request, &CreatePoolReply { pool })),
+                Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Too many pools.")),
+            },
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to map pool.")),
+        }
+    }
+
+    pub fn protocol_destroy_pool(_display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let destroy: DestroyPoolRequest = request.decode()?;
+
+        if !entity.remove_pool(destroy.pool) {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::SHM_INVALID, &request).with_message("Pool not found."));
+        }
+
+        entity.send(NetworkMessage::encode_reply(&request, &DestroyPoolReply {}))
+    }
+
+    pub fn protocol_create_buffer(_display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let create: CreateBufferRequest = request.decode()?;
+
+        let pool = match entity.pool(create.pool) {
+            Some(pool) => pool.clone(),
+            None => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::SHM_INVALID, &request).with_message("Pool not found.")),
+        };
+
+        let format = match PixelFormat::try_from(create.format) {
+            Ok(format @ (PixelFormat::XRGB8888 | PixelFormat::ARGB8888)) => format,
+            Ok(_) => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::PIXEL_FORMAT_UNSUPPORTED, &request).with_message("Only XRGB8888 and ARGB8888 can be composited.")),
+            Err(err) => return Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Unknown pixel format.")),
+        };
+
+        match ShmBuffer::new(pool, create.offset, create.width, create.height, create.stride, format) {
+            Ok(buffer) => match entity.add_buffer(EntityBuffer::Shm(buffer)) {
+                Ok(buffer) => entity.send(NetworkMessage::encode_reply(&request, &CreateBufferReply { buffer })),
+                Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Too many buffers.")),
+            },
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Buffer does not fit in the pool.")),
+        }
+    }
+
+    pub fn protocol_destroy_buffer(_display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let destroy: DestroyBufferRequest = request.decode()?;
+
+        if !entity.remove_buffer(destroy.buffer) {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::SHM_INVALID, &request).with_message("Buffer not found."));
+        }
+
+        entity.send(NetworkMessage::encode_reply(&request, &DestroyBufferReply {}))
+    }
+
+    pub fn protocol_attach_buffer(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let attach: AttachBufferRequest = request.decode()?;
+
+        match entity.buffer(attach.buffer) {
+            None => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::SHM_INVALID, &request).with_message("Buffer not found.")),
+            Some(EntityBuffer::Dmabuf { gpu, .. }) if *gpu != attach.gpu => {
+                return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("Buffer was imported on another GPU."));
+            },
+            Some(_) => (),
+        }
+
+        let gpu = display.get_gpu_mut(attach.gpu);
+
+        if gpu.is_none() {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found."));
+        }
+
+        let screen = gpu.unwrap().get_screen_mut(attach.screen);
+
+        if screen.is_none() {
+            return Self::send_error(entity, Self::screen_error(display, attach.gpu, attach.screen, &request));
+        }
+
+        let screen = screen.unwrap();
+        if let Some(plane) = entity.replace_overlay(attach.gpu, attach.screen, None) {
+            screen.hide_overlay(plane).unwrap_or_default();
+        }
+
+        let result = match entity.buffer(attach.buffer).unwrap() {
+            EntityBuffer::Shm(buffer) => screen.attach(buffer, attach.x, attach.y).map(|_| Placement::Composited),
+            EntityBuffer::Dmabuf { buffer, .. } => screen.show_overlay(buffer, attach.x, attach.y),
+        }.and_then(|placement| match placement {
+            Placement::Plane(plane) => Ok(Some(plane)),
+            Placement::Composited => screen.swap_buffers().map(|_| None),
+        });
+        display.activity();
+
+        match result {
+            Ok(plane) => {
+                entity.replace_overlay(attach.gpu, attach.screen, plane);
+                entity.send(NetworkMessage::encode_reply(&request, &AttachBufferReply {}))
+            },
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to attach buffer.")),
+        }
+    }
+
+    pub fn protocol_create_dmabuf_buffer(display: &mut Display, entity: &mut Entity, mut request: NetworkMessage) -> Result<(), ErrorKind> {
+        let create: CreateDmabufBufferRequest = request.decode()?;
+
+        let gpu = match display.get_gpu(create.gpu) {
+            Some(gpu) => gpu,
+            None => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::GPU_NOT_FOUND, &request).with_message("GPU not found.")),
+        };
+
+        match PixelFormat::from_fourcc(create.format) {
+            Ok(PixelFormat::XRGB8888 | PixelFormat::ARGB8888) => (),
+            Ok(_) => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::PIXEL_FORMAT_UNSUPPORTED, &request).with_message("Only XRGB8888 and ARGB8888 can be composited.")),
+            Err(err) => return Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Unknown fourcc code.")),
+        }
+
+        if create.planes.is_empty() || create.planes.len() > MAX_DMABUF_PLANES {
+            return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::DMABUF_IMPORT_FAILED, &request).with_message("Invalid number of planes."));
+        }
+
+        let planes: Option<Vec<DmaBufPlane>> = create.planes.into_iter()
+            .map(|plane| Some(DmaBufPlane { fd: plane.fd.into_owned()?, offset: plane.offset, stride: plane.stride }))
+            .collect();
+
+        let planes = match planes {
+            Some(planes) => planes,
+            None => return Self::send_error(entity, ProtocolErrorReply::new(ErrorKind::DMABUF_IMPORT_FAILED, &request).with_message("No file descriptor sent with a plane.")),
+        };
+
+        let dmabuf = DmaBuf { width: create.width, height: create.height, fourcc: create.format, modifier: create.modifier, planes };
+
+        match gpu.device().allocator().import(&dmabuf, &[BufferFlag::Rendering]) {
+            Ok(buffer) => match entity.add_buffer(EntityBuffer::Dmabuf { gpu: create.gpu, buffer }) {
+                Ok(buffer) => entity.send(NetworkMessage::encode_reply(&request, &CreateDmabufBufferReply { buffer })),
+                Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Too many buffers.")),
+            },
+            Err(err) => Self::send_error(entity, ProtocolErrorReply::new(err, &request).with_message("Failed to import buffer.")),
+        }
+    }
+
+    fn screen_details(edid: &Edid) -> ScreenDetailsReply {
+        let serial = match (&edid.serial_string, edid.serial) {
+            (Some(serial), _) => serial.clone(),
+            (None, 0) => String::new(),
+            (None, serial) => serial.to_string(),
+        };
+
+        let chromaticity = &edid.chromaticity;
+        let hdr = edid.hdr.unwrap_or_default();
+
+        ScreenDetailsReply {
+            has_edid: true,
+            identity: edid.identity(),
+            manufacturer: edid.manufacturer.clone(),
+            name: edid.name.clone().unwrap_or_default(),
+            serial,
+            product: edid.product as u32,
+            week: edid.week.unwrap_or_default() as u32,
+            year: edid.year as u32,
+            bit_depth: edid.bit_depth.unwrap_or_default() as u32,
+            gamma: edid.gamma.unwrap_or_default(),
+            chromaticity: [chromaticity.red, chromaticity.green, chromaticity.blue, chromaticity.white]
+                .iter().flat_map(|(x, y)| [*x, *y]).collect(),
+            timings: edid.timings.iter()
+                .map(|timing| TimingInfo { width: timing.width, height: timing.height, refresh: timing.refresh, pixel_clock: timing.pixel_clock })
+                .collect(),
+            colorimetry: edid.colorimetry as u32,
+            hdr_eotfs: hdr.eotfs as u32,
+            max_luminance: hdr.max_luminance.unwrap_or_default(),
+            max_frame_average: hdr.max_frame_average.unwrap_or_default(),
+            min_luminance: hdr.min_luminance.unwrap_or_default(),
+        }
+    }
+
+    fn mode_info(index: u32, mode: &Mode) -> ModeInfo {
+        let mut flags = 0;
+        if mode.preferred {
+            flags |= MODE_FLAG_PREFERRED;
+        }
+        if mode.interlaced {
+            flags |= MODE_FLAG_INTERLACED;
+        }
+
+        ModeInfo { index, width: mode.width, height: mode.height, refresh: mode.refresh, flags }
     }
 }
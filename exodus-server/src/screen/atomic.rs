@@ -0,0 +1,232 @@
+use std::{cell::RefCell, collections::HashMap, ffi::CStr};
+use drm::*;
+use exodus_common::{graphics::device::GPUID, error, info};
+use exodus_errors::ErrorKind;
+
+/// Kernel mode setting state shared by the outputs of a GPU.
+///
+/// When the driver supports atomic modesetting, the modesets made between
+/// `begin` and `commit` are validated and applied as a single request.
+#[derive(Debug)]
+pub struct Kms {
+    gpu:            GPUID,
+    atomic:         bool,
+    transaction:    RefCell<Option<AtomicRequest>>,
+}
+
+impl Kms {
+    /// Enables atomic modesetting when the driver supports it.
+    pub(crate) fn new(gpu: GPUID) -> Self {
+        let atomic = unsafe {
+            drmSetClientCap(gpu, DRM_CLIENT_CAP_UNIVERSAL_PLANES as u64, 1) == 0
+                && drmSetClientCap(gpu, DRM_CLIENT_CAP_ATOMIC as u64, 1) == 0
+        };
+
+        if atomic {
+            info!("Atomic modesetting enabled. - GPUID: {}", gpu);
+        } else {
+            info!("Atomic modesetting not supported, using legacy modesetting. - GPUID: {}", gpu);
+        }
+
+        Self { gpu, atomic, transaction: RefCell::new(None) }
+    }
+
+    pub fn gpu(&self) -> GPUID {
+        self.gpu
+    }
+
+    pub fn is_atomic(&self) -> bool {
+        self.atomic
+    }
+
+    /// Starts collecting modesets, discarding any unfinished transaction.
+    pub(crate) fn begin(&self) -> Result<(), ErrorKind> {
+        if self.atomic {
+            *self.transaction.borrow_mut() = Some(AtomicRequest::new()?);
+        }
+
+        Ok(())
+    }
+
+    /// Adds a modeset to the open transaction, or commits it right away when there is none.
+    pub(crate) fn modeset(&self, request: AtomicRequest) -> Result<(), ErrorKind> {
+        match self.transaction.borrow_mut().as_mut() {
+            Some(transaction) => transaction.merge(&request),
+            None => request.commit(self.gpu, DRM_MODE_ATOMIC_ALLOW_MODESET, std::ptr::null_mut()),
+        }
+    }
+
+    /// Validates the collected modesets with a test-only commit, then applies them.
+    pub(crate) fn commit(&self) -> Result<(), ErrorKind> {
+        let transaction = self.transaction.borrow_mut().take();
+
+        if let Some(transaction) = transaction {
+            transaction.commit(self.gpu, DRM_MODE_ATOMIC_ALLOW_MODESET | DRM_MODE_ATOMIC_TEST_ONLY, std::ptr::null_mut())?;
+            transaction.commit(self.gpu, DRM_MODE_ATOMIC_ALLOW_MODESET, std::ptr::null_mut())?;
+        }
+
+        Ok(())
+    }
+
+    /// Discards the collected modesets.
+    pub(crate) fn abort(&self) {
+        self.transaction.borrow_mut().take();
+    }
+}
+
+/// Wrapper around `drmModeAtomicReq`.
+#[derive(Debug)]
+pub struct AtomicRequest {
+    request: drmModeAtomicReqPtr,
+}
+
+impl AtomicRequest {
+    pub fn new() -> Result<Self, ErrorKind> {
+        let request = unsafe { drmModeAtomicAlloc() };
+
+        if request.is_null() {
+            let err = ErrorKind::ATOMIC_COMMIT_FAILED;
+            error!("Failed to allocate atomic request. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        Ok(Self { request })
+    }
+
+    pub fn add(&mut self, object: u32, property: u32, value: u64) -> Result<(), ErrorKind> {
+        if unsafe { drmModeAtomicAddProperty(self.request, object, property, value) } < 0 {
+            let err = ErrorKind::ATOMIC_COMMIT_FAILED;
+            error!("Failed to add atomic property. - ObjectID: {} - PropertyID: {} - ErrorKind: {:?}", object, property, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &AtomicRequest) -> Result<(), ErrorKind> {
+        if unsafe { drmModeAtomicMerge(self.request, other.request) } != 0 {
+            return Err(ErrorKind::ATOMIC_COMMIT_FAILED);
+        }
+
+        Ok(())
+    }
+
+    /// Commits the request, `user_data` is passed to the page flip event when one is requested.
+    pub fn commit(&self, gpu: GPUID, flags: u32, user_data: *mut libc::c_void) -> Result<(), ErrorKind> {
+        if unsafe { drmModeAtomicCommit(gpu, self.request, flags, user_data) } != 0 {
+            let err = ErrorKind::ATOMIC_COMMIT_FAILED;
+            error!("Atomic commit failed. - GPUID: {} - Flags: {:#x} - ErrorKind: {:?}", gpu, flags, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AtomicRequest {
+    fn drop(&mut self) {
+        unsafe { drmModeAtomicFree(self.request) };
+    }
+}
+
+/// Property ids of a KMS object, by name.
+#[derive(Debug, Clone)]
+pub struct Properties {
+    object: u32,
+    ids:    HashMap<String, u32>,
+    values: HashMap<String, u64>,
+}
+
+impl Properties {
+    pub fn new(gpu: GPUID, object: u32, object_type: u32) -> Result<Self, ErrorKind> {
+        let properties = unsafe { drmModeObjectGetProperties(gpu, object, object_type) };
+
+        if properties.is_null() {
+            let err = ErrorKind::ATOMIC_COMMIT_FAILED;
+            error!("Failed to get object properties. - ObjectID: {} - ErrorKind: {:?}", object, err);
+            return Err(err);
+        }
+
+        let mut ids = HashMap::new();
+        let mut values = HashMap::new();
+
+        unsafe {
+            let list = properties.as_ref().unwrap();
+            for i in 0..list.count_props as usize {
+                let id = *list.props.add(i);
+                let property = drmModeGetProperty(gpu, id);
+                if property.is_null() {
+                    continue;
+                }
+
+                let name = CStr::from_ptr((*property).name.as_ptr()).to_string_lossy().to_string();
+                values.insert(name.clone(), *list.prop_values.add(i));
+                ids.insert(name, id);
+                drmModeFreeProperty(property);
+            }
+
+            drmModeFreeObjectProperties(properties);
+        }
+
+        Ok(Self { object, ids, values })
+    }
+
+    pub fn object(&self) -> u32 {
+        self.object
+    }
+
+    /// Id of the property, for the legacy property ioctls.
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    /// Value of the property when the ids were read.
+    pub fn value(&self, name: &str) -> Option<u64> {
+        self.values.get(name).copied()
+    }
+
+    /// Adds `name = value` for this object to the request.
+    pub fn set(&self, request: &mut AtomicRequest, name: &str, value: u64) -> Result<(), ErrorKind> {
+        match self.ids.get(name) {
+            Some(id) => request.add(self.object, *id, value),
+            None => {
+                let err = ErrorKind::ATOMIC_COMMIT_FAILED;
+                error!("Missing property \"{}\". - ObjectID: {} - ErrorKind: {:?}", name, self.object, err);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Mode stored in a property blob, for the `MODE_ID` property.
+#[derive(Debug)]
+pub struct ModeBlob {
+    gpu:    GPUID,
+    id:     u32,
+}
+
+impl ModeBlob {
+    pub fn new(gpu: GPUID, mode: &drmModeModeInfo) -> Result<Self, ErrorKind> {
+        let mut id = 0;
+        let size = std::mem::size_of::<drmModeModeInfo>();
+        let result = unsafe { drmModeCreatePropertyBlob(gpu, mode as *const _ as *const libc::c_void, size, &mut id) };
+
+        if result != 0 {
+            let err = ErrorKind::ATOMIC_COMMIT_FAILED;
+            error!("Failed to create mode blob. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        Ok(Self { gpu, id })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for ModeBlob {
+    fn drop(&mut self) {
+        unsafe { drmModeDestroyPropertyBlob(self.gpu, self.id) };
+    }
+}
@@ -16,7 +16,8 @@ pub struct Connector {
     subpixel: u32,
     modes: Modes,
     connector: *mut _drmModeConnector,
-    encoder: Encoder
+    /// Encoders that can drive the connector, the one currently driving it first.
+    encoders: Vec<Encoder>,
 }
 
 impl Connector {
@@ -30,8 +31,14 @@ impl Connector {
 
         let connector = connector.unwrap();
         let modes = unsafe { Self::get_modes(connector) };
-        let encoder = unsafe { (*connector).encoder_id };
-        let encoder = Encoder::new(gpu, encoder)?;
+        let encoders = unsafe { Self::get_encoders(gpu, connector) };
+
+        if encoders.is_empty() {
+            let err = ErrorKind::ENCODER_FAILED;
+            error!("No encoder can drive the connector. - ConnectorID: {} - ErrorKind: {:?}", connector_id, err);
+            unsafe { drmModeFreeConnector(connector) };
+            return Err(err);
+        }
 
         unsafe {
             Ok(Some(Self {
@@ -42,7 +49,7 @@ impl Connector {
                 subpixel: (*connector).subpixel,
                 modes,
                 connector,
-                encoder,
+                encoders,
             }))
         }
     }
@@ -87,6 +94,28 @@ impl Connector {
         modes
     }
 
+    /// Reads the encoders of the connector, a connector plugged since the last
+    /// modeset has no current one.
+    unsafe fn get_encoders(gpu: GPUID, connector: *mut _drmModeConnector) -> Vec<Encoder> 
+    {
+        let current = (*connector).encoder_id;
+        let ids = match (*connector).encoders.is_null() {
+            true => &[][..],
+            false => std::slice::from_raw_parts((*connector).encoders, (*connector).count_encoders as usize),
+        };
+
+        let mut encoders: Vec<Encoder> = Vec::with_capacity(ids.len());
+        if current != 0 {
+            encoders.extend(Encoder::new(gpu, current).ok());
+        }
+
+        for id in ids.iter().filter(|id| **id != current) {
+            encoders.extend(Encoder::new(gpu, *id).ok());
+        }
+
+        encoders
+    }
+
     pub fn id(&self) -> u32 {
         self.id
     }
@@ -121,8 +150,13 @@ impl Connector {
         self.modes.as_slice()
     }
 
+    /// The encoder driving the connector, or the first one that can.
     pub fn encoder(&self) -> Encoder {
-        self.encoder
+        self.encoders[0]
+    }
+
+    pub fn encoders(&self) -> &[Encoder] {
+        &self.encoders
     }
 }
 
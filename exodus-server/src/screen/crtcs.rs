@@ -1,10 +1,8 @@
 use drm::*;
-use exodus_common::{*, graphics::device::GPUID};
+use exodus_common::{*, graphics::{device::GPUID, gamma::GammaRamp}};
 use exodus_errors::ErrorKind;
 use crate::framebuffer::Framebuffer;
 
-use super::connector::Connector;
-
 #[derive(Debug)]
 pub struct CRTC {
     id: u32,
@@ -15,6 +13,8 @@ pub struct CRTC {
     height: u32,
     mode: drmModeModeInfo,
     gamma_size: i32,
+    /// Ramp found when the display took the CRTC over, put back by `restore`.
+    gamma: Option<GammaRamp>,
     gpu: GPUID,
 }
 
@@ -38,7 +38,7 @@ impl CRTC {
 
         let crtc = unsafe { crtc_ptr.as_ref().unwrap() };
 
-        let crtc = CRTC {
+        let mut crtc = CRTC {
             id: crtc_id,
             buffer_id: crtc.buffer_id,
             x: crtc.x,
@@ -47,10 +47,12 @@ impl CRTC {
             height: crtc.height,
             mode: crtc.mode,
             gamma_size: crtc.gamma_size,
+            gamma: None,
             gpu,
         };
 
         unsafe { drmModeFreeCrtc(crtc_ptr) };
+        crtc.gamma = crtc.gamma().ok();
         Ok(crtc)
     }
 
@@ -86,17 +88,100 @@ impl CRTC {
         self.gamma_size
     }
 
-    pub fn set_framebuffer(&mut self, connectors: &[&Connector], mode: drmModeModeInfoPtr, framebuffer: &Framebuffer) {
+    /// Reads the gamma ramp, fails when the CRTC has none.
+    pub fn gamma(&self) -> Result<GammaRamp, ErrorKind> {
+        let size = self.gamma_size.max(0) as usize;
+        let (mut red, mut green, mut blue) = (vec![0u16; size], vec![0u16; size], vec![0u16; size]);
 
-        let connectors = connectors.iter().map(|c| c.id()).collect::<Vec<_>>();
+        let result = match size {
+            0 => -1,
+            _ => unsafe { drmModeCrtcGetGamma(self.gpu, self.id, size as u32, red.as_mut_ptr(), green.as_mut_ptr(), blue.as_mut_ptr()) },
+        };
 
-        unsafe {
+        if result != 0 {
+            let err = ErrorKind::GAMMA_FAILED;
+            error!("Failed to get gamma. - CrtcID: {} - GammaSize: {} - ErrorKind: {:?}", self.id, size, err);
+            return Err(err);
+        }
+
+        Ok(GammaRamp { red, green, blue })
+    }
+
+    /// Programs a ramp of `gamma_size` entries.
+    pub fn set_gamma(&mut self, ramp: &GammaRamp) -> Result<(), ErrorKind> {
+        let mut ramp = ramp.clone();
+
+        let result = match ramp.size() == self.gamma_size as usize {
+            true => unsafe {
+                drmModeCrtcSetGamma(self.gpu, self.id, ramp.size() as u32, ramp.red.as_mut_ptr(), ramp.green.as_mut_ptr(), ramp.blue.as_mut_ptr())
+            },
+            false => -1,
+        };
+
+        if result != 0 {
+            let err = ErrorKind::GAMMA_FAILED;
+            error!("Failed to set gamma. - CrtcID: {} - Size: {} - GammaSize: {} - ErrorKind: {:?}", self.id, ramp.size(), self.gamma_size, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Puts back the gamma ramp the CRTC had before the display took it over.
+    pub fn restore_gamma(&mut self) {
+        if let Some(gamma) = self.gamma.take() {
+            self.set_gamma(&gamma).unwrap_or_default();
+            self.gamma = Some(gamma);
+        }
+    }
+
+    /// Performs a full modeset showing `framebuffer` right away.
+    pub fn set_framebuffer(&mut self, connectors: &[u32], mode: drmModeModeInfoPtr, framebuffer: &Framebuffer) -> Result<(), ErrorKind> {
+        let result = unsafe {
             drmModeSetCrtc(self.gpu, self.id, framebuffer.id(), 0,0,
-                connectors.as_ptr() as *mut u32, connectors.len() as i32, mode);
+                connectors.as_ptr() as *mut u32, connectors.len() as i32, mode)
+        };
+
+        if result != 0 {
+            let err = ErrorKind::CRTC_FAILED;
+            error!("Failed to set crtc. - CrtcID: {} - ErrorKind: {:?}", self.id, err);
+            return Err(err);
         }
+
+        Ok(())
+    }
+
+    /// Turns the CRTC off, leaving its connectors free for another CRTC.
+    pub fn disable(&mut self) -> Result<(), ErrorKind> {
+        let result = unsafe { drmModeSetCrtc(self.gpu, self.id, 0, 0, 0, std::ptr::null_mut(), 0, std::ptr::null_mut()) };
+
+        if result != 0 {
+            let err = ErrorKind::CRTC_FAILED;
+            error!("Failed to disable crtc. - CrtcID: {} - ErrorKind: {:?}", self.id, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Schedules `framebuffer` for the next vblank.
+    /// 
+    /// A page flip event carrying `user_data` is delivered on the GPU fd once it is shown.
+    pub fn page_flip(&mut self, framebuffer: &Framebuffer, user_data: *mut libc::c_void) -> Result<(), ErrorKind> {
+        let result = unsafe { drmModePageFlip(self.gpu, self.id, framebuffer.id(), DRM_MODE_PAGE_FLIP_EVENT, user_data) };
+
+        if result != 0 {
+            let err = ErrorKind::PAGE_FLIP_FAILED;
+            error!("Failed to flip page. - CrtcID: {} - ErrorKind: {:?}", self.id, err);
+            return Err(err);
+        }
+
+        Ok(())
     }
 
     pub fn restore(&mut self, connectors: &mut [u32]) {
+        self.restore_gamma();
+
         unsafe {
             drmModeSetCrtc(self.gpu, self.id, self.buffer_id,
                 self.x, self.y, connectors.as_mut_ptr(), 1, &mut self.mode);
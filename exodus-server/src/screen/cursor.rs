@@ -0,0 +1,50 @@
+use exodus_common::graphics::buffer::Buffer;
+
+/// Size of the buffers backing cursor images, the size cursor planes usually expect.
+pub const CURSOR_SIZE: u32 = 64;
+
+/// The cursor image of a screen and where its hotspot is.
+#[derive(Debug)]
+pub struct Cursor {
+    pub(super) buffer:      Buffer,
+    pub(super) hot_x:       i32,
+    pub(super) hot_y:       i32,
+    pub(super) x:           i32,
+    pub(super) y:           i32,
+    /// Shown by the cursor plane, composited into each frame otherwise.
+    pub(super) hardware:    bool,
+}
+
+impl Cursor {
+    /// Position of the top left corner of the image.
+    pub(super) fn origin(&self) -> (i32, i32) {
+        (self.x - self.hot_x, self.y - self.hot_y)
+    }
+}
+
+/// Pixels of a buffer covered by the software cursor, put back once the buffer is drawn again.
+#[derive(Debug)]
+pub(super) struct Saved {
+    pub(super) x:       u32,
+    pub(super) y:       u32,
+    pub(super) width:   u32,
+    pub(super) height:  u32,
+    pub(super) pixels:  Vec<u32>,
+}
+
+/// Blends the ARGB pixel `src` over `dst`.
+pub(super) fn blend(src: u32, dst: u32) -> u32 {
+    let alpha = src >> 24;
+
+    match alpha {
+        0 => dst,
+        0xff => src,
+        _ => {
+            let mix = |shift: u32| {
+                let (s, d) = ((src >> shift) & 0xff, (dst >> shift) & 0xff);
+                ((s * alpha + d * (0xff - alpha)) / 0xff) << shift
+            };
+            (dst & 0xff000000) | mix(16) | mix(8) | mix(0)
+        }
+    }
+}
@@ -1,84 +1,137 @@
 #![allow(dead_code)]
 
+mod atomic;
 mod connector;
 mod crtcs;
+mod cursor;
 mod encoders;
-
+mod output;
+mod planes;
 
 use drm::_drmModeRes;
-use exodus_common::{graphics::{device::DeviceRef, buffer::Buffer}, enums::*, debug, info};
+use std::{collections::HashMap, rc::Rc, sync::Arc};
+use exodus_common::{graphics::{backend::{BufferAllocator, Mode, Output, PlaneInfo}, buffer::{Buffer, PixelSource}, device::DeviceRef, edid::Edid, gamma::GammaRamp}, enums::*, debug, info, error};
 use exodus_errors::ErrorKind;
-use crate::framebuffer::Framebuffer;
-use self::{connector::Connector, crtcs::CRTC};
+use self::{connector::Connector, cursor::{CURSOR_SIZE, Cursor, Saved, blend}, encoders::Encoder, output::Route};
+
+pub use self::output::DrmOutput;
+pub use self::planes::DrmPlane;
+pub use self::atomic::Kms;
+pub(crate) use self::output::handle_events;
+
+/// Where a buffer passed to [`Screen::show_overlay`] ended up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Scanned out by the overlay plane with this id, until it is hidden.
+    Plane(u32),
+    /// Drawn into the back buffer, for the next frame only.
+    Composited,
+}
+
+/// Flags of the buffers a screen presents.
+const BUFFER_FLAGS: [BufferFlag; 2] = [BufferFlag::Scanout, BufferFlag::Rendering];
+
+/// A mode change with its buffers allocated, see [`Screen::prepare_mode`].
+#[derive(Debug)]
+pub(crate) struct PreparedMode {
+    index:      u32,
+    mode:       Mode,
+    buffers:    Vec<Buffer>,
+}
 
 #[derive(Debug)]
 pub struct Screen {
-    device:         DeviceRef,
-    mode:           u32,
-    index:          usize,
+    back:           usize,
     buffers:        Vec<Buffer>,
-    framebuffers:   Vec<Framebuffer>,
-    connector:      Connector,
-    crtc:           CRTC,
+    output:         Box<dyn Output>,
+    allocator:      Arc<dyn BufferAllocator>,
+    cursor:         Option<Cursor>,
+    /// Pixels under the software cursor, by buffer index.
+    under:          HashMap<usize, Saved>,
+    /// Turns the picture, the size and coordinates of the screen are in its orientation.
+    transform:      SurfaceTransform,
+    /// The output scans out upright, pixels are turned when drawn into the buffers.
+    composited:     bool,
 }
 
 impl Screen {
 
-    pub(crate) fn enumerate_screens(device: &DeviceRef, resources: &_drmModeRes, flags: &[ScreenFlags]) -> Result<Vec<Self>, ErrorKind> 
+    /// Enumerates the connected DRM outputs of a device.
+    /// 
+    /// Connectors already lit keep their CRTC, the others get a free one their
+    /// encoders can route. Connectors that cannot be driven are skipped.
+    pub(crate) fn enumerate_outputs(device: &DeviceRef, kms: &Rc<Kms>, resources: &_drmModeRes, flags: &[ScreenFlags]) -> Result<Vec<Box<dyn Output>>, ErrorKind> 
     {
-        debug!("Enumerating screens. - GPU: {} - Flags: {:?}", device.id(), flags);
+        debug!("Enumerating outputs. - GPU: {} - Flags: {:?}", device.id(), flags);
 
-        let mut screens = Vec::new();
+        let mut outputs: Vec<Box<dyn Output>> = Vec::new();
+        let crtcs = unsafe { std::slice::from_raw_parts(resources.crtcs, resources.count_crtcs as usize) };
+        let encoders = unsafe { std::slice::from_raw_parts(resources.encoders, resources.count_encoders as usize) };
 
+        let mut connectors = Vec::new();
         for i in 0..resources.count_connectors {
             let connector_id = unsafe { *resources.connectors.offset(i as isize).as_ref().unwrap() };
-            if let Ok(Some(connector)) = Connector::new(device.id(), connector_id) {
-                let screen = Screen::new(device.clone(), connector, &[ScreenFlags::TripleBuffered])?;
-                info!("Detected screen. ID: {} - Port: {:?} - Resolution: {}x{}", screen.id(), screen.connector_type(), screen.width(), screen.height());
-                screens.push(screen);
+            match Connector::new(device.id(), connector_id) {
+                Ok(Some(connector)) => connectors.push(connector),
+                Ok(None) => (),
+                Err(err) => error!("Skipping connector. - ConnectorID: {} - ErrorKind: {:?}", connector_id, err),
             }
         }
 
-        debug!("Screens enumerated successfully. - GPUID: {} - Count: {}", device.id(), screens.len());
-        Ok(screens)
-    }
-
-    fn new(device: DeviceRef, connector: Connector, flags: &[ScreenFlags]) -> Result<Self, ErrorKind> {
-        debug!("Initializing screen. - ConnectorID: {} - GPUID: {} Flags: {:?}", connector.id(), device.id(), flags);
-
-        let crtc_id = connector.encoder().crtc_id();
-        let crtc = CRTC::new(device.id(), crtc_id)?;
-
-
-        let mut mode_id = 0;
-        let mut width = 0;
-        let mut height = 0;
-        let mut refresh = 0;
-
-        if flags.contains(&ScreenFlags::OptimalResolution) 
-        {
-            debug!("Detecting optimal resolution...");
-
-            for (id, mode) in connector.modes().iter().enumerate() {
-                let mode = unsafe { mode.as_ref().unwrap() };
-                if mode.hdisplay as u32 > width && mode.vdisplay as u32 > height {
-                    width = mode.hdisplay  as u32;
-                    height = mode.vdisplay  as u32;
-                    mode_id = id as u32;
-                    refresh = mode.vrefresh;
+        connectors.sort_by_key(|connector| connector.encoder().crtc_id() == 0);
+
+        let mut used = 0u32;
+        for connector in connectors {
+            let connector_id = connector.id();
+            let route = match Self::route(&connector, crtcs, encoders, used) {
+                Some(route) => route,
+                None => {
+                    let err = ErrorKind::CRTC_NOT_FOUND;
+                    error!("No free CRTC can drive the connector, skipping it. - ConnectorID: {} - ErrorKind: {:?}", connector_id, err);
+                    continue;
                 }
+            };
+
+            match DrmOutput::new(device.clone(), kms.clone(), connector, route, flags) {
+                Ok(output) => {
+                    used |= 1 << route.crtc_index;
+                    outputs.push(Box::new(output));
+                },
+                Err(err) => error!("Skipping connector. - ConnectorID: {} - ErrorKind: {:?}", connector_id, err),
             }
         }
-        else 
-        {
-            debug!("Detecting default resolution...");
-            let mode = unsafe { connector.get_mode(0).unwrap().as_ref().unwrap() };
-            mode_id = 0;
-            width = mode.hdisplay as u32;
-            height = mode.vdisplay as u32;
-            refresh = mode.vrefresh;
+
+        debug!("Outputs enumerated successfully. - GPUID: {} - Count: {}", device.id(), outputs.len());
+        Ok(outputs)
+    }
+
+    /// Picks a CRTC not in the `used` mask of indices for the connector, and the encoder routing it.
+    /// 
+    /// The CRTC already driving the connector is kept when it is free.
+    fn route(connector: &Connector, crtcs: &[u32], encoders: &[u32], used: u32) -> Option<Route> {
+        let free = |index: usize| index < 32 && used & (1 << index) == 0;
+        let route = |crtc_index: usize, encoder: Encoder| Route {
+            crtc_id: crtcs[crtc_index],
+            crtc_index,
+            encoder,
+            encoder_index: encoders.iter().position(|id| *id == encoder.id()),
+        };
+
+        let current = connector.encoder();
+        if let Some(index) = crtcs.iter().position(|id| *id != 0 && *id == current.crtc_id()).filter(|index| free(*index)) {
+            return Some(route(index, current));
         }
 
+        connector.encoders().iter().find_map(|encoder| {
+            (0..crtcs.len())
+                .find(|index| free(*index) && encoder.possible_crtcs() & (1 << index) != 0)
+                .map(|index| route(index, *encoder))
+        })
+    }
+
+    /// Creates a screen presenting buffers from `allocator` on `output`.
+    pub fn new(allocator: Arc<dyn BufferAllocator>, mut output: Box<dyn Output>, flags: &[ScreenFlags]) -> Result<Self, ErrorKind> {
+        debug!("Initializing screen. - ID: {} - Flags: {:?}", output.id(), flags);
 
         let mut buffer_count = 1;
 
@@ -90,68 +143,71 @@ impl Screen {
             }
         }
 
+        let (width, height) = (output.width(), output.height());
+        let buffers = Self::create_buffers(allocator.as_ref(), output.as_mut(), buffer_count, width, height)?;
 
-        debug!("Creating buffers...");
-        let mut buffers: Vec<Buffer> = Vec::with_capacity(buffer_count);
-        let mut framebuffers: Vec<Framebuffer> = Vec::with_capacity(buffer_count);
-
-        const FLAGS: [BufferFlag; 2] = [BufferFlag::Scanout, BufferFlag::Rendering];
-        
-        for _ in 0..buffer_count {
-            let buffer = Buffer::new(&device, width, height, PixelFormat::ARGB8888, &FLAGS)?;
-            let framebuffer = Framebuffer::new(device.id(), &buffer)?;
-            buffers.push(buffer);
-            framebuffers.push(framebuffer);
-        }
-
-        debug!("Screen initialized. - Id: {} - GPUID: {} - Width: {} - Height: {} - Refresh: {} ", connector.id(), device.id(), width, height, refresh);
+        info!("Detected screen. ID: {} - Port: {:?} - Resolution: {}x{}", output.id(), output.connector_type(), output.width(), output.height());
+        debug!("Screen initialized. - Id: {} - Width: {} - Height: {} - Refresh: {} ", output.id(), output.width(), output.height(), output.refresh());
 
         Ok(Self {
-            device,
-            index: 0,
+            back: 0,
             buffers,
-            framebuffers,
-            connector,
-            mode: mode_id as u32,
-            crtc,
+            output,
+            allocator,
+            cursor: None,
+            under: HashMap::new(),
+            transform: SurfaceTransform::Normal,
+            composited: false,
         })
     }
 
+    /// Allocates `width`x`height` buffers and attaches them to the output.
+    fn create_buffers(allocator: &dyn BufferAllocator, output: &mut dyn Output, count: usize, width: u32, height: u32) -> Result<Vec<Buffer>, ErrorKind> {
+        debug!("Creating buffers...");
+        let mut buffers: Vec<Buffer> = Vec::with_capacity(count);
+        
+        for _ in 0..count {
+            buffers.push(allocator.allocate(width, height, PixelFormat::ARGB8888, &BUFFER_FLAGS)?);
+        }
+
+        output.attach(&buffers)?;
+        Ok(buffers)
+    }
+
     pub fn id(&self) -> u32 {
-        self.connector.id()
+        self.output.id()
     }
 
     pub fn connector_type(&self) -> ConnectorType {
-        self.connector.connector_type()
+        self.output.connector_type()
     }
 
     #[allow(non_snake_case)]
     pub fn mmWidth(&self) -> u32 {
-        self.connector.mmWidth()
+        self.output.mm_width()
     }
 
     #[allow(non_snake_case)]
     pub fn mmHeight(&self) -> u32 {
-        self.connector.mmHeight()
+        self.output.mm_height()
     }
 
     pub fn subpixel(&self) -> u32 {
-        self.connector.subpixel()
+        self.output.subpixel()
     }
 
+    /// Width in the orientation of the transform.
     pub fn width(&self) -> u32 {
-        let mode = unsafe { self.connector.get_mode(self.mode).unwrap().as_ref().unwrap() };
-        mode.hdisplay as u32
+        self.transform.size(self.output.width(), self.output.height()).0
     }
 
+    /// Height in the orientation of the transform.
     pub fn height(&self) -> u32 {
-        let mode = unsafe { self.connector.get_mode(self.mode).unwrap().as_ref().unwrap() };
-        mode.vdisplay as u32
+        self.transform.size(self.output.width(), self.output.height()).1
     }
 
     pub fn refresh(&self) -> u32 {
-        let mode = unsafe { self.connector.get_mode(self.mode).unwrap().as_ref().unwrap() };
-        mode.vrefresh
+        self.output.refresh()
     }
 
     pub fn clear_color(&mut self, color: u32) {
@@ -160,34 +216,473 @@ impl Screen {
     }
     
 
+    /// Draws into the back buffer, shown by the next `swap_buffers`.
     pub fn rect(&mut self, x: u32, y: u32, width: u32, height: u32, pixels: &[u32]) -> Result<(), ErrorKind> {
-        let length = self.buffers.len();
-        let index = (self.index + 1) % length;
-        self.buffers[index].write(x, y, width, height, pixels)
+        self.write(self.back, x, y, width, height, pixels)
     }
 
-    /// Swap the buffers of the screen.
-    pub fn swap_buffers(&mut self) -> Result<(), ErrorKind> {
-        let framebuffer = &self.framebuffers[self.index];
-        let mode = self.connector.get_mode(self.mode).unwrap();
+    /// Writes the `width`x`height` rectangle at `x`, `y` of the screen into the buffer at `index`.
+    fn write(&mut self, index: usize, x: u32, y: u32, width: u32, height: u32, pixels: &[u32]) -> Result<(), ErrorKind> {
+        if !self.composited {
+            return self.buffers[index].write(x, y, width, height, pixels);
+        }
+
+        if pixels.len() != (width * height) as usize {
+            error!("Buffer is out of bounds. - ErrorKind: {:?}", ErrorKind::BUFFER_OUT_OF_BOUNDS);
+            return Err(ErrorKind::BUFFER_OUT_OF_BOUNDS);
+        }
+
+        let ((left, top, area_width, area_height), indices) = self.buffer_area(x, y, width, height)?;
+        let mut turned = vec![0; pixels.len()];
+        for (pixel, index) in pixels.iter().zip(indices) {
+            turned[index] = *pixel;
+        }
+
+        self.buffers[index].write(left, top, area_width, area_height, &turned)
+    }
+
+    /// Reads the `width`x`height` rectangle at `x`, `y` of the screen from the buffer at `index`.
+    fn read(&self, index: usize, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u32>, ErrorKind> {
+        if !self.composited {
+            return self.buffers[index].read(x, y, width, height);
+        }
+
+        let ((left, top, area_width, area_height), indices) = self.buffer_area(x, y, width, height)?;
+        let turned = self.buffers[index].read(left, top, area_width, area_height)?;
+        Ok(indices.into_iter().map(|index| turned[index]).collect())
+    }
+
+    /// Area of the buffers showing the `width`x`height` rectangle at `x`, `y` of the screen, as
+    /// `(x, y, width, height)`, and the index in that area of each pixel of the rectangle.
+    fn buffer_area(&self, x: u32, y: u32, width: u32, height: u32) -> Result<((u32, u32, u32, u32), Vec<usize>), ErrorKind> {
+        let (screen_width, screen_height) = (self.width(), self.height());
+
+        if x + width > screen_width || y + height > screen_height {
+            error!("Buffer is out of bounds. - ErrorKind: {:?}", ErrorKind::BUFFER_OUT_OF_BOUNDS);
+            return Err(ErrorKind::BUFFER_OUT_OF_BOUNDS);
+        }
+
+        if width == 0 || height == 0 {
+            return Ok(((0, 0, 0, 0), Vec::new()));
+        }
+
+        let (x0, y0) = self.transform.apply(x, y, screen_width, screen_height);
+        let (x1, y1) = self.transform.apply(x + width - 1, y + height - 1, screen_width, screen_height);
+        let (left, top) = (x0.min(x1), y0.min(y1));
+        let (area_width, area_height) = self.transform.size(width, height);
+
+        let indices = (0..width * height).map(|index| {
+            let (column, row) = self.transform.apply(x + index % width, y + index / width, screen_width, screen_height);
+            ((row - top) * area_width + column - left) as usize
+        }).collect();
+
+        Ok(((left, top, area_width, area_height), indices))
+    }
+
+    /// Identity and capabilities of the monitor, `None` when it has no valid EDID.
+    pub fn edid(&self) -> Option<&Edid> {
+        self.output.edid()
+    }
 
-        self.crtc.set_framebuffer(&[&self.connector], mode, framebuffer);
+    /// Number of entries of the gamma ramp, `0` when the screen has none.
+    pub fn gamma_size(&self) -> usize {
+        self.output.gamma_size()
+    }
+
+    pub fn gamma(&self) -> Result<GammaRamp, ErrorKind> {
+        self.output.gamma()
+    }
 
-        self.index = (self.index + 1) % self.buffers.len();
+    /// Programs the gamma ramp, it must have `gamma_size` entries.
+    /// 
+    /// The ramp found at startup is put back when the screen is disposed.
+    pub fn set_gamma(&mut self, ramp: &GammaRamp) -> Result<(), ErrorKind> {
+        if ramp.size() != self.gamma_size() {
+            let err = ErrorKind::GAMMA_FAILED;
+            error!("Gamma ramp size mismatch. - ID: {} - Size: {} - GammaSize: {} - ErrorKind: {:?}", self.id(), ramp.size(), self.gamma_size(), err);
+            return Err(err);
+        }
+
+        self.output.set_gamma(ramp)
+    }
+
+    /// Shifts the white point to `kelvin` and applies a `gamma` and `brightness` curve, for night light.
+    pub fn set_color_temperature(&mut self, kelvin: u32, gamma: f32, brightness: f32) -> Result<(), ErrorKind> {
+        let ramp = GammaRamp::temperature(self.gamma_size(), kelvin, gamma, brightness);
+        self.set_gamma(&ramp)
+    }
+
+    /// Returns `true` when the encoder of this screen can be cloned with the one of `target`.
+    pub fn can_clone(&self, target: &Screen) -> bool {
+        self.output.possible_clones() & target.output.encoder_mask() != 0
+    }
+
+    /// Drives the screens `clones`, given by id, from the CRTC of this screen, see [`Output::set_clones`].
+    pub fn set_clones(&mut self, clones: &[u32]) -> Result<(), ErrorKind> {
+        self.output.set_clones(clones)
+    }
+
+    /// Gives up the CRTC while another screen drives this one as a clone, or takes it back.
+    pub fn set_cloned(&mut self, cloned: bool) -> Result<(), ErrorKind> {
+        self.output.set_cloned(cloned)
+    }
+
+    pub fn power_state(&self) -> PowerState {
+        self.output.power_state()
+    }
+
+    /// Blanks or powers down the monitor, swaps made meanwhile show once it is on again.
+    pub fn set_power_state(&mut self, state: PowerState) -> Result<(), ErrorKind> {
+        if state == self.power_state() {
+            return Ok(());
+        }
+
+        self.output.set_power_state(state)?;
+        info!("Screen power state changed. - ID: {} - State: {:?}", self.id(), state);
         Ok(())
     }
 
+    pub fn transform(&self) -> SurfaceTransform {
+        self.transform
+    }
+
+    /// Turns the picture of the screen, by the primary plane when the output can, composited otherwise.
+    /// 
+    /// The buffers are reallocated in the new orientation, shown by the next
+    /// swap, and the cursor is composited while the screen is turned. Returns
+    /// whether the output applies the transform. On failure the previous
+    /// transform and buffers are kept.
+    pub fn set_transform(&mut self, transform: SurfaceTransform) -> Result<bool, ErrorKind> {
+        let previous = (self.transform, self.composited);
+        let hardware = self.output.set_transform(transform)?;
+        (self.transform, self.composited) = (transform, !hardware);
+
+        let (width, height) = self.buffer_size(self.output.width(), self.output.height());
+        match Self::create_buffers(self.allocator.as_ref(), self.output.as_mut(), self.buffers.len(), width, height) {
+            Ok(buffers) => self.buffers = buffers,
+            Err(err) => {
+                error!("Failed to set transform, restoring the previous one. - ErrorKind: {:?}", err);
+                (self.transform, self.composited) = previous;
+                self.output.set_transform(if self.composited { SurfaceTransform::Normal } else { self.transform })?;
+                self.output.attach(&self.buffers)?;
+                return Err(err);
+            }
+        }
+
+        if let Some(cursor) = self.cursor.as_mut().filter(|cursor| cursor.hardware && transform != SurfaceTransform::Normal) {
+            cursor.hardware = false;
+            self.output.set_cursor(None, 0, 0)?;
+        }
+
+        self.under.clear();
+        info!("Screen transform changed. - ID: {} - Transform: {:?} - Hardware: {}", self.id(), transform, hardware);
+        self.back = 0;
+        Ok(hardware)
+    }
+
+    /// Size of the buffers for an output mode of `width`x`height`, upright when the transform is composited.
+    fn buffer_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.composited {
+            true => (width, height),
+            false => self.transform.size(width, height),
+        }
+    }
+
+    /// Maps `x`, `y` of the panel, as reported by a touchscreen, to the screen.
+    /// 
+    /// Returns `None` when the position is outside the panel.
+    pub fn map_input(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        if x >= self.output.width() || y >= self.output.height() {
+            return None;
+        }
+
+        Some(self.transform.invert(x, y, self.width(), self.height()))
+    }
+
+    /// The hardware planes of the screen.
+    pub fn planes(&self) -> Vec<PlaneInfo> {
+        self.output.planes()
+    }
+
+    /// Shows `buffer` at `x`, `y` on an overlay plane when one can scan it out,
+    /// otherwise composites it into the back buffer.
+    /// 
+    /// Overlays of a turned screen are always composited.
+    pub fn show_overlay(&mut self, buffer: &Buffer, x: i32, y: i32) -> Result<Placement, ErrorKind> {
+        if self.transform == SurfaceTransform::Normal {
+            if let Some(plane) = self.output.set_overlay(buffer, x, y)? {
+                return Ok(Placement::Plane(plane));
+            }
+        }
+
+        self.attach(buffer, x, y)?;
+        Ok(Placement::Composited)
+    }
+
+    /// Turns off an overlay plane returned by `show_overlay`.
+    pub fn hide_overlay(&mut self, plane: u32) -> Result<(), ErrorKind> {
+        self.output.clear_overlay(plane)
+    }
+
+    /// Copies the part of `buffer` that falls inside the screen into the back buffer.
+    pub fn attach(&mut self, buffer: &impl PixelSource, x: i32, y: i32) -> Result<(), ErrorKind> {
+        match self.clip(x, y, buffer.width(), buffer.height()) {
+            Some((left, top, width, height)) => {
+                let pixels = buffer.read((left as i32 - x) as u32, (top as i32 - y) as u32, width, height)?;
+                self.rect(left, top, width, height, &pixels)
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Part of the `width`x`height` rectangle at `x`, `y` inside the screen, as `(x, y, width, height)`.
+    fn clip(&self, x: i32, y: i32, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + width as i32).min(self.width() as i32);
+        let bottom = (y + height as i32).min(self.height() as i32);
+
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        Some((left as u32, top as u32, (right - left) as u32, (bottom - top) as u32))
+    }
+
+    /// Sets the cursor image, `width`x`height` ARGB `pixels` with its hotspot at `hot_x`, `hot_y`.
+    /// 
+    /// The cursor plane shows it when the output has one and the screen is not
+    /// turned, otherwise it is composited into every frame. Returns whether the
+    /// cursor plane is used. Images are at most `CURSOR_SIZE` pixels wide and high.
+    pub fn set_cursor(&mut self, width: u32, height: u32, pixels: &[u32], hot_x: i32, hot_y: i32) -> Result<bool, ErrorKind> {
+        if width == 0 || height == 0 || width > CURSOR_SIZE || height > CURSOR_SIZE {
+            let err = ErrorKind::CURSOR_INVALID;
+            error!("Invalid cursor size. - ID: {} - Size: {}x{} - ErrorKind: {:?}", self.id(), width, height, err);
+            return Err(err);
+        }
+
+        if pixels.len() != (width * height) as usize {
+            let err = ErrorKind::BUFFER_INVALID_PIXELS;
+            error!("Cursor pixels do not match its size. - ID: {} - Size: {}x{} - Pixels: {} - ErrorKind: {:?}", self.id(), width, height, pixels.len(), err);
+            return Err(err);
+        }
+
+        let (buffer_width, buffer_height) = (CURSOR_SIZE, CURSOR_SIZE);
+
+        const FLAGS: [BufferFlag; 2] = [BufferFlag::Cursor, BufferFlag::Linear];
+        let mut buffer = match self.allocator.allocate(buffer_width, buffer_height, PixelFormat::ARGB8888, &FLAGS) {
+            Ok(buffer) => buffer,
+            Err(_) => Buffer::memory(buffer_width, buffer_height, PixelFormat::ARGB8888)?,
+        };
+
+        buffer.write(0, 0, buffer_width, buffer_height, &vec![0; (buffer_width * buffer_height) as usize])?;
+        buffer.write(0, 0, width, height, pixels)?;
+
+        let hardware = self.transform == SurfaceTransform::Normal && self.output.set_cursor(Some(&buffer), hot_x, hot_y)?;
+        let (x, y) = self.cursor.as_ref().map(|cursor| (cursor.x, cursor.y)).unwrap_or_default();
+        let cursor = Cursor { buffer, hot_x, hot_y, x, y, hardware };
+
+        if hardware {
+            let (left, top) = cursor.origin();
+            self.output.move_cursor(left, top)?;
+        }
+
+        debug!("Cursor set. - ID: {} - Size: {}x{} - Hardware: {}", self.id(), width, height, hardware);
+        self.cursor = Some(cursor);
+        Ok(hardware)
+    }
+
+    /// Moves the hotspot of the cursor to `x`, `y`.
+    /// 
+    /// A composited cursor moves with the next swap.
+    pub fn move_cursor(&mut self, x: i32, y: i32) -> Result<(), ErrorKind> {
+        let cursor = match self.cursor.as_mut() {
+            Some(cursor) => cursor,
+            None => return Ok(()),
+        };
+
+        cursor.x = x;
+        cursor.y = y;
+
+        if cursor.hardware {
+            let (left, top) = cursor.origin();
+            self.output.move_cursor(left, top)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn hide_cursor(&mut self) -> Result<(), ErrorKind> {
+        if let Some(cursor) = self.cursor.take() {
+            if cursor.hardware {
+                self.output.set_cursor(None, 0, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hotspot position of the cursor, `None` when it is hidden.
+    pub fn cursor_position(&self) -> Option<(i32, i32)> {
+        self.cursor.as_ref().map(|cursor| (cursor.x, cursor.y))
+    }
+
+    /// Blends the software cursor into the back buffer, saving the pixels it covers.
+    fn draw_cursor(&mut self) -> Result<(), ErrorKind> {
+        let cursor = match self.cursor.as_ref() {
+            Some(cursor) if !cursor.hardware => cursor,
+            _ => return Ok(()),
+        };
+
+        let (x, y) = cursor.origin();
+        let (left, top, width, height) = match self.clip(x, y, cursor.buffer.width(), cursor.buffer.height()) {
+            Some(area) => area,
+            None => return Ok(()),
+        };
+
+        let image = cursor.buffer.read((left as i32 - x) as u32, (top as i32 - y) as u32, width, height)?;
+        let pixels = self.read(self.back, left, top, width, height)?;
+        let blended: Vec<u32> = image.iter().zip(pixels.iter()).map(|(src, dst)| blend(*src, *dst)).collect();
+
+        self.write(self.back, left, top, width, height, &blended)?;
+        self.under.insert(self.back, Saved { x: left, y: top, width, height, pixels });
+        Ok(())
+    }
+
+    /// Puts back the pixels the software cursor covered in the back buffer.
+    fn erase_cursor(&mut self) -> Result<(), ErrorKind> {
+        if let Some(saved) = self.under.remove(&self.back) {
+            self.write(self.back, saved.x, saved.y, saved.width, saved.height, &saved.pixels)?;
+        }
+
+        Ok(())
+    }
+
+    /// Presents the back buffer and moves on to a buffer the hardware is done with.
+    /// 
+    /// Blocks until the next vblank when every other buffer is still shown or
+    /// waiting to be shown. A software cursor is drawn into the presented
+    /// buffer and erased once the buffer is drawn again, single buffered
+    /// screens keep it until drawn over.
+    pub fn swap_buffers(&mut self) -> Result<(), ErrorKind> {
+        self.draw_cursor()?;
+        self.output.present(self.back)?;
+        self.back = self.next_back()?;
+
+        if self.buffers.len() > 1 {
+            self.erase_cursor()?;
+        } else {
+            self.under.clear();
+        }
+
+        Ok(())
+    }
+
+    fn next_back(&mut self) -> Result<usize, ErrorKind> {
+        let length = self.buffers.len();
+
+        loop {
+            let displayed = self.output.displayed();
+            let pending = self.output.pending();
+
+            if length == 1 && pending.is_none() {
+                return Ok(0);
+            }
+
+            let free = (1..=length)
+                .map(|offset| (self.back + offset) % length)
+                .find(|index| Some(*index) != displayed && Some(*index) != pending);
+
+            if let Some(index) = free {
+                return Ok(index);
+            }
+
+            self.output.wait()?;
+        }
+    }
+
+    /// Reads back the pixels of the buffer being shown, `None` before the first swap.
+    pub fn scanout(&self) -> Result<Option<Vec<u32>>, ErrorKind> {
+        match self.output.displayed() {
+            Some(displayed) => Ok(Some(self.read(displayed, 0, 0, self.width(), self.height())?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn buffer_count(&self) -> usize {
         self.buffers.len()
     }
 
     pub(super) fn dispose(&mut self) {
-        debug!("Disposing screen. - ConnectorID: {} - GPUID: {}", self.connector.id(), self.device.id());
-        self.crtc.restore(&mut [self.id()])
+        debug!("Disposing screen. - ID: {}", self.id());
+        self.output.restore()
     }
 
     pub fn mode(&self) -> u32 {
-        self.mode
+        self.output.mode()
+    }
+
+    pub fn modes(&self) -> Vec<Mode> {
+        self.output.modes()
+    }
+
+    /// Switches the screen to the mode at `index` and reallocates its buffers.
+    /// 
+    /// The new mode is shown right away, cleared. On failure the previous mode
+    /// and buffers are kept on screen.
+    pub fn set_mode(&mut self, index: u32) -> Result<Mode, ErrorKind> {
+        let prepared = self.prepare_mode(index)?;
+
+        if let Err(err) = self.stage_mode(&prepared) {
+            error!("Failed to set mode, restoring the previous one. - ErrorKind: {:?}", err);
+            self.cancel_mode();
+            return Err(err);
+        }
+
+        Ok(self.finish_mode(prepared))
+    }
+
+    /// Allocates the buffers of the mode at `index`, leaving the output untouched.
+    pub(crate) fn prepare_mode(&self, index: u32) -> Result<PreparedMode, ErrorKind> {
+        let mode = *self.output.modes().get(index as usize).ok_or(ErrorKind::MODE_NOT_FOUND)?;
+        let (width, height) = self.buffer_size(mode.width, mode.height);
+
+        let mut buffers = Vec::with_capacity(self.buffers.len());
+        for _ in 0..self.buffers.len() {
+            buffers.push(self.allocator.allocate(width, height, PixelFormat::ARGB8888, &BUFFER_FLAGS)?);
+        }
+
+        Ok(PreparedMode { index, mode, buffers })
+    }
+
+    /// Drives the output at the prepared mode and presents its first buffer.
+    /// 
+    /// Inside a transaction of the device the modeset is only collected. The
+    /// previous buffers stay attached until `finish_mode` or `cancel_mode`.
+    pub(crate) fn stage_mode(&mut self, prepared: &PreparedMode) -> Result<(), ErrorKind> {
+        self.output.checkpoint()?;
+        self.output.set_mode(prepared.index)?;
+        self.output.attach(&prepared.buffers)?;
+        self.output.present(0)
+    }
+
+    /// Swaps in the buffers of a staged mode once it is shown.
+    pub(crate) fn finish_mode(&mut self, prepared: PreparedMode) -> Mode {
+        self.output.release();
+        self.buffers = prepared.buffers;
+        self.under.clear();
+        self.back = 1 % self.buffers.len();
+
+        let mode = prepared.mode;
+        info!("Screen mode changed. - ID: {} - Resolution: {}x{}@{}", self.id(), mode.width, mode.height, mode.refresh);
+        mode
+    }
+
+    /// Returns to the mode and buffers shown before `stage_mode`.
+    pub(crate) fn cancel_mode(&mut self) {
+        if let Err(err) = self.output.rollback() {
+            error!("Failed to restore mode. - ScreenID: {} - ErrorKind: {:?}", self.id(), err);
+        }
     }
 }
 
@@ -195,4 +690,4 @@ impl Drop for Screen {
     fn drop(&mut self) {
         self.dispose();
     }
-}
\ No newline at end of file
+}
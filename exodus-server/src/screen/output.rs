@@ -0,0 +1,784 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+use drm::{DRM_MODE_FLAG_INTERLACE, DRM_MODE_TYPE_PREFERRED, DRM_MODE_OBJECT_CONNECTOR, DRM_MODE_OBJECT_CRTC, DRM_MODE_PAGE_FLIP_EVENT, DRM_MODE_ATOMIC_NONBLOCK, DRM_MODE_ATOMIC_ALLOW_MODESET, drmEventContext, drmHandleEvent, drmModeSetPlane, drmModeSetCursor, drmModeSetCursor2, drmModeMoveCursor, drmModeGetPropertyBlob, drmModeFreePropertyBlob, drmModeConnectorSetProperty};
+use exodus_common::{graphics::{backend::{Mode, Output, PlaneInfo, Presenter}, buffer::Buffer, device::{DeviceRef, GPUID}, edid::Edid, gamma::GammaRamp}, enums::*, debug, error};
+use exodus_errors::ErrorKind;
+use crate::framebuffer::Framebuffer;
+use super::{atomic::{AtomicRequest, Kms, ModeBlob, Properties}, connector::Connector, crtcs::CRTC, encoders::Encoder, planes::{DrmPlane, enumerate_planes, primary_plane}};
+
+/// How long `wait` blocks for a page flip event before giving up, in milliseconds.
+const PAGE_FLIP_TIMEOUT: i32 = 1000;
+
+/// Buffers of an output as seen by the hardware, updated by the page flip handler.
+#[derive(Debug, Default, Clone, Copy)]
+struct FlipState {
+    displayed:  Option<usize>,
+    pending:    Option<usize>,
+}
+
+/// Called by `drmHandleEvent` when a flip scheduled by `CRTC::page_flip` completed.
+unsafe extern "C" fn page_flip_handler(_fd: i32, _sequence: u32, _tv_sec: u32, _tv_usec: u32, user_data: *mut libc::c_void) {
+    let state = &*(user_data as *const Cell<FlipState>);
+    let flip = state.get();
+    state.set(FlipState { displayed: flip.pending.or(flip.displayed), pending: None });
+}
+
+/// Reads the pending events of the GPU fd and runs their handlers.
+pub(crate) fn handle_events(gpu: GPUID) -> Result<(), ErrorKind> {
+    let mut context: drmEventContext = unsafe { std::mem::zeroed() };
+    context.version = 2;
+    context.page_flip_handler = Some(page_flip_handler);
+
+    if unsafe { drmHandleEvent(gpu, &mut context) } != 0 {
+        let err = ErrorKind::PAGE_FLIP_FAILED;
+        error!("Failed to handle gpu events. - GPUID: {} - ErrorKind: {:?}", gpu, err);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// The CRTC and encoder chosen by `Screen::enumerate_outputs` to drive a connector.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Route {
+    pub(crate) crtc_id:         u32,
+    /// Index of the CRTC in the resources of the GPU.
+    pub(crate) crtc_index:      usize,
+    pub(crate) encoder:         Encoder,
+    /// Index of the encoder in the resources of the GPU, `None` when it was not listed.
+    pub(crate) encoder_index:   Option<usize>,
+}
+
+/// What a mode change replaces, kept by `checkpoint` so it can be rolled back.
+#[derive(Debug)]
+struct Saved {
+    mode:           u32,
+    framebuffers:   Vec<Framebuffer>,
+    blob:           Option<ModeBlob>,
+    flip:           FlipState,
+    modeset:        bool,
+}
+
+/// Property ids of the objects programmed by an atomic commit.
+#[derive(Debug)]
+struct AtomicObjects {
+    connector:  Properties,
+    crtc:       Properties,
+    plane:      Properties,
+}
+
+/// A connector driven through a CRTC with kernel mode setting.
+/// 
+/// The first frame after attaching buffers is shown with a modeset, the
+/// following ones are page flipped at vblank. Atomic commits are used when
+/// the driver supports them, legacy modesetting otherwise.
+/// 
+/// While powered down nothing is programmed, the last presented buffer is
+/// shown with a modeset when the output is powered on.
+/// 
+/// Transforms are applied by the `rotation` property of the primary plane,
+/// which only atomic drivers expose.
+/// 
+/// The CRTC can also drive the connectors of other outputs as clones, an
+/// output driven as a clone turns its own CRTC off until it is released.
+#[derive(Debug)]
+pub struct DrmOutput {
+    device:         DeviceRef,
+    kms:            Rc<Kms>,
+    mode:           u32,
+    framebuffers:   Vec<Framebuffer>,
+    connector:      Connector,
+    crtc:           CRTC,
+    atomic:         Option<AtomicObjects>,
+    blob:           Option<ModeBlob>,
+    modeset:        bool,
+    planes:         Vec<DrmPlane>,
+    edid:           Option<Edid>,
+    power:          PowerState,
+    /// Applied to the buffers by the primary plane.
+    transform:      SurfaceTransform,
+    /// Encoder routing the CRTC to the connector.
+    encoder:        Encoder,
+    /// Index of the encoder in the resources of the GPU, `None` when it was not listed.
+    encoder_index:  Option<usize>,
+    /// Framebuffers shown on overlay planes, by plane id.
+    overlays:       HashMap<u32, Framebuffer>,
+    /// Boxed so the address handed to the kernel stays valid.
+    flip:           Box<Cell<FlipState>>,
+    /// Framebuffers still scanned out while a mode change is tested.
+    saved:          Option<Saved>,
+    /// Connectors of other outputs driven by the CRTC, with their properties on atomic drivers.
+    clones:         Vec<(u32, Option<Properties>)>,
+    /// Set while the connector is driven by the CRTC of another output.
+    cloned:         bool,
+}
+
+impl DrmOutput {
+    pub(crate) fn new(device: DeviceRef, kms: Rc<Kms>, connector: Connector, route: Route, flags: &[ScreenFlags]) -> Result<Self, ErrorKind> {
+        debug!("Initializing output. - ConnectorID: {} - GPUID: {} - CRTCID: {} Flags: {:?}", connector.id(), device.id(), route.crtc_id, flags);
+
+        let crtc = CRTC::new(device.id(), route.crtc_id)?;
+
+        let mut mode_id = 0;
+
+        if flags.contains(&ScreenFlags::OptimalResolution) 
+        {
+            debug!("Detecting optimal resolution...");
+            let mut width = 0;
+            let mut height = 0;
+
+            for (id, mode) in connector.modes().iter().enumerate() {
+                let mode = unsafe { mode.as_ref().unwrap() };
+                if mode.hdisplay as u32 > width && mode.vdisplay as u32 > height {
+                    width = mode.hdisplay  as u32;
+                    height = mode.vdisplay  as u32;
+                    mode_id = id as u32;
+                }
+            }
+        }
+
+        let planes = enumerate_planes(device.id(), route.crtc_index);
+        let edid = Self::read_edid(device.id(), connector.id());
+
+        let atomic = match kms.is_atomic() {
+            true => Self::atomic_objects(device.id(), &connector, &crtc, &planes),
+            false => None,
+        };
+
+        Ok(Self {
+            device,
+            kms,
+            mode: mode_id,
+            framebuffers: Vec::new(),
+            connector,
+            crtc,
+            atomic,
+            blob: None,
+            modeset: true,
+            planes,
+            edid,
+            power: PowerState::On,
+            transform: SurfaceTransform::Normal,
+            encoder: route.encoder,
+            encoder_index: route.encoder_index,
+            overlays: HashMap::new(),
+            flip: Box::default(),
+            saved: None,
+            clones: Vec::new(),
+            cloned: false,
+        })
+    }
+
+    /// Reads the EDID blob of the connector, `None` when the monitor did not provide a valid one.
+    fn read_edid(gpu: GPUID, connector: u32) -> Option<Edid> {
+        let properties = Properties::new(gpu, connector, DRM_MODE_OBJECT_CONNECTOR).ok()?;
+        let blob = unsafe { drmModeGetPropertyBlob(gpu, properties.value("EDID")? as u32) };
+
+        if blob.is_null() {
+            return None;
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts((*blob).data as *const u8, (*blob).length as usize).to_vec() };
+        unsafe { drmModeFreePropertyBlob(blob) };
+
+        Edid::parse(&bytes).ok()
+    }
+
+    /// Looks up the properties programmed by atomic commits, `None` falls back to legacy modesetting.
+    fn atomic_objects(gpu: GPUID, connector: &Connector, crtc: &CRTC, planes: &[DrmPlane]) -> Option<AtomicObjects> {
+        let primary = primary_plane(planes)?;
+
+        let objects = AtomicObjects {
+            connector: Properties::new(gpu, connector.id(), DRM_MODE_OBJECT_CONNECTOR).ok()?,
+            crtc: Properties::new(gpu, crtc.id(), DRM_MODE_OBJECT_CRTC).ok()?,
+            plane: primary.properties()?.clone(),
+        };
+
+        Some(objects)
+    }
+
+    /// Adds `plane` scanning out the `src_width`x`src_height` `framebuffer` at `x`, `y` and `width`x`height` to the request.
+    #[allow(clippy::too_many_arguments)]
+    fn plane_state(plane: &Properties, request: &mut AtomicRequest, crtc: u32, framebuffer: u32, x: i32, y: i32, width: u32, height: u32, src_width: u32, src_height: u32) -> Result<(), ErrorKind> {
+        plane.set(request, "FB_ID", framebuffer as u64)?;
+        plane.set(request, "CRTC_ID", crtc as u64)?;
+        plane.set(request, "SRC_X", 0)?;
+        plane.set(request, "SRC_Y", 0)?;
+        plane.set(request, "SRC_W", (src_width as u64) << 16)?;
+        plane.set(request, "SRC_H", (src_height as u64) << 16)?;
+        plane.set(request, "CRTC_X", x as i64 as u64)?;
+        plane.set(request, "CRTC_Y", y as i64 as u64)?;
+        plane.set(request, "CRTC_W", width as u64)?;
+        plane.set(request, "CRTC_H", height as u64)
+    }
+
+    /// Programs `plane` to scan out `framebuffer`, or turns it off when `framebuffer` is `None`.
+    fn program_plane(&self, plane: &DrmPlane, framebuffer: Option<&Framebuffer>, x: i32, y: i32, width: u32, height: u32) -> Result<(), ErrorKind> {
+        let (crtc, fb) = match framebuffer {
+            Some(framebuffer) => (self.crtc.id(), framebuffer.id()),
+            None => (0, 0),
+        };
+
+        if let (true, Some(properties)) = (self.atomic.is_some(), plane.properties()) {
+            let mut request = AtomicRequest::new()?;
+            Self::plane_state(properties, &mut request, crtc, fb, x, y, width, height, width, height)?;
+            return request.commit(self.device.id(), 0, std::ptr::null_mut());
+        }
+
+        let result = unsafe {
+            drmModeSetPlane(self.device.id(), plane.id(), crtc, fb, 0, x, y, width, height, 0, 0, width << 16, height << 16)
+        };
+
+        if result != 0 {
+            let err = ErrorKind::PLANE_FAILED;
+            error!("Failed to set plane. - PlaneID: {} - ConnectorID: {} - ErrorKind: {:?}", plane.id(), self.connector.id(), err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Turns off every overlay plane.
+    fn clear_overlays(&mut self) {
+        let planes: Vec<u32> = self.overlays.keys().copied().collect();
+        for plane in planes {
+            self.clear_overlay(plane).unwrap_or_default();
+        }
+    }
+
+    /// Builds the atomic request driving the output at its mode with `framebuffer`.
+    fn atomic_modeset(&mut self, framebuffer: u32) -> Result<AtomicRequest, ErrorKind> {
+        let blob = ModeBlob::new(self.device.id(), self.mode_info())?;
+        let objects = self.atomic.as_ref().unwrap();
+        let (width, height) = (self.width(), self.height());
+        let (src_width, src_height) = self.transform.size(width, height);
+
+        let mut request = AtomicRequest::new()?;
+        objects.connector.set(&mut request, "CRTC_ID", self.crtc.id() as u64)?;
+        objects.crtc.set(&mut request, "MODE_ID", blob.id() as u64)?;
+        objects.crtc.set(&mut request, "ACTIVE", 1)?;
+        Self::plane_state(&objects.plane, &mut request, self.crtc.id(), framebuffer, 0, 0, width, height, src_width, src_height)?;
+
+        for properties in self.clones.iter().filter_map(|(_, properties)| properties.as_ref()) {
+            properties.set(&mut request, "CRTC_ID", self.crtc.id() as u64)?;
+        }
+
+        // Planes without the property can only scan out upright, see `set_transform`.
+        if objects.plane.id("rotation").is_some() {
+            objects.plane.set(&mut request, "rotation", self.transform.rotation())?;
+        }
+
+        self.blob = Some(blob);
+        Ok(request)
+    }
+
+    /// Puts the CRTC back in the state it had before the display took it over.
+    fn atomic_restore(&mut self) -> Result<(), ErrorKind> {
+        let objects = self.atomic.as_ref().unwrap();
+        let mut request = AtomicRequest::new()?;
+
+        if self.crtc.buffer_id() == 0 {
+            objects.connector.set(&mut request, "CRTC_ID", 0)?;
+            objects.crtc.set(&mut request, "ACTIVE", 0)?;
+            objects.crtc.set(&mut request, "MODE_ID", 0)?;
+            objects.plane.set(&mut request, "FB_ID", 0)?;
+            objects.plane.set(&mut request, "CRTC_ID", 0)?;
+            return request.commit(self.device.id(), DRM_MODE_ATOMIC_ALLOW_MODESET, std::ptr::null_mut());
+        }
+
+        let blob = ModeBlob::new(self.device.id(), &self.crtc.mode())?;
+        objects.connector.set(&mut request, "CRTC_ID", self.crtc.id() as u64)?;
+        objects.crtc.set(&mut request, "MODE_ID", blob.id() as u64)?;
+        objects.crtc.set(&mut request, "ACTIVE", 1)?;
+        Self::plane_state(&objects.plane, &mut request, self.crtc.id(), self.crtc.buffer_id(), 0, 0, self.crtc.width(), self.crtc.height(), self.crtc.width(), self.crtc.height())?;
+
+        if let Some(rotation) = objects.plane.value("rotation") {
+            objects.plane.set(&mut request, "rotation", rotation)?;
+        }
+
+        request.commit(self.device.id(), DRM_MODE_ATOMIC_ALLOW_MODESET, std::ptr::null_mut())
+    }
+
+    /// Turns the CRTC off and detaches the connector, so the CRTC of another output can drive it.
+    fn release_crtc(&mut self) -> Result<(), ErrorKind> {
+        self.clear_overlays();
+
+        let objects = match self.atomic.as_ref() {
+            Some(objects) => objects,
+            None => return self.crtc.disable(),
+        };
+
+        let mut request = AtomicRequest::new()?;
+        objects.connector.set(&mut request, "CRTC_ID", 0)?;
+        objects.crtc.set(&mut request, "ACTIVE", 0)?;
+        objects.crtc.set(&mut request, "MODE_ID", 0)?;
+        objects.plane.set(&mut request, "FB_ID", 0)?;
+        objects.plane.set(&mut request, "CRTC_ID", 0)?;
+        request.commit(self.device.id(), DRM_MODE_ATOMIC_ALLOW_MODESET, std::ptr::null_mut())
+    }
+
+    /// Sets the DPMS property of the connector, for drivers without atomic modesetting.
+    fn legacy_power(&self, state: PowerState) -> Result<(), ErrorKind> {
+        let property = Properties::new(self.device.id(), self.connector.id(), DRM_MODE_OBJECT_CONNECTOR)
+            .ok()
+            .and_then(|properties| properties.id("DPMS"));
+
+        let result = match property {
+            Some(property) => unsafe { drmModeConnectorSetProperty(self.device.id(), self.connector.id(), property, state as u64) },
+            None => -1,
+        };
+
+        if result != 0 {
+            let err = ErrorKind::POWER_FAILED;
+            error!("Failed to set DPMS. - ConnectorID: {} - State: {:?} - ErrorKind: {:?}", self.connector.id(), state, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn mode_info(&self) -> &drm::_drmModeModeInfo {
+        unsafe { self.connector.get_mode(self.mode).unwrap().as_ref().unwrap() }
+    }
+}
+
+impl Presenter for DrmOutput {
+    fn attach(&mut self, buffers: &[Buffer]) -> Result<(), ErrorKind> {
+        self.wait()?;
+
+        let mut framebuffers = Vec::with_capacity(buffers.len());
+        for buffer in buffers {
+            framebuffers.push(Framebuffer::opaque(self.device.id(), buffer)?);
+        }
+
+        self.framebuffers = framebuffers;
+        self.modeset = true;
+        self.flip.set(FlipState::default());
+        Ok(())
+    }
+
+    fn present(&mut self, index: usize) -> Result<(), ErrorKind> {
+        self.wait()?;
+
+        let framebuffer = self.framebuffers.get(index).ok_or(ErrorKind::BUFFER_OUT_OF_BOUNDS)?;
+
+        let framebuffer = framebuffer.id();
+
+        // Shown by the modeset made once the output is on and has its CRTC again.
+        if self.power != PowerState::On || self.cloned {
+            self.modeset = true;
+            self.flip.set(FlipState { displayed: Some(index), pending: None });
+            return Ok(());
+        }
+
+        if self.modeset {
+            match self.atomic.is_some() {
+                true => {
+                    let request = self.atomic_modeset(framebuffer)?;
+                    self.kms.modeset(request)?;
+                },
+                false => {
+                    let mode = self.connector.get_mode(self.mode).unwrap();
+                    let connectors: Vec<u32> = std::iter::once(self.connector.id()).chain(self.clones.iter().map(|(id, _)| *id)).collect();
+                    self.crtc.set_framebuffer(&connectors, mode, &self.framebuffers[index])?;
+                },
+            }
+
+            self.modeset = false;
+            self.flip.set(FlipState { displayed: Some(index), pending: None });
+            return Ok(());
+        }
+
+        let user_data = self.flip.as_ref() as *const Cell<FlipState> as *mut libc::c_void;
+        match self.atomic.as_ref() {
+            Some(objects) => {
+                let (src_width, src_height) = self.transform.size(self.width(), self.height());
+                let mut request = AtomicRequest::new()?;
+                Self::plane_state(&objects.plane, &mut request, self.crtc.id(), framebuffer, 0, 0, self.width(), self.height(), src_width, src_height)?;
+                request.commit(self.device.id(), DRM_MODE_PAGE_FLIP_EVENT | DRM_MODE_ATOMIC_NONBLOCK, user_data)?;
+            },
+            None => self.crtc.page_flip(&self.framebuffers[index], user_data)?,
+        }
+        self.flip.set(FlipState { pending: Some(index), ..self.flip.get() });
+        Ok(())
+    }
+
+    fn displayed(&self) -> Option<usize> {
+        self.flip.get().displayed
+    }
+
+    fn pending(&self) -> Option<usize> {
+        self.flip.get().pending
+    }
+
+    fn wait(&mut self) -> Result<(), ErrorKind> {
+        while self.flip.get().pending.is_some() {
+            let mut fd = libc::pollfd { fd: self.device.id(), events: libc::POLLIN, revents: 0 };
+            let ready = unsafe { libc::poll(&mut fd, 1, PAGE_FLIP_TIMEOUT) };
+
+            if ready < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            else if ready <= 0 {
+                let err = ErrorKind::PAGE_FLIP_FAILED;
+                error!("Page flip did not complete. - ConnectorID: {} - ErrorKind: {:?}", self.connector.id(), err);
+                self.flip.set(FlipState { pending: None, ..self.flip.get() });
+                return Err(err);
+            }
+
+            handle_events(self.device.id())?;
+        }
+
+        Ok(())
+    }
+
+    fn set_overlay(&mut self, buffer: &Buffer, x: i32, y: i32) -> Result<Option<u32>, ErrorKind> {
+        // Memory and shared memory buffers have no handle to scan out from.
+        if buffer.handle() == 0 {
+            return Ok(None);
+        }
+
+        let plane = self.planes.iter()
+            .find(|plane| plane.kind() == Planes::Overlay && plane.supports(buffer.format()) && !self.overlays.contains_key(&plane.id()))
+            .cloned();
+
+        let plane = match plane {
+            Some(plane) => plane,
+            None => return Ok(None),
+        };
+
+        self.wait()?;
+
+        let framebuffer = match Framebuffer::new(self.device.id(), buffer) {
+            Ok(framebuffer) => framebuffer,
+            Err(_) => return Ok(None),
+        };
+
+        if self.program_plane(&plane, Some(&framebuffer), x, y, buffer.width(), buffer.height()).is_err() {
+            debug!("Overlay plane rejected the buffer, compositing instead. - PlaneID: {} - ConnectorID: {}", plane.id(), self.connector.id());
+            return Ok(None);
+        }
+
+        debug!("Overlay plane set. - PlaneID: {} - ConnectorID: {} - Position: {}x{}", plane.id(), self.connector.id(), x, y);
+        self.overlays.insert(plane.id(), framebuffer);
+        Ok(Some(plane.id()))
+    }
+
+    fn clear_overlay(&mut self, plane: u32) -> Result<(), ErrorKind> {
+        let drm_plane = match self.planes.iter().find(|x| x.id() == plane && self.overlays.contains_key(&x.id())) {
+            Some(drm_plane) => drm_plane.clone(),
+            None => {
+                let err = ErrorKind::PLANE_FAILED;
+                error!("Overlay plane is not set. - PlaneID: {} - ErrorKind: {:?}", plane, err);
+                return Err(err);
+            }
+        };
+
+        self.wait()?;
+        self.program_plane(&drm_plane, None, 0, 0, 0, 0)?;
+        self.overlays.remove(&plane);
+        Ok(())
+    }
+
+    fn set_cursor(&mut self, buffer: Option<&Buffer>, hot_x: i32, hot_y: i32) -> Result<bool, ErrorKind> {
+        let buffer = match buffer {
+            Some(buffer) if buffer.handle() == 0 => return Ok(false),
+            Some(buffer) => buffer,
+            None => {
+                unsafe { drmModeSetCursor(self.device.id(), self.crtc.id(), 0, 0, 0) };
+                return Ok(false);
+            }
+        };
+
+        // Cursor planes are only listed with universal planes, so legacy drivers try the cursor anyway.
+        if self.kms.is_atomic() && !self.planes.iter().any(|plane| plane.kind() == Planes::Cursor) {
+            return Ok(false);
+        }
+
+        let result = unsafe {
+            drmModeSetCursor2(self.device.id(), self.crtc.id(), buffer.handle(), buffer.width(), buffer.height(), hot_x, hot_y)
+        };
+
+        if result != 0 {
+            debug!("Cursor plane rejected the buffer, compositing instead. - ConnectorID: {}", self.connector.id());
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn move_cursor(&mut self, x: i32, y: i32) -> Result<(), ErrorKind> {
+        if unsafe { drmModeMoveCursor(self.device.id(), self.crtc.id(), x, y) } != 0 {
+            let err = ErrorKind::PLANE_FAILED;
+            error!("Failed to move cursor. - ConnectorID: {} - ErrorKind: {:?}", self.connector.id(), err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn restore(&mut self) {
+        debug!("Restoring output. - ConnectorID: {} - GPUID: {}", self.connector.id(), self.device.id());
+        self.wait().unwrap_or_default();
+        self.clear_overlays();
+        unsafe { drmModeSetCursor(self.device.id(), self.crtc.id(), 0, 0, 0) };
+        self.power = PowerState::On;
+        self.transform = SurfaceTransform::Normal;
+
+        if self.atomic.is_some() {
+            if self.atomic_restore().is_ok() {
+                self.crtc.restore_gamma();
+                return;
+            }
+            debug!("Atomic restore failed, using legacy modesetting. - ConnectorID: {}", self.connector.id());
+        }
+
+        self.crtc.restore(&mut [self.connector.id()])
+    }
+}
+
+impl Output for DrmOutput {
+    fn id(&self) -> u32 {
+        self.connector.id()
+    }
+
+    fn connector_type(&self) -> ConnectorType {
+        self.connector.connector_type()
+    }
+
+    fn mm_width(&self) -> u32 {
+        self.connector.mmWidth()
+    }
+
+    fn mm_height(&self) -> u32 {
+        self.connector.mmHeight()
+    }
+
+    fn subpixel(&self) -> u32 {
+        self.connector.subpixel()
+    }
+
+    fn width(&self) -> u32 {
+        self.mode_info().hdisplay as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.mode_info().vdisplay as u32
+    }
+
+    fn refresh(&self) -> u32 {
+        self.mode_info().vrefresh
+    }
+
+    fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    fn modes(&self) -> Vec<Mode> {
+        self.connector.modes().iter().map(|mode| {
+            let mode = unsafe { mode.as_ref().unwrap() };
+            Mode {
+                width: mode.hdisplay as u32,
+                height: mode.vdisplay as u32,
+                refresh: mode.vrefresh,
+                preferred: mode.type_ & DRM_MODE_TYPE_PREFERRED != 0,
+                interlaced: mode.flags & DRM_MODE_FLAG_INTERLACE != 0,
+            }
+        }).collect()
+    }
+
+    fn edid(&self) -> Option<&Edid> {
+        self.edid.as_ref()
+    }
+
+    fn planes(&self) -> Vec<PlaneInfo> {
+        self.planes.iter().map(DrmPlane::info).collect()
+    }
+
+    fn gamma_size(&self) -> usize {
+        self.crtc.gamma_size().max(0) as usize
+    }
+
+    fn gamma(&self) -> Result<GammaRamp, ErrorKind> {
+        self.crtc.gamma()
+    }
+
+    fn set_gamma(&mut self, ramp: &GammaRamp) -> Result<(), ErrorKind> {
+        self.crtc.set_gamma(ramp)
+    }
+
+    fn power_state(&self) -> PowerState {
+        self.power
+    }
+
+    fn encoder_mask(&self) -> u32 {
+        self.encoder_index.map(|index| 1 << index).unwrap_or_default()
+    }
+
+    fn possible_clones(&self) -> u32 {
+        self.encoder.possible_clones()
+    }
+
+    /// Shown right away when a buffer is displayed, so connectors the CRTC cannot drive are reported.
+    /// 
+    /// Connectors left out are taken back by the modeset of their own output.
+    fn set_clones(&mut self, clones: &[u32]) -> Result<(), ErrorKind> {
+        self.wait()?;
+
+        let mut next = Vec::with_capacity(clones.len());
+        for id in clones {
+            let properties = match self.atomic.is_some() {
+                true => Some(Properties::new(self.device.id(), *id, DRM_MODE_OBJECT_CONNECTOR)?),
+                false => None,
+            };
+            next.push((*id, properties));
+        }
+
+        debug!("Setting clones. - ConnectorID: {} - Clones: {:?}", self.connector.id(), clones);
+        let previous = std::mem::replace(&mut self.clones, next);
+        self.modeset = true;
+
+        let displayed = match self.flip.get().displayed {
+            Some(displayed) => displayed,
+            None => return Ok(()),
+        };
+
+        if let Err(err) = self.present(displayed) {
+            error!("Failed to drive the clones. - ConnectorID: {} - Clones: {:?} - ErrorKind: {:?}", self.connector.id(), clones, err);
+            self.clones = previous;
+            self.present(displayed).unwrap_or_default();
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn set_cloned(&mut self, cloned: bool) -> Result<(), ErrorKind> {
+        if cloned == self.cloned {
+            return Ok(());
+        }
+
+        debug!("Setting cloned. - ConnectorID: {} - Cloned: {}", self.connector.id(), cloned);
+        self.wait()?;
+
+        if cloned {
+            self.release_crtc()?;
+        }
+
+        self.cloned = cloned;
+        self.modeset = true;
+
+        if let (false, Some(displayed)) = (cloned, self.flip.get().displayed) {
+            self.present(displayed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Atomic drivers only turn the CRTC off, standby and suspend are the same as off.
+    /// 
+    /// While cloned the state is only kept, the CRTC of the other output drives the monitor.
+    fn set_power_state(&mut self, state: PowerState) -> Result<(), ErrorKind> {
+        if state == self.power {
+            return Ok(());
+        }
+
+        if self.cloned {
+            self.power = state;
+            return Ok(());
+        }
+
+        debug!("Setting power state. - ConnectorID: {} - State: {:?}", self.connector.id(), state);
+        self.wait()?;
+
+        match self.atomic.as_ref() {
+            Some(objects) => {
+                let mut request = AtomicRequest::new()?;
+                objects.crtc.set(&mut request, "ACTIVE", (state == PowerState::On) as u64)?;
+                request.commit(self.device.id(), DRM_MODE_ATOMIC_ALLOW_MODESET, std::ptr::null_mut())
+                    .map_err(|_| ErrorKind::POWER_FAILED)?;
+            },
+            None => self.legacy_power(state)?,
+        }
+
+        self.power = state;
+
+        // Frames swapped while powered down were not programmed.
+        if let (PowerState::On, true, Some(displayed)) = (state, self.modeset, self.flip.get().displayed) {
+            self.present(displayed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Falls back to scanning out upright when the primary plane cannot apply `transform`.
+    fn set_transform(&mut self, transform: SurfaceTransform) -> Result<bool, ErrorKind> {
+        let supported = transform == SurfaceTransform::Normal || (self.atomic.is_some() && primary_plane(&self.planes)
+            .is_some_and(|plane| plane.supports_rotation(transform.rotation())));
+
+        let applied = match supported {
+            true => transform,
+            false => SurfaceTransform::Normal,
+        };
+
+        if applied != self.transform {
+            debug!("Setting plane rotation. - ConnectorID: {} - Transform: {:?}", self.connector.id(), applied);
+            self.wait()?;
+            self.transform = applied;
+            self.modeset = true;
+        }
+
+        Ok(supported)
+    }
+
+    fn set_mode(&mut self, index: u32) -> Result<(), ErrorKind> {
+        if self.connector.get_mode(index).is_none() {
+            return Err(ErrorKind::MODE_NOT_FOUND);
+        }
+
+        debug!("Setting mode. - ConnectorID: {} - Mode: {}", self.connector.id(), index);
+        self.wait()?;
+        self.mode = index;
+        self.modeset = true;
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> Result<(), ErrorKind> {
+        self.wait()?;
+        self.saved = Some(Saved {
+            mode: self.mode,
+            framebuffers: std::mem::take(&mut self.framebuffers),
+            blob: self.blob.take(),
+            flip: self.flip.get(),
+            modeset: self.modeset,
+        });
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        self.saved = None;
+    }
+
+    fn rollback(&mut self) -> Result<(), ErrorKind> {
+        let saved = match self.saved.take() {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+
+        debug!("Rolling back mode. - ConnectorID: {} - Mode: {}", self.connector.id(), saved.mode);
+        self.mode = saved.mode;
+        self.framebuffers = saved.framebuffers;
+        self.blob = saved.blob;
+        self.flip.set(saved.flip);
+        self.modeset = saved.modeset;
+
+        // An atomic modeset is applied whole or not at all, a legacy one may
+        // already have replaced the picture.
+        match (self.atomic.is_none(), saved.flip.displayed) {
+            (true, Some(index)) => {
+                self.modeset = true;
+                self.present(index)
+            },
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use drm::*;
+use exodus_common::{enums::{PixelFormat, Planes}, graphics::{backend::PlaneInfo, device::GPUID}, types::PlanePtr, debug};
+use super::atomic::Properties;
+
+/// Values of the `type` property of planes.
+const PLANE_TYPE_OVERLAY: u64 = 0;
+const PLANE_TYPE_PRIMARY: u64 = 1;
+const PLANE_TYPE_CURSOR: u64 = 2;
+
+/// A hardware plane that can scan out from a CRTC.
+#[derive(Debug, Clone)]
+pub struct DrmPlane {
+    id:         u32,
+    kind:       Planes,
+    formats:    Vec<u32>,
+    /// `DRM_MODE_ROTATE_*` and `DRM_MODE_REFLECT_*` bits of the `rotation` property.
+    rotations:  u64,
+    properties: Option<Properties>,
+}
+
+impl DrmPlane {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn kind(&self) -> Planes {
+        self.kind
+    }
+
+    /// Fourcc codes of the pixel formats the plane can scan out.
+    pub fn formats(&self) -> &[u32] {
+        &self.formats
+    }
+
+    pub fn supports(&self, format: PixelFormat) -> bool {
+        self.formats.contains(&format.fourcc())
+    }
+
+    /// Returns `true` when the `rotation` property accepts every bit of `rotation`.
+    pub fn supports_rotation(&self, rotation: u64) -> bool {
+        self.rotations & rotation == rotation
+    }
+
+    /// Property ids, `None` when the driver does not expose them.
+    pub fn properties(&self) -> Option<&Properties> {
+        self.properties.as_ref()
+    }
+
+    pub fn info(&self) -> PlaneInfo {
+        PlaneInfo { id: self.id, kind: self.kind, formats: self.formats.clone() }
+    }
+}
+
+/// The primary plane of `planes`, scanning out the buffers of the screen.
+pub fn primary_plane(planes: &[DrmPlane]) -> Option<&DrmPlane> {
+    planes.iter().find(|plane| plane.kind() == Planes::Background)
+}
+
+/// Maps the DRM plane type to `Planes`, the primary plane is the background.
+fn plane_kind(plane_type: Option<u64>) -> Planes {
+    match plane_type {
+        Some(PLANE_TYPE_PRIMARY)    => Planes::Background,
+        Some(PLANE_TYPE_CURSOR)     => Planes::Cursor,
+        Some(PLANE_TYPE_OVERLAY)    => Planes::Overlay,
+        // Without universal planes only overlays are listed.
+        _                           => Planes::Overlay,
+    }
+}
+
+/// Bits the `rotation` property accepts, planes without it can only scan out upright.
+fn supported_rotations(gpu: GPUID, properties: Option<&Properties>) -> u64 {
+    let property = match properties.and_then(|x| x.id("rotation")) {
+        Some(id) => unsafe { drmModeGetProperty(gpu, id) },
+        None => return DRM_MODE_ROTATE_0 as u64,
+    };
+
+    if property.is_null() {
+        return DRM_MODE_ROTATE_0 as u64;
+    }
+
+    // Bitmask properties list the bit index of each value.
+    let rotations = unsafe {
+        let enums = std::slice::from_raw_parts((*property).enums, (*property).count_enums as usize);
+        enums.iter().fold(0, |rotations, x| rotations | 1 << x.value)
+    };
+
+    unsafe { drmModeFreeProperty(property) };
+    rotations
+}
+
+/// Lists the planes that can scan out from the CRTC at `crtc_index`.
+pub fn enumerate_planes(gpu: GPUID, crtc_index: usize) -> Vec<DrmPlane> {
+    let resources = unsafe { drmModeGetPlaneResources(gpu) };
+    if resources.is_null() {
+        return Vec::new();
+    }
+
+    let mut planes = Vec::new();
+
+    unsafe {
+        let list = resources.as_ref().unwrap();
+        for i in 0..list.count_planes as usize {
+            let plane: PlanePtr = drmModeGetPlane(gpu, *list.planes.add(i));
+            if plane.is_null() {
+                continue;
+            }
+
+            let possible = (*plane).possible_crtcs & (1 << crtc_index) != 0;
+            let id = (*plane).plane_id;
+            let formats = std::slice::from_raw_parts((*plane).formats, (*plane).count_formats as usize).to_vec();
+            drmModeFreePlane(plane);
+
+            if !possible {
+                continue;
+            }
+
+            let properties = Properties::new(gpu, id, DRM_MODE_OBJECT_PLANE).ok();
+            let kind = plane_kind(properties.as_ref().and_then(|x| x.value("type")));
+            let rotations = supported_rotations(gpu, properties.as_ref());
+
+            debug!("Plane found. - PlaneID: {} - Kind: {:?} - Formats: {} - CrtcIndex: {}", id, kind, formats.len(), crtc_index);
+            planes.push(DrmPlane { id, kind, formats, rotations, properties });
+        }
+
+        drmModeFreePlaneResources(resources);
+    }
+
+    planes
+}
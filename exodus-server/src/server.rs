@@ -0,0 +1,204 @@
+use std::{collections::HashMap, os::fd::{AsRawFd, RawFd}, time::Instant};
+use exodus_common::{info, debug, error};
+use exodus_errors::ErrorKind;
+use exodus_protocols::protocol_code::ProtocolCode;
+use crate::{client::Entity, display::Display, event_loop::{Event, EventLoop, SignalFd}, hotplug::{HotplugMonitor, HOTPLUG_POLL_INTERVAL}, protocol_handler::ProtocolHandler};
+
+const LISTENER_TOKEN: u64 = u64::MAX;
+const SIGNAL_TOKEN: u64 = u64::MAX - 1;
+const HOTPLUG_TOKEN: u64 = u64::MAX - 2;
+/// Set on the token of a GPU event fd, entity tokens are their bare fd.
+const GPU_TOKEN: u64 = 1 << 32;
+const MAX_EVENTS: usize = 64;
+
+/// Runs the display: accepts entities, dispatches their requests and stops on SIGINT/SIGTERM.
+/// 
+/// Monitors are detected from kernel uevents, or by probing the outputs every
+/// `HOTPLUG_POLL_INTERVAL` when uevents are not available. The screens are
+/// powered off once the idle timeout of the display elapses.
+#[derive(Debug)]
+pub struct Server {
+    display:    Display,
+    handler:    ProtocolHandler,
+    entities:   HashMap<RawFd, Entity>,
+    events:     EventLoop,
+    signals:    SignalFd,
+    hotplug:    Option<HotplugMonitor>,
+    probed:     Instant,
+}
+
+impl Server {
+    pub fn new(display: Display) -> Result<Self, ErrorKind> {
+        let signals = SignalFd::new(&[libc::SIGINT, libc::SIGTERM])?;
+        let events = EventLoop::new(MAX_EVENTS)?;
+
+        events.add(display.as_raw_fd(), LISTENER_TOKEN, false)?;
+        events.add(signals.as_raw_fd(), SIGNAL_TOKEN, false)?;
+
+        for fd in display.gpus().iter().filter_map(|gpu| gpu.event_fd()) {
+            events.add(fd, GPU_TOKEN | fd as u64, false)?;
+        }
+
+        let hotplug = match HotplugMonitor::new() {
+            Ok(hotplug) => {
+                events.add(hotplug.as_raw_fd(), HOTPLUG_TOKEN, false)?;
+                Some(hotplug)
+            },
+            Err(_) => {
+                info!("Uevents not available, probing screens every {:?}.", HOTPLUG_POLL_INTERVAL);
+                None
+            },
+        };
+
+        Ok(Self { display, handler: ProtocolHandler::new(), entities: HashMap::new(), events, signals, hotplug, probed: Instant::now() })
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn handler_mut(&mut self) -> &mut ProtocolHandler {
+        &mut self.handler
+    }
+
+    /// Runs until a termination signal arrives, then disconnects every entity
+    /// and removes the display socket.
+    pub fn run(&mut self) -> Result<(), ErrorKind> {
+        info!("Display {} running.", self.display.id());
+
+        let result = self.event_loop();
+
+        self.entities.clear();
+        self.display.dispose();
+
+        info!("Display {} stopped.", self.display.id());
+        result
+    }
+
+    fn event_loop(&mut self) -> Result<(), ErrorKind> {
+        loop {
+            let probe = match self.hotplug {
+                Some(_) => None,
+                None => Some(HOTPLUG_POLL_INTERVAL.saturating_sub(self.probed.elapsed())),
+            };
+
+            let timeout = match (probe, self.display.idle_remaining()) {
+                (Some(probe), Some(idle)) => Some(probe.min(idle)),
+                (probe, idle) => probe.or(idle),
+            };
+
+            for event in self.events.wait(timeout)? {
+                match event.token {
+                    SIGNAL_TOKEN => {
+                        if let Some(signal) = self.signals.read() {
+                            info!("Received signal {}, shutting down.", signal);
+                            return Ok(());
+                        }
+                    },
+                    LISTENER_TOKEN => self.accept()?,
+                    HOTPLUG_TOKEN => {
+                        if self.hotplug.as_mut().is_some_and(|hotplug| hotplug.read()) {
+                            self.display.hotplug();
+                        }
+                    },
+                    token if token & GPU_TOKEN != 0 => self.gpu_event((token & !GPU_TOKEN) as RawFd),
+                    token => self.entity_event(token as RawFd, event),
+                }
+            }
+
+            if self.hotplug.is_none() && self.probed.elapsed() >= HOTPLUG_POLL_INTERVAL {
+                self.display.hotplug();
+                self.probed = Instant::now();
+            }
+
+            self.display.idle();
+
+            self.broadcast();
+        }
+    }
+
+    /// Sends the events queued on the display to the entities that negotiated them.
+    fn broadcast(&mut self) {
+        for (origin, event) in self.display.take_events() {
+            let code = ProtocolCode::from(event.code().unwrap_or_default());
+            let mut failed = Vec::new();
+
+            for (fd, entity) in self.entities.iter_mut() {
+                if entity.id() == origin || !entity.supports(code) {
+                    continue;
+                }
+
+                let result = entity.send(event.clone())
+                    .and_then(|_| self.events.modify(*fd, *fd as u64, entity.has_pending_output()));
+                if result.is_err() {
+                    failed.push(*fd);
+                }
+            }
+
+            failed.into_iter().for_each(|fd| self.remove(fd));
+        }
+    }
+
+    fn gpu_event(&mut self, fd: RawFd) {
+        let gpu = self.display.gpus_mut().iter_mut().find(|gpu| gpu.event_fd() == Some(fd));
+
+        if let Some(gpu) = gpu {
+            if let Err(err) = gpu.dispatch_events() {
+                error!("Failed to dispatch gpu events. - GPUID: {} - ErrorKind: {:?}", gpu.id(), err);
+            }
+        }
+    }
+
+    fn accept(&mut self) -> Result<(), ErrorKind> {
+        while let Some(entity) = self.display.accept() {
+            let fd = entity.as_raw_fd();
+            self.events.add(fd, fd as u64, false)?;
+
+            debug!("Entity connected. - ID: {}", entity.id());
+            self.entities.insert(fd, entity);
+        }
+
+        Ok(())
+    }
+
+    fn entity_event(&mut self, fd: RawFd, event: Event) {
+        let Some(entity) = self.entities.get_mut(&fd) else {
+            return;
+        };
+
+        let mut result = Ok(());
+        if event.writable {
+            result = entity.flush();
+        }
+        if result.is_ok() && (event.readable || event.hangup) {
+            result = self.handler.handle(&mut self.display, entity);
+        }
+        if result.is_ok() {
+            result = self.events.modify(fd, fd as u64, entity.has_pending_output());
+        }
+
+        match result {
+            Ok(()) => {},
+            Err(ErrorKind::CONNECTION_CLOSED) => self.remove(fd),
+            Err(err) => {
+                error!("Failed to serve entity, disconnecting. - ErrorKind: {:?}", err);
+                self.remove(fd);
+            },
+        }
+    }
+
+    /// Disconnects an entity and turns off the overlay planes showing its buffers.
+    fn remove(&mut self, fd: RawFd) {
+        self.events.remove(fd).unwrap_or_default();
+
+        let Some(entity) = self.entities.remove(&fd) else {
+            return;
+        };
+
+        for ((gpu, screen), plane) in entity.overlays() {
+            if let Some(screen) = self.display.get_gpu_mut(gpu).and_then(|gpu| gpu.get_screen_mut(screen)) {
+                screen.hide_overlay(plane).unwrap_or_default();
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{cell::Cell, os::fd::{AsRawFd, OwnedFd}, rc::Rc, sync::{Once, atomic::{Ordering, compiler_fence}}};
+use exodus_common::{enums::PixelFormat, graphics::buffer::PixelSource, error};
+use exodus_errors::ErrorKind;
+
+/// Largest pool an entity can create.
+pub const MAX_POOL_SIZE: u32 = 1 << 30;
+
+/// Most pools an entity can hold at once.
+pub const MAX_POOLS: usize = 64;
+
+/// Most buffers an entity can hold at once, shared memory and imported ones.
+pub const MAX_BUFFERS: usize = 256;
+
+static HANDLER: Once = Once::new();
+
+thread_local! {
+    /// Range of the pool being read, a `SIGBUS` inside it means the entity truncated the file.
+    ///
+    /// Kept per thread, the signal is delivered to the thread that faulted.
+    static GUARD_START: Cell<usize> = const { Cell::new(0) };
+    static GUARD_END: Cell<usize> = const { Cell::new(0) };
+    static FAULTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Shared memory sent by an entity, mapped read-only.
+#[derive(Debug)]
+pub struct ShmPool {
+    fd:         OwnedFd,
+    map:        *mut u8,
+    size:       usize,
+    /// Set once a read found the file truncated, the mapping is then zeroed memory.
+    truncated:  Cell<bool>,
+}
+
+impl ShmPool {
+    /// Maps the first `size` bytes of `fd`, the file must be at least that long.
+    pub fn new(fd: OwnedFd, size: u32) -> Result<Self, ErrorKind> {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } < 0 {
+            let err = ErrorKind::SHM_INVALID;
+            error!("Failed to stat pool. - ErrorKind: {:?}", err);
+            return Err(err);
+        }
+
+        if size == 0 || size > MAX_POOL_SIZE || (stat.st_size as u64) < size as u64 {
+            let err = ErrorKind::SHM_INVALID;
+            error!("Invalid pool size. - Size: {} - File: {} - ErrorKind: {:?}", size, stat.st_size, err);
+            return Err(err);
+        }
+
+        let map = unsafe {
+            libc::mmap(std::ptr::null_mut(), size as usize, libc::PROT_READ, libc::MAP_SHARED, fd.as_raw_fd(), 0)
+        };
+
+        if map == libc::MAP_FAILED {
+            let err = ErrorKind::SHM_INVALID;
+            error!("Failed to map pool. - Size: {} - ErrorKind: {:?}", size, err);
+            return Err(err);
+        }
+
+        HANDLER.call_once(install_sigbus_handler);
+        Ok(Self { fd, map: map as *mut u8, size: size as usize, truncated: Cell::new(false) })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Copies each `(offset, target, length)` range of the pool to its target.
+    ///
+    /// Fails with `SHM_TRUNCATED` when the entity shrank the file below the
+    /// bytes read, the pool then stays unusable.
+    fn copy(&self, reads: impl Iterator<Item = (usize, *mut u8, usize)>) -> Result<(), ErrorKind> {
+        if self.truncated.get() {
+            return Err(ErrorKind::SHM_TRUNCATED);
+        }
+
+        FAULTED.set(false);
+        GUARD_START.set(self.map as usize);
+        GUARD_END.set(self.map as usize + self.size);
+        compiler_fence(Ordering::SeqCst);
+
+        for (offset, target, length) in reads {
+            unsafe { std::ptr::copy_nonoverlapping(self.map.add(offset), target, length) };
+        }
+
+        compiler_fence(Ordering::SeqCst);
+        GUARD_START.set(0);
+        GUARD_END.set(0);
+
+        if FAULTED.get() {
+            self.truncated.set(true);
+            let err = ErrorKind::SHM_TRUNCATED;
+            error!("Pool truncated by its entity. - Size: {} - ErrorKind: {:?}", self.size, err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+impl AsRawFd for ShmPool {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Drop for ShmPool {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map as *mut libc::c_void, self.size) };
+    }
+}
+
+/// Pixels of an entity inside a pool, kept alive by the buffer.
+#[derive(Debug)]
+pub struct ShmBuffer {
+    pool:   Rc<ShmPool>,
+    offset: usize,
+    width:  u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl ShmBuffer {
+    /// Describes a `width`x`height` buffer at `offset` of `pool`, rows are `stride` bytes apart.
+    pub fn new(pool: Rc<ShmPool>, offset: u32, width: u32, height: u32, stride: u32, format: PixelFormat) -> Result<Self, ErrorKind> {
+        let pixel = format.size() as u64;
+        let (offset, width, height, stride) = (offset as u64, width as u64, height as u64, stride as u64);
+
+        let valid = width > 0 && height > 0
+            && offset % pixel == 0
+            && stride % pixel == 0
+            && stride >= width * pixel
+            && offset + stride * (height - 1) + width * pixel <= pool.size() as u64;
+
+        if !valid {
+            let err = ErrorKind::SHM_INVALID;
+            error!("Buffer does not fit in its pool. - Offset: {} - Width: {} - Height: {} - Stride: {} - Pool: {} - ErrorKind: {:?}",
+                offset, width, height, stride, pool.size(), err);
+            return Err(err);
+        }
+
+        Ok(Self { pool, offset: offset as usize, width: width as u32, height: height as u32, stride: stride as usize, format })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Reads the `width`x`height` rectangle at `x`, `y` of the buffer.
+    pub fn read(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u32>, ErrorKind> {
+        if x as u64 + width as u64 > self.width as u64 || y as u64 + height as u64 > self.height as u64 {
+            error!("Buffer is out of bounds. - ErrorKind: {:?}", ErrorKind::BUFFER_OUT_OF_BOUNDS);
+            return Err(ErrorKind::BUFFER_OUT_OF_BOUNDS);
+        }
+
+        let mut pixels = vec![0u32; (width * height) as usize];
+        let row = width as usize * 4;
+        let reads = (0..height as usize).map(|index| {
+            let offset = self.offset + (y as usize + index) * self.stride + x as usize * 4;
+            (offset, unsafe { (pixels.as_mut_ptr() as *mut u8).add(index * row) }, row)
+        });
+
+        self.pool.copy(reads)?;
+        Ok(pixels)
+    }
+}
+
+impl PixelSource for ShmBuffer {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn read(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u32>, ErrorKind> {
+        ShmBuffer::read(self, x, y, width, height)
+    }
+}
+
+fn install_sigbus_handler() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = sigbus_handler as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        if libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut()) < 0 {
+            error!("Failed to install SIGBUS handler. - ErrorKind: {:?}", ErrorKind::SIGNAL_HANDLER_FAILED);
+        }
+    }
+}
+
+/// Maps zeroed memory over a pool truncated while being read, so the read completes.
+///
+/// Faults outside of a pool are not ours, the default action is restored and
+/// the faulting access is retried.
+extern "C" fn sigbus_handler(_signal: libc::c_int, info: *mut libc::siginfo_t, _context: *mut libc::c_void) {
+    let address = unsafe { (*info).si_addr() } as usize;
+    let start = GUARD_START.get();
+    let end = GUARD_END.get();
+
+    if address < start || address >= end {
+        unsafe { libc::signal(libc::SIGBUS, libc::SIG_DFL) };
+        return;
+    }
+
+    let map = unsafe {
+        libc::mmap(start as *mut libc::c_void, end - start, libc::PROT_READ, libc::MAP_PRIVATE | libc::MAP_FIXED | libc::MAP_ANONYMOUS, -1, 0)
+    };
+
+    if map == libc::MAP_FAILED {
+        unsafe { libc::signal(libc::SIGBUS, libc::SIG_DFL) };
+        return;
+    }
+
+    FAULTED.set(true);
+}